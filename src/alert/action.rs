@@ -0,0 +1,356 @@
+//! Actions triggered when an alert fires or resolves.
+
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::alert::AlertEvent;
+use crate::{Error, Result};
+
+pub trait Action: Send {
+    fn run(&self, event: &AlertEvent) -> Result<()>;
+}
+
+/// Runs a shell command with the event in `MONE_ALERT_*` environment variables.
+pub struct CommandAction {
+    command: String,
+}
+
+impl CommandAction {
+    pub fn new(command: &str) -> CommandAction {
+        CommandAction {
+            command: command.to_string(),
+        }
+    }
+}
+
+impl Action for CommandAction {
+    fn run(&self, event: &AlertEvent) -> Result<()> {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env("MONE_ALERT_STATE", event.state.to_string())
+            .env("MONE_ALERT_RULE", event.rule.to_string())
+            .env("MONE_ALERT_INTERFACE", &event.interface)
+            .env("MONE_ALERT_VALUE", event.value.to_string())
+            .status()?;
+        if !status.success() {
+            return Err(Error::AlertError(format!(
+                "`{}` exited with {}",
+                self.command, status
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Appends one line per event to a file.
+pub struct LogAction {
+    path: PathBuf,
+}
+
+impl LogAction {
+    pub fn new<P: AsRef<Path>>(path: P) -> LogAction {
+        LogAction {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn open(&self) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+    }
+}
+
+impl Action for LogAction {
+    fn run(&self, event: &AlertEvent) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        writeln!(self.open()?, "{} {}", timestamp, event)?;
+        Ok(())
+    }
+}
+
+/// POSTs the event as JSON to an `http://` URL.
+pub struct WebhookAction {
+    host: String,
+    port: u16,
+    path: String,
+    timeout: Duration,
+}
+
+impl WebhookAction {
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn new(url: &str) -> Result<WebhookAction> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => {
+                return Err(Error::ParseError(format!(
+                    "webhook URL must start with `http://`, got `{}`",
+                    url
+                )))
+            }
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        // an IPv6 literal is bracketed, as in `http://[::1]:8080/`
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => match bracketed.split_once(']') {
+                Some((host, port)) if host.parse::<Ipv6Addr>().is_ok() => {
+                    (host, port.strip_prefix(':').unwrap_or(port))
+                }
+                _ => return Err(Error::ParseError(format!("invalid IPv6 host in `{}`", url))),
+            },
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, port),
+                None => (authority, ""),
+            },
+        };
+        let port = match port {
+            "" => 80,
+            port => port
+                .parse()
+                .map_err(|_| Error::ParseError(format!("invalid port in `{}`", url)))?,
+        };
+        Ok(WebhookAction {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            timeout: Self::TIMEOUT,
+        })
+    }
+
+    /// Bounds connecting, sending and receiving the response by `timeout` instead of
+    /// `TIMEOUT`.
+    pub fn with_timeout(mut self, timeout: Duration) -> WebhookAction {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Value of the `Host` header: the host, bracketed if it is an IPv6 literal, with the port
+    /// unless it is the default one.
+    fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        match self.port {
+            80 => host,
+            port => format!("{}:{}", host, port),
+        }
+    }
+
+    /// Resolves the host on a helper thread, so that a slow DNS lookup is bounded by the
+    /// timeout too. A lookup which did not return in time is left running detached.
+    fn resolve(&self) -> Result<Vec<SocketAddr>> {
+        let (tx, rx) = mpsc::channel();
+        let (host, port) = (self.host.clone(), self.port);
+        thread::spawn(move || {
+            let addrs = (host.as_str(), port)
+                .to_socket_addrs()
+                .map(|addrs| addrs.collect::<Vec<_>>());
+            tx.send(addrs).unwrap_or(());
+        });
+        match rx.recv_timeout(self.timeout) {
+            Ok(addrs) => Ok(addrs?),
+            Err(_) => Err(Error::AlertError(format!(
+                "resolving `{}` timed out",
+                self.host
+            ))),
+        }
+    }
+
+    /// Connects to the first reachable address of the host. Every step is bounded by the
+    /// timeout, as the actions run one after another on a single thread.
+    fn connect(&self) -> Result<TcpStream> {
+        let mut last_err = None;
+        for addr in self.resolve()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(self.timeout))?;
+                    stream.set_write_timeout(Some(self.timeout))?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(match last_err {
+            Some(err) => err.into(),
+            None => Error::AlertError(format!("no address found for `{}`", self.host)),
+        })
+    }
+}
+
+impl Action for WebhookAction {
+    fn run(&self, event: &AlertEvent) -> Result<()> {
+        let body = json!({
+            "state": event.state.to_string(),
+            "rule": event.rule.to_string(),
+            "interface": event.interface,
+            "value": event.value,
+        })
+        .to_string();
+
+        let mut stream = self.connect()?;
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host_header(),
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes())?;
+
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line)?;
+        match status_line.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(Error::AlertError(format!(
+                "webhook returned `{}`",
+                status_line.trim()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::AlertState;
+    use crate::test_utils::temp_path;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::time::Instant;
+    use std::{fs, thread};
+
+    fn event() -> AlertEvent {
+        AlertEvent {
+            state: AlertState::Firing,
+            rule: "eth0 rx > 1 KiB".parse().unwrap(),
+            interface: "eth0".to_string(),
+            value: 2048.0,
+        }
+    }
+
+    #[test]
+    fn test_command_action() {
+        let path = temp_path("command");
+        let command = format!(
+            "echo \"$MONE_ALERT_STATE $MONE_ALERT_INTERFACE $MONE_ALERT_VALUE\" > {}",
+            path.display()
+        );
+        CommandAction::new(&command).run(&event()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "firing eth0 2048\n");
+        fs::remove_file(&path).unwrap();
+
+        assert!(CommandAction::new("exit 3").run(&event()).is_err());
+    }
+
+    #[test]
+    fn test_log_action() {
+        let path = temp_path("log");
+        let action = LogAction::new(&path);
+        action.run(&event()).unwrap();
+        action.run(&event()).unwrap();
+        let log = fs::read_to_string(&path).unwrap();
+        assert_eq!(log.lines().count(), 2);
+        assert!(log
+            .lines()
+            .all(|l| l.ends_with("firing: eth0 rx > 1 KiB (eth0: 2048)")));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_webhook_url() {
+        let action = WebhookAction::new("http://example.com:8080/hooks/mone").unwrap();
+        assert_eq!(action.host, "example.com");
+        assert_eq!(action.port, 8080);
+        assert_eq!(action.path, "/hooks/mone");
+        let action = WebhookAction::new("http://example.com").unwrap();
+        assert_eq!(action.port, 80);
+        assert_eq!(action.path, "/");
+        assert!(WebhookAction::new("https://example.com").is_err());
+
+        let action = WebhookAction::new("http://[::1]:8080/alert").unwrap();
+        assert_eq!(action.host, "::1");
+        assert_eq!(action.port, 8080);
+        assert_eq!(action.host_header(), "[::1]:8080");
+        let action = WebhookAction::new("http://[::1]").unwrap();
+        assert_eq!(action.port, 80);
+        assert_eq!(action.host_header(), "[::1]");
+        assert_eq!(
+            WebhookAction::new("http://example.com:8080/")
+                .unwrap()
+                .host_header(),
+            "example.com:8080"
+        );
+        assert_eq!(
+            WebhookAction::new("http://example.com/")
+                .unwrap()
+                .host_header(),
+            "example.com"
+        );
+        assert!(WebhookAction::new("http://[example.com]/").is_err());
+        assert!(WebhookAction::new("http://::1/").is_err());
+    }
+
+    #[test]
+    fn test_webhook_action() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buf = [0; 4096];
+            while !request.ends_with(b"}") {
+                let len = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..len]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let action = WebhookAction::new(&format!("http://127.0.0.1:{}/alert", port)).unwrap();
+        action.run(&event()).unwrap();
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /alert HTTP/1.1\r\n"));
+        assert!(request.contains(&format!("\r\nHost: 127.0.0.1:{}\r\n", port)));
+        let body = request.split("\r\n\r\n").nth(1).unwrap();
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["state"], "firing");
+        assert_eq!(body["interface"], "eth0");
+        assert_eq!(body["value"], 2048.0);
+    }
+
+    #[test]
+    fn test_webhook_action_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let action = WebhookAction::new(&format!("http://127.0.0.1:{}/alert", port))
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+        // the connection is queued by the listener, but the request is never answered
+        let started_at = Instant::now();
+        assert!(action.run(&event()).is_err());
+        assert!(started_at.elapsed() < Duration::from_secs(2));
+        drop(listener);
+    }
+}
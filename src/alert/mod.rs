@@ -0,0 +1,339 @@
+//! Threshold alerts on the stats collected by a Reader.
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+use std::{fmt, mem};
+
+use crate::{InterfaceInfo, InterfaceStats};
+
+pub use self::action::{Action, CommandAction, LogAction, WebhookAction};
+pub use self::rule::{AlertMetric, Comparison, Rule};
+
+pub mod action;
+pub mod rule;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+impl fmt::Display for AlertState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlertState::Firing => write!(f, "firing"),
+            AlertState::Resolved => write!(f, "resolved"),
+        }
+    }
+}
+
+/// An alert which fired or resolved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlertEvent {
+    pub state: AlertState,
+    pub rule: Rule,
+    pub interface: String,
+    /// The value which triggered the event
+    pub value: f64,
+}
+
+impl fmt::Display for AlertEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({}: {})",
+            self.state, self.rule, self.interface, self.value
+        )
+    }
+}
+
+/// An alert which is currently firing, passed to Writers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FiringAlert {
+    pub rule: Rule,
+    /// Index of the interface in `InterfaceInfo`
+    pub index: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RuleState {
+    /// Not firing. Holds when the condition started to hold.
+    Ok(Option<Instant>),
+    /// Firing. Holds when the condition started to clear.
+    Firing(Option<Instant>),
+}
+
+pub struct Alerts {
+    rules: Vec<Rule>,
+    info: InterfaceInfo,
+    prev_stats: InterfaceStats,
+    /// State of each rule for each interface
+    states: Vec<Vec<RuleState>>,
+    action_tx: Option<mpsc::Sender<AlertEvent>>,
+    action_thread: Option<thread::JoinHandle<()>>,
+    /// Errors of the actions, sent back by the action thread
+    failure_rx: mpsc::Receiver<String>,
+}
+
+impl Alerts {
+    /// Creates an alert engine. `actions` are run in a background thread in the order the
+    /// events occur, so that a slow action does not block the monitor. Their errors are returned
+    /// by `finish` rather than printed, as a writer may be drawing on the terminal.
+    pub fn new(
+        rules: Vec<Rule>,
+        actions: Vec<Box<dyn Action>>,
+        info: &InterfaceInfo,
+        initial_stats: InterfaceStats,
+    ) -> Alerts {
+        let (action_tx, action_rx) = mpsc::channel::<AlertEvent>();
+        let (failure_tx, failure_rx) = mpsc::channel::<String>();
+        let action_thread = thread::spawn(move || {
+            for event in action_rx {
+                for action in actions.iter() {
+                    if let Err(err) = action.run(&event) {
+                        failure_tx
+                            .send(format!("{} ({})", err, event))
+                            .unwrap_or(());
+                    }
+                }
+            }
+        });
+
+        Alerts {
+            states: vec![vec![RuleState::Ok(None); info.0.len()]; rules.len()],
+            rules,
+            info: info.clone(),
+            prev_stats: initial_stats,
+            action_tx: Some(action_tx),
+            action_thread: Some(action_thread),
+            failure_rx,
+        }
+    }
+
+    /// Evaluates the rules against the difference from the previous stats and runs the actions
+    /// for the alerts which fired or resolved.
+    pub fn update(&mut self, stats: &InterfaceStats, now: Instant) -> Vec<AlertEvent> {
+        let diff = stats.counter_diff(&self.prev_stats);
+        self.prev_stats = stats.clone();
+
+        let mut events = vec![];
+        for (rule, states) in self.rules.iter().zip(self.states.iter_mut()) {
+            for (i, item) in self.info.0.iter().enumerate() {
+                if !rule.applies_to(&item.name) {
+                    continue;
+                }
+                let value = match &diff.0[i] {
+                    Some(stat) => rule.metric.value(stat),
+                    None => continue,
+                };
+                let (state, fired) = Self::next_state(rule, states[i], value, now);
+                states[i] = state;
+                if let Some(state) = fired {
+                    events.push(AlertEvent {
+                        state,
+                        rule: rule.clone(),
+                        interface: item.name.clone(),
+                        value,
+                    });
+                }
+            }
+        }
+
+        if let Some(tx) = &self.action_tx {
+            for event in events.iter() {
+                tx.send(event.clone()).unwrap_or(());
+            }
+        }
+        events
+    }
+
+    fn next_state(
+        rule: &Rule,
+        state: RuleState,
+        value: f64,
+        now: Instant,
+    ) -> (RuleState, Option<AlertState>) {
+        match state {
+            RuleState::Ok(since) if rule.is_breached(value) => {
+                let since = since.unwrap_or(now);
+                if now - since >= rule.duration {
+                    (RuleState::Firing(None), Some(AlertState::Firing))
+                } else {
+                    (RuleState::Ok(Some(since)), None)
+                }
+            }
+            RuleState::Ok(_) => (RuleState::Ok(None), None),
+            RuleState::Firing(since) if rule.is_cleared(value) => {
+                let since = since.unwrap_or(now);
+                if now - since >= rule.duration {
+                    (RuleState::Ok(None), Some(AlertState::Resolved))
+                } else {
+                    (RuleState::Firing(Some(since)), None)
+                }
+            }
+            RuleState::Firing(_) => (RuleState::Firing(None), None),
+        }
+    }
+
+    pub fn firing(&self) -> Vec<FiringAlert> {
+        let mut alerts = vec![];
+        for (rule, states) in self.rules.iter().zip(self.states.iter()) {
+            for (index, state) in states.iter().enumerate() {
+                if let RuleState::Firing(_) = state {
+                    alerts.push(FiringAlert {
+                        rule: rule.clone(),
+                        index,
+                    });
+                }
+            }
+        }
+        alerts
+    }
+
+    /// Waits for the pending actions and returns the errors of the actions which failed.
+    pub fn finish(mut self) -> Vec<String> {
+        self.join_actions();
+        self.failure_rx.try_iter().collect()
+    }
+
+    fn join_actions(&mut self) {
+        // Closing the channel lets the action thread finish the pending actions and exit.
+        mem::drop(self.action_tx.take());
+        if let Some(thread) = self.action_thread.take() {
+            thread
+                .join()
+                .expect("Failed to shutdown alert action thread");
+        }
+    }
+}
+
+impl Drop for Alerts {
+    fn drop(&mut self) {
+        self.join_actions();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::info;
+    use crate::utils::NumBytes;
+    use crate::InterfaceStat;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    struct RecordAction(Arc<Mutex<Vec<String>>>);
+
+    impl Action for RecordAction {
+        fn run(&self, event: &AlertEvent) -> crate::Result<()> {
+            self.0.lock().unwrap().push(format!("{}", event));
+            Ok(())
+        }
+    }
+
+    struct FailingAction;
+
+    impl Action for FailingAction {
+        fn run(&self, _event: &AlertEvent) -> crate::Result<()> {
+            Err(crate::Error::AlertError("connection refused".to_string()))
+        }
+    }
+
+    fn stats(rx: u64) -> InterfaceStats {
+        let stat = InterfaceStat {
+            rx: NumBytes::from(rx),
+            tx: NumBytes::from(0),
+            drops: 0,
+        };
+        InterfaceStats(vec![None, Some(stat)])
+    }
+
+    #[test]
+    fn test_fire_and_resolve_with_duration() {
+        let record = Arc::new(Mutex::new(vec![]));
+        let mut alerts = Alerts::new(
+            vec!["eth0 rx > 100 for 2s".parse().unwrap()],
+            vec![Box::new(RecordAction(Arc::clone(&record)))],
+            &info(),
+            stats(0),
+        );
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+
+        // rx per tick: 200, 200, 200 -> fires once the condition held for 2s
+        assert!(alerts.update(&stats(200), at(0)).is_empty());
+        assert!(alerts.update(&stats(400), at(1)).is_empty());
+        let events = alerts.update(&stats(600), at(2));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].state, AlertState::Firing);
+        assert_eq!(events[0].interface, "eth0");
+        assert_eq!(alerts.firing().len(), 1);
+        assert_eq!(alerts.firing()[0].index, 1);
+
+        // 95 is below the threshold but within the hysteresis band
+        assert!(alerts.update(&stats(695), at(3)).is_empty());
+        assert!(alerts.update(&stats(790), at(4)).is_empty());
+        // 0 clears, but has to hold for 2s
+        assert!(alerts.update(&stats(790), at(5)).is_empty());
+        assert!(alerts.update(&stats(790), at(6)).is_empty());
+        let events = alerts.update(&stats(790), at(7));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].state, AlertState::Resolved);
+        assert!(alerts.firing().is_empty());
+
+        mem::drop(alerts);
+        assert_eq!(
+            *record.lock().unwrap(),
+            vec![
+                "firing: eth0 rx > 100 for 2s (eth0: 200)",
+                "resolved: eth0 rx > 100 for 2s (eth0: 0)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interrupted_condition_does_not_fire() {
+        let mut alerts = Alerts::new(
+            vec!["rx > 100 for 2s".parse().unwrap()],
+            vec![],
+            &info(),
+            stats(0),
+        );
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+        assert!(alerts.update(&stats(200), at(0)).is_empty());
+        assert!(alerts.update(&stats(200), at(1)).is_empty());
+        assert!(alerts.update(&stats(400), at(2)).is_empty());
+        assert!(alerts.update(&stats(600), at(3)).is_empty());
+        assert_eq!(alerts.update(&stats(800), at(4)).len(), 1);
+    }
+
+    #[test]
+    fn test_wrapped_counter_does_not_fire() {
+        let mut alerts = Alerts::new(
+            vec!["eth0 rx > 1 KiB".parse().unwrap()],
+            vec![],
+            &info(),
+            stats(u64::from(u32::MAX) - 100),
+        );
+        // the 32-bit counter wrapped around, and is counted from zero
+        assert!(alerts.update(&stats(200), Instant::now()).is_empty());
+        assert!(alerts.firing().is_empty());
+    }
+
+    #[test]
+    fn test_failures() {
+        let mut alerts = Alerts::new(
+            vec!["eth0 rx > 100".parse().unwrap()],
+            vec![Box::new(FailingAction)],
+            &info(),
+            stats(0),
+        );
+        assert_eq!(alerts.update(&stats(200), Instant::now()).len(), 1);
+        assert_eq!(
+            alerts.finish(),
+            vec!["connection refused (firing: eth0 rx > 100 (eth0: 200))"]
+        );
+    }
+}
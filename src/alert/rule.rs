@@ -0,0 +1,189 @@
+//! Alert rules such as `eth0 rx > 80 MiB/s for 30s` or `drops > 0`.
+
+use std::time::Duration;
+use std::{fmt, str};
+
+use crate::utils::{parse_bytes, parse_duration};
+use crate::{Error, InterfaceStat};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertMetric {
+    /// Received bytes per second
+    Rx,
+    /// Transmitted bytes per second
+    Tx,
+    /// Dropped packets per second
+    Drops,
+}
+
+impl AlertMetric {
+    pub fn value(self, stat: &InterfaceStat) -> f64 {
+        match self {
+            AlertMetric::Rx => stat.rx.to_f64().unwrap_or(0.0),
+            AlertMetric::Tx => stat.tx.to_f64().unwrap_or(0.0),
+            AlertMetric::Drops => stat.drops as f64,
+        }
+    }
+}
+
+impl str::FromStr for AlertMetric {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<AlertMetric, Error> {
+        match s {
+            "rx" => Ok(AlertMetric::Rx),
+            "tx" => Ok(AlertMetric::Tx),
+            "drops" => Ok(AlertMetric::Drops),
+            _ => Err(Error::ParseError(format!("unknown metric `{}`", s))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl str::FromStr for Comparison {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Comparison, Error> {
+        match s {
+            ">" => Ok(Comparison::Gt),
+            ">=" => Ok(Comparison::Ge),
+            "<" => Ok(Comparison::Lt),
+            "<=" => Ok(Comparison::Le),
+            _ => Err(Error::ParseError(format!("unknown comparison `{}`", s))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    /// Interface the rule applies to. `None` means every interface.
+    pub interface: Option<String>,
+    pub metric: AlertMetric,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    /// How long the condition has to hold before the alert fires or resolves
+    pub duration: Duration,
+    text: String,
+}
+
+impl Rule {
+    /// Fraction of the threshold a value has to move back past before a firing alert
+    /// resolves, so that a value hovering around the threshold does not flap.
+    pub const HYSTERESIS: f64 = 0.1;
+
+    pub fn applies_to(&self, interface: &str) -> bool {
+        self.interface
+            .as_ref()
+            .map_or(true, |name| name == interface)
+    }
+
+    pub fn is_breached(&self, value: f64) -> bool {
+        match self.comparison {
+            Comparison::Gt => value > self.threshold,
+            Comparison::Ge => value >= self.threshold,
+            Comparison::Lt => value < self.threshold,
+            Comparison::Le => value <= self.threshold,
+        }
+    }
+
+    pub fn is_cleared(&self, value: f64) -> bool {
+        match self.comparison {
+            Comparison::Gt => value <= self.threshold * (1.0 - Self::HYSTERESIS),
+            Comparison::Ge => value < self.threshold * (1.0 - Self::HYSTERESIS),
+            Comparison::Lt => value >= self.threshold * (1.0 + Self::HYSTERESIS),
+            Comparison::Le => value > self.threshold * (1.0 + Self::HYSTERESIS),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl str::FromStr for Rule {
+    type Err = Error;
+
+    /// Parses `[<interface>] <rx|tx|drops> <op> <value> [for <duration>]`.
+    fn from_str(s: &str) -> Result<Rule, Error> {
+        let err = || {
+            Error::ParseError(format!(
+                "expected `[<interface>] <rx|tx|drops> <op> <value> [for <duration>]`, got `{}`",
+                s
+            ))
+        };
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        let (interface, rest) = match tokens.first() {
+            Some(&first) if first.parse::<AlertMetric>().is_ok() => (None, &tokens[..]),
+            Some(&first) => (Some(first.to_string()), &tokens[1..]),
+            None => return Err(err()),
+        };
+        if rest.len() < 3 {
+            return Err(err());
+        }
+        let metric = rest[0].parse()?;
+        let comparison = rest[1].parse()?;
+        let (value, duration) = match rest.iter().position(|&t| t == "for") {
+            Some(i) => (&rest[2..i], parse_duration(&rest[i + 1..].join(""))?),
+            None => (&rest[2..], Duration::from_secs(0)),
+        };
+        if value.is_empty() {
+            return Err(err());
+        }
+        Ok(Rule {
+            interface,
+            metric,
+            comparison,
+            threshold: parse_bytes(&value.join(""))?,
+            duration,
+            text: tokens.join(" "),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rule() {
+        let rule: Rule = "eth0 rx > 80 MiB/s for 30s".parse().unwrap();
+        assert_eq!(rule.interface, Some("eth0".to_string()));
+        assert_eq!(rule.metric, AlertMetric::Rx);
+        assert_eq!(rule.comparison, Comparison::Gt);
+        assert_eq!(rule.threshold, 80.0 * 1024.0 * 1024.0);
+        assert_eq!(rule.duration, Duration::from_secs(30));
+        assert_eq!(format!("{}", rule), "eth0 rx > 80 MiB/s for 30s");
+
+        let rule: Rule = "drops > 0".parse().unwrap();
+        assert_eq!(rule.interface, None);
+        assert_eq!(rule.metric, AlertMetric::Drops);
+        assert_eq!(rule.threshold, 0.0);
+        assert_eq!(rule.duration, Duration::from_secs(0));
+
+        assert!("eth0 rx".parse::<Rule>().is_err());
+        assert!("eth0 rx ~ 1".parse::<Rule>().is_err());
+        assert!("eth0 rx > for 1s".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let rule: Rule = "tx > 100".parse().unwrap();
+        assert!(rule.is_breached(101.0));
+        assert!(!rule.is_breached(95.0));
+        assert!(!rule.is_cleared(95.0));
+        assert!(rule.is_cleared(90.0));
+
+        let rule: Rule = "drops > 0".parse().unwrap();
+        assert!(rule.is_breached(1.0));
+        assert!(rule.is_cleared(0.0));
+    }
+}
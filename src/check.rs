@@ -0,0 +1,307 @@
+//! Monitoring plugin mode compatible with Nagios and Icinga.
+//!
+//! Samples the stats for a while, compares the rates against the `--warning` and `--critical`
+//! thresholds and prints a plugin line with perfdata, e.g.
+//! `MONE WARNING - eth0: rx 90.0 MiB/s, tx 1.20 KiB/s | eth0_rx=94371840B;83886080;; ...`.
+
+use std::time::{Duration, Instant};
+use std::{fmt, str, thread};
+use structopt::StructOpt;
+
+use crate::utils::{parse_bytes, parse_duration, set_units, units};
+use crate::{new_reader, ConfigOpt, Error, InterfaceInfo, InterfaceStats, ReaderOpt, UnitOpt};
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub struct CheckOpt {
+    #[structopt(flatten)]
+    pub reader: ReaderOpt,
+
+    #[structopt(flatten)]
+    pub units: UnitOpt,
+
+    #[structopt(flatten)]
+    pub config: ConfigOpt,
+
+    /// How long to sample the stats, e.g. `5s`
+    #[structopt(
+        short = "d",
+        long = "duration",
+        default_value = "5s",
+        parse(try_from_str = "parse_duration")
+    )]
+    pub duration: Duration,
+
+    /// Warning threshold in bytes per second, as `[<interface>=]<rx>[,<tx>]`, e.g. `80MiB` or
+    /// `eth0=80MiB,10MiB`
+    #[structopt(short = "w", long = "warning", number_of_values = 1)]
+    pub warning: Vec<Threshold>,
+
+    /// Critical threshold in bytes per second, in the same format as `--warning`
+    #[structopt(short = "c", long = "critical", number_of_values = 1)]
+    pub critical: Vec<Threshold>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Threshold {
+    /// `None` means every interface
+    pub interface: Option<String>,
+    pub rx: f64,
+    pub tx: f64,
+}
+
+impl str::FromStr for Threshold {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Threshold, Error> {
+        let (interface, values) = match s.find('=') {
+            Some(i) => (Some(s[..i].to_string()), &s[i + 1..]),
+            None => (None, s),
+        };
+        let mut values = values.splitn(2, ',');
+        let rx = parse_bytes(values.next().unwrap_or(""))?;
+        let tx = match values.next() {
+            Some(tx) => parse_bytes(tx)?,
+            None => rx,
+        };
+        Ok(Threshold { interface, rx, tx })
+    }
+}
+
+/// Returns the threshold for `interface`, preferring thresholds given for the interface.
+fn find_threshold<'a>(thresholds: &'a [Threshold], interface: &str) -> Option<&'a Threshold> {
+    thresholds
+        .iter()
+        .rev()
+        .find(|t| t.interface.as_ref().is_some_and(|name| name == interface))
+        .or_else(|| thresholds.iter().rev().find(|t| t.interface.is_none()))
+}
+
+/// Plugin return codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckStatus::Ok => write!(f, "OK"),
+            CheckStatus::Warning => write!(f, "WARNING"),
+            CheckStatus::Critical => write!(f, "CRITICAL"),
+            CheckStatus::Unknown => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    text: String,
+    perfdata: Vec<String>,
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MONE {} - {}", self.status, self.text)?;
+        if !self.perfdata.is_empty() {
+            write!(f, " | {}", self.perfdata.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl CheckResult {
+    fn unknown(text: String) -> CheckResult {
+        CheckResult {
+            status: CheckStatus::Unknown,
+            text,
+            perfdata: vec![],
+        }
+    }
+}
+
+fn status_of(rate: f64, warning: Option<f64>, critical: Option<f64>) -> CheckStatus {
+    if critical.is_some_and(|c| rate > c) {
+        CheckStatus::Critical
+    } else if warning.is_some_and(|w| rate > w) {
+        CheckStatus::Warning
+    } else {
+        CheckStatus::Ok
+    }
+}
+
+/// Compares the rates computed from `diff`, the difference of the stats sampled over
+/// `elapsed`, against the thresholds in `opt`.
+pub fn evaluate(
+    opt: &CheckOpt,
+    info: &InterfaceInfo,
+    diff: &InterfaceStats,
+    elapsed: Duration,
+) -> CheckResult {
+    for name in opt.reader.interfaces.iter() {
+        if !info.0.iter().any(|item| &item.name == name) {
+            return CheckResult::unknown(format!("interface `{}` not found", name));
+        }
+    }
+
+    let secs = elapsed.as_secs_f64().max(1e-3);
+    let mut status = CheckStatus::Ok;
+    let mut texts = vec![];
+    let mut perfdata = vec![];
+    for (item, stat) in info.0.iter().zip(diff.0.iter()) {
+        if !opt.reader.interfaces.is_empty() && !opt.reader.interfaces.contains(&item.name) {
+            continue;
+        }
+        let stat = match stat {
+            Some(stat) => stat,
+            None => return CheckResult::unknown(format!("no stats for interface `{}`", item.name)),
+        };
+        let warning = find_threshold(&opt.warning, &item.name);
+        let critical = find_threshold(&opt.critical, &item.name);
+        let mut text = vec![];
+        for (metric, bytes, w, c) in [
+            ("rx", stat.rx, warning.map(|t| t.rx), critical.map(|t| t.rx)),
+            ("tx", stat.tx, warning.map(|t| t.tx), critical.map(|t| t.tx)),
+        ] {
+            let rate = bytes.to_f64().unwrap_or(0.0) / secs;
+            status = status.max(status_of(rate, w, c));
+            text.push(format!("{} {}", metric, units().format_rate(rate.round())));
+            let limit = |v: Option<f64>| v.map(|v| format!("{:.0}", v)).unwrap_or_default();
+            perfdata.push(format!(
+                "{}_{}={:.0}B;{};{};0;",
+                item.name,
+                metric,
+                rate,
+                limit(w),
+                limit(c)
+            ));
+        }
+        texts.push(format!("{}: {}", item.name, text.join(", ")));
+    }
+
+    if texts.is_empty() {
+        return CheckResult::unknown("no interfaces to check".to_string());
+    }
+    CheckResult {
+        status,
+        text: texts.join("; "),
+        perfdata,
+    }
+}
+
+/// Samples the stats for `check.duration`, prints the plugin line and returns the status.
+pub fn run(check: &CheckOpt) -> CheckStatus {
+    set_units(check.units.units());
+    let result = match new_reader(&check.reader) {
+        Err(err) => CheckResult::unknown(format!("{}", err)),
+        Ok(reader) => {
+            let started_at = Instant::now();
+            let initial_stats = reader.read();
+            thread::sleep(check.duration);
+            let stats = reader.read();
+            let diff = stats.counter_diff(&initial_stats);
+            evaluate(check, reader.get_info(), &diff, started_at.elapsed())
+        }
+    };
+    println!("{}", result);
+    result.status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::info;
+    use crate::utils::NumBytes;
+    use crate::InterfaceStat;
+
+    fn diff() -> InterfaceStats {
+        let stat = |rx: u64, tx: u64| {
+            Some(InterfaceStat {
+                rx: NumBytes::from(rx),
+                tx: NumBytes::from(tx),
+                drops: 0,
+            })
+        };
+        // per 2 seconds
+        InterfaceStats(vec![stat(2048, 2048), stat(20 << 20, 2 << 20)])
+    }
+
+    fn check(args: &[&str]) -> CheckResult {
+        let opt = CheckOpt::from_iter([&["check"], args].concat());
+        evaluate(&opt, &info(), &diff(), Duration::from_secs(2))
+    }
+
+    #[test]
+    fn test_parse_threshold() {
+        let t: Threshold = "80MiB".parse().unwrap();
+        assert_eq!(t.interface, None);
+        assert_eq!(t.rx, 80.0 * 1024.0 * 1024.0);
+        assert_eq!(t.tx, t.rx);
+        let t: Threshold = "eth0=1K,2K".parse().unwrap();
+        assert_eq!(t.interface, Some("eth0".to_string()));
+        assert_eq!((t.rx, t.tx), (1024.0, 2048.0));
+        assert!("eth0=".parse::<Threshold>().is_err());
+    }
+
+    #[test]
+    fn test_ok() {
+        let result = check(&["-i", "eth0", "-w", "20MiB", "-c", "30MiB"]);
+        assert_eq!(result.status, CheckStatus::Ok);
+        assert_eq!(
+            format!("{}", result),
+            "MONE OK - eth0: rx 10.0 MiB/s, tx 1.00 MiB/s | \
+             eth0_rx=10485760B;20971520;31457280;0; eth0_tx=1048576B;20971520;31457280;0;"
+        );
+    }
+
+    #[test]
+    fn test_warning_and_critical() {
+        assert_eq!(check(&["-w", "5MiB"]).status, CheckStatus::Warning);
+        assert_eq!(
+            check(&["-w", "5MiB", "-c", "8MiB"]).status,
+            CheckStatus::Critical
+        );
+        // interface specific thresholds take precedence
+        assert_eq!(
+            check(&["-c", "5MiB", "-c", "eth0=20MiB"]).status,
+            CheckStatus::Ok
+        );
+        assert_eq!(
+            check(&["-c", "eth0=20MiB,512KiB"]).status,
+            CheckStatus::Critical
+        );
+        assert_eq!(check(&["-i", "lo", "-w", "5MiB"]).status, CheckStatus::Ok);
+    }
+
+    #[test]
+    fn test_unknown() {
+        let result = check(&["-i", "wlan0"]);
+        assert_eq!(result.status, CheckStatus::Unknown);
+        assert_eq!(
+            format!("{}", result),
+            "MONE UNKNOWN - interface `wlan0` not found"
+        );
+    }
+
+    #[test]
+    fn test_wrapped_counter() {
+        let stat = |rx: u64| {
+            Some(InterfaceStat {
+                rx: NumBytes::from(rx),
+                tx: NumBytes::from(0),
+                drops: 0,
+            })
+        };
+        // the 32-bit rx counter of eth0 wrapped around during the sample period
+        let initial_stats = InterfaceStats(vec![stat(0), stat(u64::from(u32::MAX) - 1024)]);
+        let stats = InterfaceStats(vec![stat(0), stat(2048)]);
+        let opt = CheckOpt::from_iter(&["check", "-i", "eth0", "-c", "1MiB"]);
+        let diff = stats.counter_diff(&initial_stats);
+        let result = evaluate(&opt, &info(), &diff, Duration::from_secs(2));
+        assert_eq!(result.status, CheckStatus::Ok);
+        assert!(result.to_string().contains("eth0: rx 1.00 KiB/s"));
+    }
+}
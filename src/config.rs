@@ -0,0 +1,411 @@
+//! Settings from a TOML config file, `~/.config/mone/config.toml` by default.
+//!
+//! Keys are named after the long command line flags, and repeatable flags take arrays. The top
+//! level sets the defaults, and a `[profile.<name>]` table overrides them when selected with
+//! `--profile <name>`. Flags given on the command line override the file.
+//!
+//! ```toml
+//! theme = "light"
+//! alert = ["drops > 0"]
+//!
+//! [profile.server]
+//! interface = ["eth0"]
+//! writer = ["simple=/var/log/mone.log", { type = "status", output = "/run/mone", width = 10 }]
+//! ```
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs, str};
+use structopt::clap::ArgMatches;
+use structopt::StructOpt;
+
+use crate::check::CheckOpt;
+use crate::utils::parse_duration;
+use crate::{Command, ConfigOpt, Error, LiveOpt, Opt, ReaderOpt, Result, UnitOpt, WriterSpec};
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    reader: Option<String>,
+    writer: Option<Vec<WriterEntry>>,
+    n: Option<usize>,
+    interface: Option<Vec<String>>,
+    netns: Option<Vec<String>>,
+    containers: Option<bool>,
+    counter: Option<Vec<String>>,
+    count: Option<usize>,
+    duration: Option<String>,
+    theme: Option<String>,
+    color: Option<Vec<String>>,
+    format: Option<String>,
+    status_mode: Option<String>,
+    width: Option<usize>,
+    bits: Option<bool>,
+    si: Option<bool>,
+    utilization_threshold: Option<f64>,
+    alert: Option<Vec<String>>,
+    alert_command: Option<String>,
+    alert_log: Option<PathBuf>,
+    alert_webhook: Option<String>,
+    profile: HashMap<String, Settings>,
+}
+
+/// A writer given as `"simple=mone.log"` or as a table with writer-specific settings.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+enum WriterEntry {
+    Spec(String),
+    Table(WriterTable),
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct WriterTable {
+    #[serde(rename = "type")]
+    writer: String,
+    output: Option<PathBuf>,
+    format: Option<String>,
+    status_mode: Option<String>,
+    width: Option<usize>,
+}
+
+fn parse<T>(key: &str, s: &str) -> Result<T>
+where
+    T: str::FromStr,
+    T::Err: fmt::Display,
+{
+    s.parse()
+        .map_err(|err| Error::ParseError(format!("invalid `{}` in config: {}", key, err)))
+}
+
+fn parse_all<T>(key: &str, values: &[String]) -> Result<Vec<T>>
+where
+    T: str::FromStr,
+    T::Err: fmt::Display,
+{
+    values.iter().map(|s| parse(key, s)).collect()
+}
+
+impl WriterEntry {
+    fn to_spec(&self) -> Result<WriterSpec> {
+        match self {
+            WriterEntry::Spec(s) => parse("writer", s),
+            WriterEntry::Table(table) => Ok(WriterSpec {
+                writer: parse("writer", &table.writer)?,
+                output: table.output.clone(),
+                format: table.format.clone(),
+                status_mode: match &table.status_mode {
+                    Some(mode) => Some(parse("status-mode", mode)?),
+                    None => None,
+                },
+                width: table.width,
+            }),
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_file(path: &Path) -> Result<Settings> {
+        let text = fs::read_to_string(path).map_err(|err| {
+            Error::ParseError(format!("failed to read `{}`: {}", path.display(), err))
+        })?;
+        toml::from_str(&text)
+            .map_err(|err| Error::ParseError(format!("{}: {}", path.display(), err)))
+    }
+
+    /// Returns the settings with the profile `name` applied.
+    pub fn with_profile(mut self, name: &str) -> Result<Settings> {
+        match self.profile.remove(name) {
+            Some(profile) => Ok(self.merge(profile)),
+            None => Err(Error::ParseError(format!("profile `{}` not found", name))),
+        }
+    }
+
+    /// Returns the settings with the values set in `other` overriding those in `self`.
+    fn merge(self, other: Settings) -> Settings {
+        Settings {
+            reader: other.reader.or(self.reader),
+            writer: other.writer.or(self.writer),
+            n: other.n.or(self.n),
+            interface: other.interface.or(self.interface),
+            netns: other.netns.or(self.netns),
+            containers: other.containers.or(self.containers),
+            counter: other.counter.or(self.counter),
+            count: other.count.or(self.count),
+            duration: other.duration.or(self.duration),
+            theme: other.theme.or(self.theme),
+            color: other.color.or(self.color),
+            format: other.format.or(self.format),
+            status_mode: other.status_mode.or(self.status_mode),
+            width: other.width.or(self.width),
+            bits: other.bits.or(self.bits),
+            si: other.si.or(self.si),
+            utilization_threshold: other.utilization_threshold.or(self.utilization_threshold),
+            alert: other.alert.or(self.alert),
+            alert_command: other.alert_command.or(self.alert_command),
+            alert_log: other.alert_log.or(self.alert_log),
+            alert_webhook: other.alert_webhook.or(self.alert_webhook),
+            profile: HashMap::new(),
+        }
+    }
+
+    /// Sets the fields of `opt` which are not given on the command line.
+    pub fn apply(&self, opt: &mut LiveOpt, matches: &ArgMatches) -> Result<()> {
+        let unset = |name: &str| matches.occurrences_of(name) == 0;
+
+        self.apply_reader(&mut opt.reader, matches)?;
+        self.apply_units(&mut opt.units, matches);
+        if let (Some(writers), true) = (&self.writer, unset("writers")) {
+            opt.writers = writers
+                .iter()
+                .map(WriterEntry::to_spec)
+                .collect::<Result<_>>()?;
+        }
+        if let (Some(n), true) = (self.n, unset("n")) {
+            opt.n = n;
+        }
+        if let (Some(count), true) = (self.count, unset("count")) {
+            opt.count = Some(count);
+        }
+        if let (Some(duration), true) = (&self.duration, unset("duration")) {
+            opt.duration = Some(parse_duration(duration)?);
+        }
+        if let (Some(theme), true) = (&self.theme, unset("theme")) {
+            opt.theme = parse("theme", theme)?;
+        }
+        if let (Some(colors), true) = (&self.color, unset("colors")) {
+            opt.colors = parse_all("color", colors)?;
+        }
+        if let (Some(format), true) = (&self.format, unset("format")) {
+            opt.format = format.clone();
+        }
+        if let (Some(mode), true) = (&self.status_mode, unset("status_mode")) {
+            opt.status_mode = parse("status-mode", mode)?;
+        }
+        if let (Some(width), true) = (self.width, unset("width")) {
+            opt.width = width;
+        }
+        if let (Some(threshold), true) =
+            (self.utilization_threshold, unset("utilization_threshold"))
+        {
+            opt.utilization_threshold = threshold;
+        }
+        if let (Some(alerts), true) = (&self.alert, unset("alerts")) {
+            opt.alerts = parse_all("alert", alerts)?;
+        }
+        if let (Some(command), true) = (&self.alert_command, unset("alert_command")) {
+            opt.alert_command = Some(command.clone());
+        }
+        if let (Some(path), true) = (&self.alert_log, unset("alert_log")) {
+            opt.alert_log = Some(path.clone());
+        }
+        if let (Some(url), true) = (&self.alert_webhook, unset("alert_webhook")) {
+            opt.alert_webhook = Some(url.clone());
+        }
+        Ok(())
+    }
+
+    /// Sets the fields of `opt` which are not given on the command line. `duration` is not
+    /// applied, as it is the sampling time of `check`.
+    pub fn apply_check(&self, opt: &mut CheckOpt, matches: &ArgMatches) -> Result<()> {
+        self.apply_reader(&mut opt.reader, matches)?;
+        self.apply_units(&mut opt.units, matches);
+        Ok(())
+    }
+
+    fn apply_reader(&self, opt: &mut ReaderOpt, matches: &ArgMatches) -> Result<()> {
+        let unset = |name: &str| matches.occurrences_of(name) == 0;
+
+        if let (Some(reader), true) = (&self.reader, unset("reader")) {
+            opt.reader = parse("reader", reader)?;
+        }
+        if let (Some(interfaces), true) = (&self.interface, unset("interfaces")) {
+            opt.interfaces = interfaces.clone();
+        }
+        if let (Some(netns), true) = (&self.netns, unset("netns")) {
+            opt.netns = parse_all("netns", netns)?;
+        }
+        if let (Some(containers), true) = (self.containers, unset("containers")) {
+            opt.containers = containers;
+        }
+        if let (Some(counters), true) = (&self.counter, unset("counters")) {
+            opt.counters = parse_all("counter", counters)?;
+        }
+        Ok(())
+    }
+
+    fn apply_units(&self, opt: &mut UnitOpt, matches: &ArgMatches) {
+        let unset = |name: &str| matches.occurrences_of(name) == 0;
+
+        if let (Some(bits), true) = (self.bits, unset("bits")) {
+            opt.bits = bits;
+        }
+        if let (Some(si), true) = (self.si, unset("si")) {
+            opt.si = si;
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/mone/config.toml`, or `~/.config/mone/config.toml`
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("mone").join("config.toml"))
+}
+
+/// Loads the config file given in `opt` or the default one, if it exists, with the selected
+/// profile applied.
+fn load_settings(opt: &ConfigOpt) -> Result<Option<Settings>> {
+    let settings = match &opt.config {
+        Some(path) => Settings::from_file(path)?,
+        None => match default_path() {
+            Some(path) if path.exists() => Settings::from_file(&path)?,
+            _ if opt.profile.is_some() => {
+                return Err(Error::Other("`--profile` requires a config file."))
+            }
+            _ => return Ok(None),
+        },
+    };
+    match &opt.profile {
+        Some(name) => settings.with_profile(name).map(Some),
+        None => Ok(Some(settings)),
+    }
+}
+
+fn apply_config(opt: &mut Opt, matches: &ArgMatches) -> Result<()> {
+    // the options of the command, or those of `mone` itself for the default `live`
+    let matches = matches.subcommand().1.unwrap_or(matches);
+    match &mut opt.cmd {
+        None => apply_live_config(&mut opt.live, matches),
+        Some(Command::Live(live))
+        | Some(Command::Record { live, .. })
+        | Some(Command::Replay { live, .. }) => apply_live_config(live, matches),
+        Some(Command::Check(check)) => match load_settings(&check.config)? {
+            Some(settings) => settings.apply_check(check, matches),
+            None => Ok(()),
+        },
+        Some(Command::List { .. }) | Some(Command::Report { .. }) => Ok(()),
+    }
+}
+
+fn apply_live_config(opt: &mut LiveOpt, matches: &ArgMatches) -> Result<()> {
+    match load_settings(&opt.config)? {
+        Some(settings) => settings.apply(opt, matches),
+        None => Ok(()),
+    }
+}
+
+/// Parses the command line arguments and applies the config file.
+pub fn load_opt() -> Result<Opt> {
+    let matches = Opt::clap().get_matches();
+    let mut opt = Opt::from_clap(&matches);
+    apply_config(&mut opt, &matches)?;
+    Ok(opt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::in_netns::Netns;
+    use crate::test_utils::temp_path;
+    use crate::{StatusMode, ThemeType, WriterType};
+    use std::time::Duration;
+
+    const CONFIG: &str = r#"
+theme = "light"
+n = 60
+alert = ["drops > 0"]
+
+[profile.server]
+interface = ["eth0"]
+netns = ["all", "1234"]
+counter = ["eth0:rx_queue_*"]
+duration = "5m"
+writer = ["simple=/var/log/mone.log", { type = "status", output = "/run/mone", status-mode = "waybar" }]
+"#;
+
+    fn parse_opt(args: &[&str], settings: Settings) -> Result<LiveOpt> {
+        let matches = LiveOpt::clap().get_matches_from([&["mone"], args].concat());
+        let mut opt = LiveOpt::from_clap(&matches);
+        settings.apply(&mut opt, &matches)?;
+        Ok(opt)
+    }
+
+    #[test]
+    fn test_apply() {
+        let settings: Settings = toml::from_str(CONFIG).unwrap();
+        let opt = parse_opt(&[], settings.clone()).unwrap();
+        assert_eq!(opt.theme, ThemeType::light);
+        assert_eq!(opt.n, 60);
+        assert_eq!(opt.alerts.len(), 1);
+        assert_eq!(opt.writers.len(), 1);
+        assert!(opt.reader.interfaces.is_empty());
+
+        let settings = settings.with_profile("server").unwrap();
+        let opt = parse_opt(&["-n", "30", "--theme", "dark"], settings).unwrap();
+        assert_eq!(opt.theme, ThemeType::dark);
+        assert_eq!(opt.n, 30);
+        assert_eq!(opt.reader.interfaces, vec!["eth0"]);
+        assert_eq!(opt.reader.netns, vec![Netns::All, Netns::Pid(1234)]);
+        assert_eq!(opt.reader.counters[0].to_string(), "eth0:rx_queue_*");
+        assert_eq!(opt.duration, Some(Duration::from_secs(300)));
+        assert_eq!(opt.writers[0].to_string(), "simple=/var/log/mone.log");
+        assert_eq!(opt.writers[1].writer, WriterType::status);
+        assert_eq!(opt.writers[1].status_mode, Some(StatusMode::waybar));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let settings: Settings = toml::from_str(CONFIG).unwrap();
+        assert!(settings.with_profile("laptop").is_err());
+        assert!(toml::from_str::<Settings>("colour = []").is_err());
+        let settings: Settings = toml::from_str(r#"theme = "solarized""#).unwrap();
+        assert!(parse_opt(&[], settings).is_err());
+    }
+
+    #[test]
+    fn test_apply_config_to_command() {
+        let path = temp_path("config.toml");
+        fs::write(&path, CONFIG).unwrap();
+        let config = path.to_str().unwrap();
+        let load = |args: &[&str]| {
+            let matches = Opt::clap().get_matches_from([&["mone"], args].concat());
+            let mut opt = Opt::from_clap(&matches);
+            apply_config(&mut opt, &matches).map(|()| opt)
+        };
+
+        let opt = load(&["--config", config, "-n", "30"]).unwrap();
+        assert_eq!((opt.live.n, opt.live.theme), (30, ThemeType::light));
+        match load(&[
+            "record",
+            "out.mone",
+            "--config",
+            config,
+            "--profile",
+            "server",
+        ]) {
+            Ok(Opt {
+                cmd: Some(Command::Record { live, .. }),
+                ..
+            }) => {
+                assert_eq!(live.n, 60);
+                assert_eq!(live.reader.interfaces, vec!["eth0"]);
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        match load(&["check", "--config", config, "--profile", "server"]) {
+            Ok(Opt {
+                cmd: Some(Command::Check(check)),
+                ..
+            }) => {
+                assert_eq!(check.reader.interfaces, vec!["eth0"]);
+                assert_eq!(check.duration, Duration::from_secs(5));
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
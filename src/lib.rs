@@ -1,18 +1,45 @@
+// `failure_derive` expands to impls nested in anonymous consts. The lint is newer than the msrv.
+#![allow(unknown_lints, non_local_definitions)]
+
 #[macro_use]
 extern crate cfg_if;
 #[macro_use]
 extern crate failure;
 
-use std::{fmt, io, ops, result};
+use nix::net::if_::InterfaceFlags;
+use std::fs::{File, OpenOptions};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fmt, io, ops, result, str};
 use structopt::{clap::arg_enum, StructOpt};
 
+use crate::alert::{Action, Alerts, CommandAction, LogAction, Rule, WebhookAction};
+use crate::check::CheckOpt;
 use crate::monitor::Monitor;
-use crate::reader::{in_libc::LibcReader, Read};
-use crate::utils::NumBytes;
-use crate::writer::{out_simple::SimpleWriter, out_tui::TuiWriter, Write};
+use crate::reader::in_container::ContainerReader;
+use crate::reader::in_ethtool::{CounterPattern, EthtoolReader};
+use crate::reader::in_netns::{Netns, NetnsReader};
+use crate::reader::sysfs::LinkSettings;
+use crate::reader::{filter::FilterReader, in_libc::LibcReader, in_replay::ReplayReader, Read};
+use crate::record::Recording;
+use crate::utils::{parse_duration, set_units, NumBytes, Units};
+use crate::writer::tui_backend::DefaultTuiBackend;
+use crate::writer::{
+    out_record::RecordWriter, out_simple::SimpleWriter, out_spark::SparkWriter,
+    out_status::StatusWriter, out_tui::TuiWriter, theme::InterfaceColor, Write,
+};
 
+pub mod alert;
+pub mod check;
+pub mod config;
+pub mod list;
 pub mod monitor;
 pub mod reader;
+pub mod record;
+pub mod report;
+#[cfg(test)]
+mod test_utils;
 pub mod utils;
 pub mod writer;
 
@@ -28,6 +55,12 @@ pub enum Error {
     #[fail(display = "{}", _0)]
     LinkStatsError(String),
     #[fail(display = "{}", _0)]
+    ParseError(String),
+    #[fail(display = "{}", _0)]
+    AlertError(String),
+    #[fail(display = "{}", _0)]
+    WriterError(String),
+    #[fail(display = "{}", _0)]
     Other(&'static str),
 }
 
@@ -46,6 +79,34 @@ impl From<io::Error> for Error {
 #[derive(Clone, Debug, PartialEq)]
 pub struct InterfaceInfoItem {
     name: String,
+    pub ipv4: Vec<Ipv4Addr>,
+    pub ipv6: Vec<Ipv6Addr>,
+    /// MAC address, e.g. `02:42:ac:11:00:02`
+    pub mac: Option<String>,
+    pub flags: InterfaceFlags,
+    pub link: LinkSettings,
+}
+
+impl InterfaceInfoItem {
+    pub fn new(name: &str) -> InterfaceInfoItem {
+        InterfaceInfoItem {
+            name: name.to_string(),
+            ipv4: vec![],
+            ipv6: vec![],
+            mac: None,
+            flags: InterfaceFlags::empty(),
+            link: LinkSettings::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.flags
+            .contains(InterfaceFlags::IFF_UP | InterfaceFlags::IFF_RUNNING)
+    }
 }
 
 impl fmt::Display for InterfaceInfoItem {
@@ -54,7 +115,7 @@ impl fmt::Display for InterfaceInfoItem {
             f,
             "{:<width$}",
             self.name,
-            width = InterfaceStat::DISPLAY_WIDTH
+            width = InterfaceStat::display_width()
         )
     }
 }
@@ -85,11 +146,14 @@ impl fmt::Display for InterfaceInfo {
 pub struct InterfaceStat {
     rx: NumBytes<u64>,
     tx: NumBytes<u64>,
+    /// Number of dropped packets
+    drops: u64,
 }
 
 impl InterfaceStat {
-    const DISPLAY_WIDTH: usize =
-        NumBytes::<u64>::DISPLAY_WIDTH + 1 + NumBytes::<u64>::DISPLAY_WIDTH;
+    fn display_width() -> usize {
+        NumBytes::<u64>::display_width() + 1 + NumBytes::<u64>::display_width()
+    }
 }
 
 impl fmt::Display for InterfaceStat {
@@ -98,12 +162,54 @@ impl fmt::Display for InterfaceStat {
     }
 }
 
+/// Rates of an interface as percentages of its link speed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Utilization {
+    pub rx: f64,
+    pub tx: f64,
+}
+
+impl Utilization {
+    /// `stat` is the difference of the stats in one second. Returns `None` if the link speed is
+    /// unknown.
+    pub fn new(stat: &InterfaceStat, link: &LinkSettings) -> Option<Utilization> {
+        let bytes_per_sec = link.speed? as f64 * 1_000_000.0 / 8.0;
+        Some(Utilization {
+            rx: stat.rx.value() as f64 / bytes_per_sec * 100.0,
+            tx: stat.tx.value() as f64 / bytes_per_sec * 100.0,
+        })
+    }
+
+    pub fn max(&self) -> f64 {
+        self.rx.max(self.tx)
+    }
+}
+
+impl fmt::Display for Utilization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>5.1}% {:>5.1}%", self.rx, self.tx)
+    }
+}
+
+/// Difference of a counter between two reads. A counter which went down was reset, e.g. by
+/// recreating the interface, or wrapped around, as the 32-bit counters of `getifaddrs` do, so
+/// `cur` is counted from zero. The width of the counters is unknown, so the part before the wrap
+/// is lost.
+pub(crate) fn counter_diff(prev: u64, cur: u64) -> u64 {
+    if cur < prev {
+        cur
+    } else {
+        cur - prev
+    }
+}
+
 impl ops::Sub for &InterfaceStat {
     type Output = InterfaceStat;
     fn sub(self, other: &InterfaceStat) -> Self::Output {
         InterfaceStat {
             rx: self.rx - other.rx,
             tx: self.tx - other.tx,
+            drops: self.drops.saturating_sub(other.drops),
         }
     }
 }
@@ -117,6 +223,56 @@ impl InterfaceStats {
     fn empty(len: usize) -> InterfaceStats {
         InterfaceStats(vec![None; len])
     }
+
+    /// Formats the stat of an interface, or `None` for both columns if it is missing.
+    pub(crate) fn format_stat(stat: Option<&InterfaceStat>) -> String {
+        match stat {
+            Some(stat) => stat.to_string(),
+            None => format!(
+                "{:<w$} {:<w$}",
+                "None",
+                "None",
+                w = NumBytes::<u64>::display_width()
+            ),
+        }
+    }
+
+    /// Differences since `prev`, with `counter_diff` for the counters, unlike `&self - prev`
+    /// which overflows when a counter went down.
+    pub(crate) fn counter_diff(&self, prev: &InterfaceStats) -> InterfaceStats {
+        let diff = |cur: &NumBytes<u64>, prev: &NumBytes<u64>| {
+            NumBytes::from(counter_diff(prev.value(), cur.value()))
+        };
+        InterfaceStats(
+            self.0
+                .iter()
+                .zip(prev.0.iter())
+                .map(|(stat, prev)| match (stat, prev) {
+                    (Some(stat), Some(prev)) => Some(InterfaceStat {
+                        rx: diff(&stat.rx, &prev.rx),
+                        tx: diff(&stat.tx, &prev.tx),
+                        drops: counter_diff(prev.drops, stat.drops),
+                    }),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Divides the differences `self` over `interval` into the differences per second.
+    pub(crate) fn per_sec(mut self, interval: Duration) -> InterfaceStats {
+        let secs = interval.as_secs_f64();
+        if secs == 1.0 || secs == 0.0 {
+            return self;
+        }
+        let scale = |value: u64| (value as f64 / secs).round() as u64;
+        for stat in self.0.iter_mut().flatten() {
+            stat.rx = NumBytes::from(scale(stat.rx.value()));
+            stat.tx = NumBytes::from(scale(stat.tx.value()));
+            stat.drops = scale(stat.drops);
+        }
+        self
+    }
 }
 
 impl ops::Sub for &InterfaceStats {
@@ -144,16 +300,7 @@ impl fmt::Display for InterfaceStats {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let len = self.0.len();
         for (i, stat) in self.0.iter().enumerate() {
-            match stat {
-                Some(stat) => write!(f, "{}", stat)?,
-                None => write!(
-                    f,
-                    "{:<w$} {:<w$}",
-                    "None",
-                    "None",
-                    w = NumBytes::<u64>::DISPLAY_WIDTH
-                )?,
-            }
+            write!(f, "{}", Self::format_stat(stat.as_ref()))?;
             write!(f, "{}", if i == len - 1 { "" } else { Self::DELIMITER })?
         }
         Ok(())
@@ -165,6 +312,17 @@ arg_enum! {
     #[derive(Clone, Copy, PartialEq, Debug)]
     pub enum ReaderType {
         libc,
+        ethtool,
+    }
+}
+
+arg_enum! {
+    /// Series of the packets of pcap and pcapng files
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum Breakdown {
+        interface,
+        protocol,
     }
 }
 
@@ -174,18 +332,203 @@ arg_enum! {
     pub enum WriterType {
         tui,
         simple,
+        status,
+        spark,
+    }
+}
+
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum ThemeType {
+        dark,
+        light,
+        monochrome,
+        colorblind,
+    }
+}
+
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum StatusMode {
+        plain,
+        i3bar,
+        waybar,
+    }
+}
+
+/// A writer and the file it writes to, e.g. `tui` or `simple=mone.log`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WriterSpec {
+    pub writer: WriterType,
+    /// `None` means stdout
+    pub output: Option<PathBuf>,
+    /// Overrides `--format` for this writer. Only settable in the config file.
+    pub format: Option<String>,
+    /// Overrides `--status-mode` for this writer. Only settable in the config file.
+    pub status_mode: Option<StatusMode>,
+    /// Overrides `--width` for this writer. Only settable in the config file.
+    pub width: Option<usize>,
+}
+
+impl fmt::Display for WriterSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.output {
+            Some(path) => write!(f, "{}={}", self.writer, path.display()),
+            None => write!(f, "{}", self.writer),
+        }
+    }
+}
+
+impl str::FromStr for WriterSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<WriterSpec> {
+        let (writer, output) = match s.find('=') {
+            Some(i) => (&s[..i], Some(PathBuf::from(&s[i + 1..]))),
+            None => (s, None),
+        };
+        let writer = writer.parse().map_err(|_| {
+            Error::ParseError(format!(
+                "unknown writer `{}`, expected one of {}",
+                writer,
+                WriterType::variants().join(", ")
+            ))
+        })?;
+        Ok(WriterSpec {
+            writer,
+            output,
+            format: None,
+            status_mode: None,
+            width: None,
+        })
     }
 }
 
 static DEFAULT_READER: &str = "libc";
 static DEFAULT_WRITER: &str = "tui";
+static DEFAULT_THEME: &str = "dark";
+static DEFAULT_STATUS_MODE: &str = "plain";
 
+/// Options of the commands showing the stats with the writers
 #[derive(StructOpt, Debug, PartialEq)]
-#[structopt()]
-pub struct Opt {
+pub struct LiveOpt {
+    #[structopt(flatten)]
+    pub reader: ReaderOpt,
+
+    /// Writer to use. Can be given multiple times to run several writers at once.
+    ///
+    /// - tui: output in TUI mode{n}- simple: output simple log to stdout{n}- status: output one line
+    ///   per tick for status bars{n}- spark: output sparklines redrawn in place{n}
+    ///
+    /// Append `=<path>` to append the output to a file instead of stdout, e.g. `simple=mone.log`.
+    /// At most one writer can write to stdout.
+    #[structopt(
+        short = "w",
+        long = "writer",
+        number_of_values = 1,
+        raw(default_value = "DEFAULT_WRITER")
+    )]
+    pub writers: Vec<WriterSpec>,
+
+    /// Number of stats history to show
+    #[structopt(short = "n", default_value = "180")]
+    pub n: usize,
+
+    /// Stop after this number of stats
+    #[structopt(long = "count")]
+    pub count: Option<usize>,
+
+    /// Stop after this duration, e.g. `30s` or `5m`
+    #[structopt(long = "duration", parse(try_from_str = "parse_duration"))]
+    pub duration: Option<Duration>,
+
+    /// Color theme of the TUI
+    ///
+    /// Falls back to `monochrome` if the `NO_COLOR` environment variable is set.
+    #[structopt(
+        long = "theme",
+        raw(possible_values = "&ThemeType::variants()"),
+        raw(default_value = "DEFAULT_THEME")
+    )]
+    pub theme: ThemeType,
+
+    /// Color of an interface in the TUI, e.g. `eth0=lightblue`, `eth0=#ff8000` or `eth0=208`
+    #[structopt(long = "color", number_of_values = 1)]
+    pub colors: Vec<InterfaceColor>,
+
+    /// Format of each interface in the status writer
+    ///
+    /// `{name}`, `{rx}` and `{tx}` are replaced with the interface name and the received and
+    /// transmitted bytes per second.
+    #[structopt(
+        long = "format",
+        raw(default_value = "StatusWriter::<io::Stdout>::DEFAULT_FORMAT")
+    )]
+    pub format: String,
+
+    /// Output protocol of the status writer
+    ///
+    /// - plain: plain text, e.g. for tmux{n}- i3bar: i3bar JSON protocol{n}- waybar: JSON with
+    ///   tooltip and class for waybar custom modules{n}
+    #[structopt(
+        long = "status-mode",
+        raw(possible_values = "&StatusMode::variants()"),
+        raw(default_value = "DEFAULT_STATUS_MODE")
+    )]
+    pub status_mode: StatusMode,
+
+    /// Number of stats history to show in each sparkline of the spark writer
+    #[structopt(long = "width", default_value = "30")]
+    pub width: usize,
+
+    /// Utilization of the link speed in percent above which a link is highlighted
+    #[structopt(long = "utilization-threshold", default_value = "80")]
+    pub utilization_threshold: f64,
+
+    /// Alert rule, e.g. `eth0 rx > 80 MiB/s for 30s` or `drops > 0`
+    ///
+    /// Format: `[<interface>] <rx|tx|drops> <op> <value> [for <duration>]` where `<op>` is one of
+    /// `>`, `>=`, `<` and `<=`. Rules without an interface apply to every interface.
+    #[structopt(long = "alert", number_of_values = 1)]
+    pub alerts: Vec<Rule>,
+
+    /// Shell command to run when an alert fires or resolves
+    ///
+    /// The event is passed in `MONE_ALERT_STATE`, `MONE_ALERT_RULE`, `MONE_ALERT_INTERFACE` and
+    /// `MONE_ALERT_VALUE`.
+    #[structopt(long = "alert-command")]
+    pub alert_command: Option<String>,
+
+    /// File to append alert events to
+    #[structopt(long = "alert-log", parse(from_os_str))]
+    pub alert_log: Option<PathBuf>,
+
+    /// `http://` URL to POST alert events to as JSON
+    #[structopt(long = "alert-webhook")]
+    pub alert_webhook: Option<String>,
+
+    #[structopt(flatten)]
+    pub units: UnitOpt,
+
+    #[structopt(flatten)]
+    pub config: ConfigOpt,
+}
+
+impl LiveOpt {
+    pub fn units(&self) -> Units {
+        self.units.units()
+    }
+}
+
+/// Options of the reader, shared by the commands reading the stats of the system
+#[derive(StructOpt, Debug, PartialEq)]
+pub struct ReaderOpt {
     /// Reader to use
     ///
-    /// - libc: collect network interface stats using getifaddr from libc{n}
+    /// - libc: collect network interface stats using getifaddr from libc{n}- ethtool: collect the
+    ///   driver counters given with `--counter`, as `ethtool -S` shows (Linux only){n}
     #[structopt(
         short = "r",
         long = "reader",
@@ -194,36 +537,313 @@ pub struct Opt {
     )]
     pub reader: ReaderType,
 
-    /// Writer to use
+    /// Interface to monitor. Can be given multiple times. Defaults to every interface.
+    #[structopt(short = "i", long = "interface", number_of_values = 1)]
+    pub interfaces: Vec<String>,
+
+    /// Network namespace to monitor too, as a name in `/var/run/netns`, a pid, a path or `all`.
+    /// Can be given multiple times. Its interfaces are named `<namespace>:<interface>`.
+    #[structopt(long = "netns", number_of_values = 1)]
+    pub netns: Vec<Netns>,
+
+    /// Name the host side veth interfaces after their containers or pods (Linux only)
+    #[structopt(long = "containers")]
+    pub containers: bool,
+
+    /// Driver counter to monitor with the ethtool reader, as `[<interface>:]<counter>`, e.g.
+    /// `eth0:rx_queue_*_bytes`. `*` and `?` match any characters. Can be given multiple times.
+    #[structopt(long = "counter", number_of_values = 1)]
+    pub counters: Vec<CounterPattern>,
+}
+
+/// Units of the amounts shown
+#[derive(StructOpt, Debug, PartialEq)]
+pub struct UnitOpt {
+    /// Show amounts in bits instead of bytes, e.g. `Mibit/s`
+    #[structopt(long = "bits")]
+    pub bits: bool,
+
+    /// Show amounts with decimal (SI) prefixes instead of binary (IEC) ones, e.g. `MB/s`
+    #[structopt(long = "si")]
+    pub si: bool,
+}
+
+impl UnitOpt {
+    pub fn units(&self) -> Units {
+        Units {
+            bits: self.bits,
+            si: self.si,
+        }
+    }
+}
+
+/// The config file, which sets the options not given on the command line
+#[derive(StructOpt, Debug, PartialEq)]
+pub struct ConfigOpt {
+    /// Config file. Defaults to `~/.config/mone/config.toml`
     ///
-    /// - tui: output in TUI mode{n}- simple: output simple log to stdout{n}
-    #[structopt(
-        short = "w",
-        long = "writer",
-        raw(possible_values = "&WriterType::variants()"),
-        raw(default_value = "DEFAULT_WRITER")
-    )]
-    pub writer: WriterType,
+    /// Keys are named after the long flags, e.g. `theme = "light"` or `writer = ["tui"]`. Flags
+    /// given on the command line override the file.
+    #[structopt(long = "config", parse(from_os_str))]
+    pub config: Option<PathBuf>,
 
-    /// Number of stats history to show
-    #[structopt(short = "n", default_value = "180")]
-    pub n: usize,
+    /// Profile of the config file to use, defined in a `[profile.<name>]` table
+    #[structopt(long = "profile")]
+    pub profile: Option<String>,
+}
+
+/// Options of `mone` itself, which runs `live` unless a command is given. Without a command it
+/// takes the options of `live`, and with one it takes no options.
+#[derive(StructOpt, Debug, PartialEq)]
+#[structopt(raw(setting = "structopt::clap::AppSettings::ArgsNegateSubcommands"))]
+pub struct Opt {
+    #[structopt(flatten)]
+    pub live: LiveOpt,
+
+    #[structopt(subcommand)]
+    pub cmd: Option<Command>,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub enum Command {
+    /// Show the network stats with the writers. This is the default.
+    #[structopt(name = "live")]
+    Live(LiveOpt),
+
+    /// List the network interfaces with their addresses, flags, state and link settings
+    #[structopt(name = "list")]
+    List {
+        /// Output JSON
+        #[structopt(long = "json")]
+        json: bool,
+    },
+
+    /// Record the network stats to a file, for `replay` and `report`
+    ///
+    /// Stop with Ctrl+C, `--count` or `--duration`.
+    #[structopt(name = "record")]
+    Record {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        #[structopt(flatten)]
+        live: LiveOpt,
+    },
+
+    /// Show a file made with `record`, or a pcap or pcapng file, with the writers, at the
+    /// recorded pace
+    #[structopt(name = "replay")]
+    Replay {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// Replay this many times faster. The rates stay those recorded, averaged over the
+        /// longer ticks.
+        #[structopt(long = "speed", default_value = "1")]
+        speed: f64,
+
+        /// Series of the packets of pcap and pcapng files
+        #[structopt(
+            long = "by",
+            raw(possible_values = "&Breakdown::variants()"),
+            default_value = "interface"
+        )]
+        by: Breakdown,
+
+        #[structopt(flatten)]
+        live: LiveOpt,
+    },
+
+    /// Summarize a file made with `record`, or a pcap or pcapng file
+    #[structopt(name = "report")]
+    Report {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// Series of the packets of pcap and pcapng files
+        #[structopt(
+            long = "by",
+            raw(possible_values = "&Breakdown::variants()"),
+            default_value = "interface"
+        )]
+        by: Breakdown,
+
+        #[structopt(flatten)]
+        units: UnitOpt,
+    },
+
+    /// Sample the stats and exit with a Nagios / Icinga plugin status
+    ///
+    /// Prints a plugin line with perfdata and exits with 0 (OK), 1 (WARNING), 2 (CRITICAL) or
+    /// 3 (UNKNOWN).
+    #[structopt(name = "check")]
+    Check(CheckOpt),
 }
 
-pub fn run(opt: &Opt) -> Result<()> {
+pub fn new_reader(opt: &ReaderOpt) -> Result<Box<dyn Read + Send>> {
     let reader: Box<dyn Read + Send> = match opt.reader {
         ReaderType::libc => Box::new(LibcReader::new()?),
+        ReaderType::ethtool => Box::new(EthtoolReader::new(&opt.counters)?),
     };
-    let writer: Box<dyn Write> = match opt.writer {
-        WriterType::tui => Box::new(TuiWriter::new(&opt, reader.get_info(), reader.read())?),
+    let reader: Box<dyn Read + Send> = if opt.netns.is_empty() {
+        reader
+    } else {
+        Box::new(NetnsReader::new(reader, &opt.netns)?)
+    };
+    let reader: Box<dyn Read + Send> = if opt.containers {
+        Box::new(ContainerReader::new(reader)?)
+    } else {
+        reader
+    };
+    filter_reader(opt, reader)
+}
+
+fn filter_reader(opt: &ReaderOpt, reader: Box<dyn Read + Send>) -> Result<Box<dyn Read + Send>> {
+    if opt.interfaces.is_empty() {
+        Ok(reader)
+    } else {
+        Ok(Box::new(FilterReader::new(reader, &opt.interfaces)?))
+    }
+}
+
+fn new_writer(
+    opt: &LiveOpt,
+    spec: &WriterSpec,
+    reader: &(dyn Read + Send),
+) -> Result<Box<dyn Write + Send>> {
+    let output: Box<dyn io::Write + Send> = match &spec.output {
+        None => Box::new(io::stdout()),
+        Some(_) if spec.writer == WriterType::tui => {
+            return Err(Error::Other("The tui writer can not write to a file."))
+        }
+        Some(path) => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+    };
+    Ok(match spec.writer {
+        WriterType::tui => Box::new(TuiWriter::<DefaultTuiBackend>::new(
+            opt,
+            reader.get_info(),
+            reader.read(),
+        )?),
         WriterType::simple => Box::new(SimpleWriter::new(
-            io::stdout(),
+            output,
+            opt.utilization_threshold,
             reader.get_info(),
             reader.read(),
         )?),
+        WriterType::spark => Box::new(SparkWriter::new(
+            output,
+            spec.width.unwrap_or(opt.width),
+            reader.get_info(),
+            reader.read(),
+        )?),
+        WriterType::status => Box::new(StatusWriter::new(
+            output,
+            spec.format.as_ref().unwrap_or(&opt.format),
+            spec.status_mode.unwrap_or(opt.status_mode),
+            reader.get_info(),
+            reader.read(),
+        )?),
+    })
+}
+
+/// Runs the command of `opt` and returns the exit code.
+pub fn run(opt: &Opt) -> Result<i32> {
+    match &opt.cmd {
+        None => live(&opt.live, new_reader(&opt.live.reader)?, None)?,
+        Some(Command::Live(live_opt)) => live(live_opt, new_reader(&live_opt.reader)?, None)?,
+        Some(Command::List { json }) => list::run(*json)?,
+        Some(Command::Record { path, live }) => record(live, path)?,
+        Some(Command::Replay {
+            path,
+            speed,
+            by,
+            live: live_opt,
+        }) => {
+            if !(*speed > 0.0 && speed.is_finite()) {
+                return Err(Error::Other("`--speed` must be a positive number."));
+            }
+            let reader = ReplayReader::new(Recording::load(path, *by)?).with_speed(*speed);
+            let duration = Duration::from_secs_f64(reader.duration());
+            let reader = filter_reader(&live_opt.reader, Box::new(reader))?;
+            live(live_opt, reader, Some(duration))?
+        }
+        Some(Command::Report { path, by, units }) => {
+            set_units(units.units());
+            report::run(path, *by)?
+        }
+        // `check` reports its result with the exit code
+        Some(Command::Check(check)) => return Ok(check::run(check) as i32),
+    }
+    Ok(0)
+}
+
+/// Runs the writers of `opt`, stopping after `duration` unless `--count` or `--duration` is
+/// given.
+fn live(opt: &LiveOpt, reader: Box<dyn Read + Send>, duration: Option<Duration>) -> Result<()> {
+    set_units(opt.units());
+    if opt
+        .writers
+        .iter()
+        .filter(|spec| spec.output.is_none())
+        .count()
+        > 1
+    {
+        return Err(Error::Other(
+            "At most one writer can write to stdout. Give the others a file, e.g. `simple=mone.log`.",
+        ));
+    }
+    let mut writers = vec![];
+    for spec in opt.writers.iter() {
+        writers.push((spec.to_string(), new_writer(opt, spec, reader.as_ref())?));
+    }
+    monitor(opt, reader, writers, duration)
+}
+
+fn record(opt: &LiveOpt, path: &Path) -> Result<()> {
+    let reader = new_reader(&opt.reader)?;
+    let writer = RecordWriter::new(File::create(path)?, reader.get_info(), reader.read())?;
+    let writers: Vec<(String, Box<dyn Write + Send>)> =
+        vec![(format!("record={}", path.display()), Box::new(writer))];
+    monitor(opt, reader, writers, None)
+}
+
+fn monitor(
+    opt: &LiveOpt,
+    reader: Box<dyn Read + Send>,
+    writers: Vec<(String, Box<dyn Write + Send>)>,
+    duration: Option<Duration>,
+) -> Result<()> {
+    let alerts = if opt.alerts.is_empty() {
+        None
+    } else {
+        let mut actions: Vec<Box<dyn Action>> = vec![];
+        if let Some(command) = &opt.alert_command {
+            actions.push(Box::new(CommandAction::new(command)));
+        }
+        if let Some(path) = &opt.alert_log {
+            actions.push(Box::new(LogAction::new(path)));
+        }
+        if let Some(url) = &opt.alert_webhook {
+            actions.push(Box::new(WebhookAction::new(url)?));
+        }
+        Some(Alerts::new(
+            opt.alerts.clone(),
+            actions,
+            reader.get_info(),
+            reader.read(),
+        ))
     };
 
-    let mut monitor = Monitor::new(reader, writer);
+    let mut monitor = Monitor::new(reader, writers);
+    if let Some(alerts) = alerts {
+        monitor = monitor.with_alerts(alerts);
+    }
+    if let Some(count) = opt.count {
+        monitor = monitor.with_count(count);
+    }
+    if let Some(duration) = opt.duration.or(duration) {
+        monitor = monitor.with_duration(duration);
+    }
     monitor.run()
 }
 
@@ -235,10 +855,103 @@ mod tests {
     fn test_parse_opt() {
         let opt = Opt::from_args();
         let expected = Opt {
-            reader: ReaderType::libc,
-            writer: WriterType::tui,
-            n: 180,
+            live: LiveOpt {
+                reader: ReaderOpt {
+                    reader: ReaderType::libc,
+                    interfaces: vec![],
+                    netns: vec![],
+                    containers: false,
+                    counters: vec![],
+                },
+                writers: vec![WriterSpec {
+                    writer: WriterType::tui,
+                    output: None,
+                    format: None,
+                    status_mode: None,
+                    width: None,
+                }],
+                n: 180,
+                count: None,
+                duration: None,
+                theme: ThemeType::dark,
+                colors: vec![],
+                format: "{name} ↓{rx} ↑{tx}".to_string(),
+                status_mode: StatusMode::plain,
+                width: 30,
+                utilization_threshold: 80.0,
+                alerts: vec![],
+                alert_command: None,
+                alert_log: None,
+                alert_webhook: None,
+                units: UnitOpt {
+                    bits: false,
+                    si: false,
+                },
+                config: ConfigOpt {
+                    config: None,
+                    profile: None,
+                },
+            },
+            cmd: None,
         };
         assert_eq!(opt, expected);
     }
+
+    #[test]
+    fn test_parse_command() {
+        let parse = |args: &[&str]| Opt::clap().get_matches_from_safe([&["mone"], args].concat());
+        assert!(parse(&["-w", "simple"]).is_ok());
+        assert!(parse(&["live", "-w", "simple"]).is_ok());
+        assert!(parse(&["replay", "a.mone", "-w", "simple", "--count", "3"]).is_ok());
+        assert!(parse(&["check", "-i", "eth0", "-w", "80MiB", "--netns", "all"]).is_ok());
+        // the options of `live` do nothing for the other commands
+        assert!(parse(&["list", "-w", "simple"]).is_err());
+        assert!(parse(&["report", "a.mone", "--theme", "light"]).is_err());
+        assert!(parse(&["report", "a.mone", "--bits"]).is_ok());
+        assert!(parse(&["-w", "simple", "list"]).is_err());
+    }
+
+    #[test]
+    fn test_utilization() {
+        let stat = InterfaceStat {
+            rx: NumBytes::from(100_000_000 / 8),
+            tx: NumBytes::from(0),
+            drops: 0,
+        };
+        let link = LinkSettings {
+            speed: Some(1000),
+            ..LinkSettings::default()
+        };
+        let utilization = Utilization::new(&stat, &link).unwrap();
+        assert_eq!((utilization.rx, utilization.tx), (10.0, 0.0));
+        assert_eq!(utilization.to_string(), " 10.0%   0.0%");
+        assert_eq!(Utilization::new(&stat, &LinkSettings::default()), None);
+    }
+
+    #[test]
+    fn test_per_sec() {
+        let diff = InterfaceStats(vec![
+            None,
+            Some(InterfaceStat {
+                rx: NumBytes::from(3000),
+                tx: NumBytes::from(1),
+                drops: 6,
+            }),
+        ]);
+        assert_eq!(diff.clone().per_sec(Duration::from_secs(1)), diff);
+        let per_sec = diff.per_sec(Duration::from_secs(3));
+        let stat = per_sec.0[1].as_ref().unwrap();
+        assert_eq!((stat.rx.value(), stat.tx.value(), stat.drops), (1000, 0, 2));
+        assert_eq!(per_sec.0[0], None);
+    }
+
+    #[test]
+    fn test_parse_writer_spec() {
+        let spec: WriterSpec = "simple=/var/log/mone.log".parse().unwrap();
+        assert_eq!(spec.writer, WriterType::simple);
+        assert_eq!(spec.output, Some(PathBuf::from("/var/log/mone.log")));
+        assert_eq!(spec.to_string(), "simple=/var/log/mone.log");
+        assert_eq!("tui".parse::<WriterSpec>().unwrap().output, None);
+        assert!("curses".parse::<WriterSpec>().is_err());
+    }
 }
@@ -0,0 +1,261 @@
+//! `mone list`: the network interfaces with their addresses, flags and state.
+
+use nix::net::if_::InterfaceFlags;
+use nix::sys::socket::SockAddr;
+use serde_json::json;
+use std::fmt;
+
+use crate::reader::in_libc::{interface_addresses, interface_info_item, InterfaceAddress};
+use crate::reader::sysfs::LinkSettings;
+use crate::{InterfaceInfoItem, Result};
+
+/// An interface with its addresses, in the order returned by `getifaddrs`.
+pub struct InterfaceListing {
+    pub item: InterfaceInfoItem,
+    pub addresses: Vec<InterfaceAddress>,
+}
+
+impl InterfaceListing {
+    /// Groups `addrs` by interface.
+    pub fn group(addrs: Vec<InterfaceAddress>) -> Vec<InterfaceListing> {
+        let mut names: Vec<String> = vec![];
+        for addr in addrs.iter() {
+            if !names.contains(&addr.interface_name) {
+                names.push(addr.interface_name.clone());
+            }
+        }
+        names
+            .iter()
+            .map(|name| InterfaceListing {
+                item: interface_info_item(name, &addrs),
+                addresses: addrs
+                    .iter()
+                    .filter(|addr| &addr.interface_name == name)
+                    .cloned()
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let item = &self.item;
+        json!({
+            "name": item.name(),
+            "state": if item.is_up() { "up" } else { "down" },
+            "flags": flag_names(item.flags),
+            "mac": item.mac,
+            "mtu": item.link.mtu,
+            "speed": item.link.speed,
+            "duplex": item.link.duplex.map(|duplex| duplex.to_string()),
+            "ipv4": item.ipv4,
+            "ipv6": item.ipv6,
+        })
+    }
+}
+
+const FLAG_NAMES: &[(InterfaceFlags, &str)] = &[
+    (InterfaceFlags::IFF_UP, "UP"),
+    (InterfaceFlags::IFF_BROADCAST, "BROADCAST"),
+    (InterfaceFlags::IFF_DEBUG, "DEBUG"),
+    (InterfaceFlags::IFF_LOOPBACK, "LOOPBACK"),
+    (InterfaceFlags::IFF_POINTOPOINT, "POINTOPOINT"),
+    (InterfaceFlags::IFF_RUNNING, "RUNNING"),
+    (InterfaceFlags::IFF_NOARP, "NOARP"),
+    (InterfaceFlags::IFF_PROMISC, "PROMISC"),
+    (InterfaceFlags::IFF_ALLMULTI, "ALLMULTI"),
+    (InterfaceFlags::IFF_MULTICAST, "MULTICAST"),
+    #[cfg(target_os = "linux")]
+    (InterfaceFlags::IFF_LOWER_UP, "LOWER_UP"),
+    #[cfg(target_os = "linux")]
+    (InterfaceFlags::IFF_DORMANT, "DORMANT"),
+];
+
+/// `IFF_UP | IFF_BROADCAST` -> `["UP", "BROADCAST"]`
+///
+/// The `Debug` output of `InterfaceFlags` can not be used since some flags of the `ifreq` of
+/// TUN/TAP devices share the same bits.
+pub fn flag_names(flags: InterfaceFlags) -> Vec<&'static str> {
+    FLAG_NAMES
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Formats the MTU, speed and duplex that are known, e.g. `mtu 1500 speed 1000Mb/s duplex full`.
+pub fn format_link(link: &LinkSettings) -> String {
+    let mut parts = vec![];
+    if let Some(mtu) = link.mtu {
+        parts.push(format!("mtu {}", mtu));
+    }
+    if let Some(speed) = link.speed {
+        parts.push(format!("speed {}Mb/s", speed));
+    }
+    if let Some(duplex) = link.duplex {
+        parts.push(format!("duplex {}", duplex));
+    }
+    parts.join(" ")
+}
+
+/// Formats an IP address line, e.g. `inet 192.168.0.2 netmask 255.255.255.0`.
+fn format_address(addr: &InterfaceAddress) -> Option<String> {
+    let ip = |addr: &Option<SockAddr>| match addr {
+        Some(SockAddr::Inet(inet)) => Some(inet.to_std().ip()),
+        _ => None,
+    };
+    let address = ip(&addr.address)?;
+    let mut line = format!(
+        "{} {}",
+        if address.is_ipv4() { "inet" } else { "inet6" },
+        address
+    );
+    if let Some(netmask) = ip(&addr.netmask) {
+        line += &format!(" netmask {}", netmask);
+    }
+    if let Some(broadcast) = ip(&addr.broadcast) {
+        line += &format!(" broadcast {}", broadcast);
+    }
+    if let Some(destination) = ip(&addr.destination) {
+        line += &format!(" destination {}", destination);
+    }
+    Some(line)
+}
+
+impl fmt::Display for InterfaceListing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let item = &self.item;
+        write!(
+            f,
+            "{}: <{}> state {}",
+            item.name(),
+            flag_names(item.flags).join(","),
+            if item.is_up() { "UP" } else { "DOWN" }
+        )?;
+        let link = format_link(&item.link);
+        if !link.is_empty() {
+            write!(f, " {}", link)?;
+        }
+        writeln!(f)?;
+        if let Some(mac) = &item.mac {
+            writeln!(f, "    ether {}", mac)?;
+        }
+        for line in self.addresses.iter().filter_map(format_address) {
+            writeln!(f, "    {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn run(json: bool) -> Result<()> {
+    let mut listings = InterfaceListing::group(interface_addresses()?);
+    for listing in listings.iter_mut() {
+        listing.item.link = LinkSettings::read(listing.item.name());
+    }
+    if json {
+        let values: Vec<_> = listings.iter().map(InterfaceListing::to_json).collect();
+        println!("{}", serde_json::Value::Array(values));
+    } else {
+        for listing in listings {
+            print!("{}", listing);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::sysfs::Duplex;
+    use nix::sys::socket::InetAddr;
+    use std::net::SocketAddr;
+
+    fn inet(addr: &str) -> Option<SockAddr> {
+        let addr: SocketAddr = format!("{}:0", addr)
+            .parse()
+            .or_else(|_| format!("[{}]:0", addr).parse())
+            .unwrap();
+        Some(SockAddr::new_inet(InetAddr::from_std(&addr)))
+    }
+
+    fn address(
+        name: &str,
+        flags: InterfaceFlags,
+        address: &str,
+        netmask: &str,
+    ) -> InterfaceAddress {
+        InterfaceAddress {
+            interface_name: name.to_string(),
+            flags,
+            address: inet(address),
+            netmask: inet(netmask),
+            broadcast: None,
+            destination: None,
+            data: None,
+        }
+    }
+
+    fn listings() -> Vec<InterfaceListing> {
+        let up = InterfaceFlags::IFF_UP | InterfaceFlags::IFF_RUNNING;
+        let mut eth0 = address("eth0", up, "192.168.0.2", "255.255.255.0");
+        eth0.broadcast = inet("192.168.0.255");
+        let mut listings = InterfaceListing::group(vec![
+            address("lo", up, "127.0.0.1", "255.0.0.0"),
+            eth0,
+            address("eth1", InterfaceFlags::IFF_UP, "10.0.0.1", "255.0.0.0"),
+            address("lo", up, "::1", "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"),
+        ]);
+        listings[1].item.link = LinkSettings {
+            mtu: Some(1500),
+            speed: Some(1000),
+            duplex: Some(Duplex::Full),
+        };
+        listings
+    }
+
+    #[test]
+    fn test_flag_names() {
+        assert_eq!(
+            flag_names(InterfaceFlags::IFF_UP | InterfaceFlags::IFF_BROADCAST),
+            vec!["UP", "BROADCAST"]
+        );
+        assert!(flag_names(InterfaceFlags::empty()).is_empty());
+    }
+
+    #[test]
+    fn test_list() {
+        let text: String = listings().iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            text,
+            [
+                "lo: <UP,RUNNING> state UP\n",
+                "    inet 127.0.0.1 netmask 255.0.0.0\n",
+                "    inet6 ::1 netmask ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff\n",
+                "eth0: <UP,RUNNING> state UP mtu 1500 speed 1000Mb/s duplex full\n",
+                "    inet 192.168.0.2 netmask 255.255.255.0 broadcast 192.168.0.255\n",
+                "eth1: <UP> state DOWN\n",
+                "    inet 10.0.0.1 netmask 255.0.0.0\n",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_list_json() {
+        let listings = listings();
+        assert_eq!(
+            listings[0].to_json(),
+            json!({
+                "name": "lo",
+                "state": "up",
+                "flags": ["UP", "RUNNING"],
+                "mac": null,
+                "mtu": null,
+                "speed": null,
+                "duplex": null,
+                "ipv4": ["127.0.0.1"],
+                "ipv6": ["::1"],
+            })
+        );
+        assert_eq!(listings[1].to_json()["duplex"], "full");
+    }
+}
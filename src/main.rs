@@ -1,14 +1,18 @@
 use std::process;
-use structopt::StructOpt;
 
 fn main() {
-    let result = {
-        let opt = mone::Opt::from_args();
-        mone::run(&opt)
+    let opt = match mone::config::load_opt() {
+        Ok(opt) => opt,
+        Err(err) => {
+            eprintln!("{:?}", err);
+            process::exit(1);
+        }
     };
 
+    let result = mone::run(&opt);
+
     process::exit(match result {
-        Ok(()) => 0,
+        Ok(code) => code,
         Err(err) => {
             eprintln!("{:?}", err);
             1
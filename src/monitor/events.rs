@@ -35,15 +35,23 @@ impl Default for Config {
 }
 
 impl Events {
-    pub fn new(reader: Box<dyn Read + Send>, writer: &mut Box<dyn Write>) -> Result<Events> {
-        Events::with_config(reader, writer, Config::default())
+    pub fn new<'a, I>(reader: Box<dyn Read + Send>, writers: I) -> Result<Events>
+    where
+        I: IntoIterator<Item = &'a mut Box<dyn Write + Send>>,
+    {
+        Events::with_config(reader, writers, Config::default())
     }
 
-    pub fn with_config(
+    /// Creates the events of `reader`. `writers` can request a shutdown with the callback passed
+    /// to `Write::setup_shutdown`.
+    pub fn with_config<'a, I>(
         reader: Box<dyn Read + Send>,
-        writer: &mut Box<dyn Write>,
+        writers: I,
         config: Config,
-    ) -> Result<Events> {
+    ) -> Result<Events>
+    where
+        I: IntoIterator<Item = &'a mut Box<dyn Write + Send>>,
+    {
         let (tx, rx) = mpsc::channel();
 
         let running = Arc::new(AtomicBool::new(true));
@@ -62,19 +70,20 @@ impl Events {
                     }
                     if t % config.tick_steps == 0 {
                         let stats = reader.read();
-                        if let Err(_) = tx.send(Event::Tick(stats)) {
+                        if tx.send(Event::Tick(stats)).is_err() {
                             break;
-                        };
+                        }
                     }
                 }
             })
         };
 
-        writer.setup_shutdown(Box::new(move || {
-            if let Err(_) = tx.send(Event::Shutdown) {
-                return;
-            };
-        }))?;
+        for writer in writers {
+            let tx = mpsc::Sender::clone(&tx);
+            writer.setup_shutdown(Box::new(move || {
+                tx.send(Event::Shutdown).unwrap_or(());
+            }))?;
+        }
 
         Ok(Events {
             running,
@@ -88,10 +97,7 @@ impl Iterator for Events {
     type Item = Event;
 
     fn next(&mut self) -> Option<Event> {
-        match self.rx.recv() {
-            Ok(event) => Some(event),
-            Err(_) => None,
-        }
+        self.rx.recv().ok()
     }
 }
 
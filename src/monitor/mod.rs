@@ -1,35 +1,190 @@
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use crate::alert::Alerts;
 use crate::reader::Read;
 use crate::writer::Write;
 use crate::{Error, Result};
 
+use self::writers::Writers;
+
 pub mod events;
+mod writers;
 
 pub struct Monitor {
     reader: Option<Box<dyn Read + Send>>,
-    writer: Box<dyn Write>,
+    /// Writers with their names used in error messages
+    writers: Vec<(String, Box<dyn Write + Send>)>,
+    alerts: Option<Alerts>,
+    count: Option<usize>,
+    duration: Option<Duration>,
 }
 
 impl Monitor {
-    pub fn new(reader: Box<dyn Read + Send>, writer: Box<dyn Write>) -> Monitor {
+    pub fn new(
+        reader: Box<dyn Read + Send>,
+        writers: Vec<(String, Box<dyn Write + Send>)>,
+    ) -> Monitor {
         Monitor {
             reader: Some(reader),
-            writer,
+            writers,
+            alerts: None,
+            count: None,
+            duration: None,
+        }
+    }
+
+    /// Stops after `count` ticks.
+    pub fn with_count(mut self, count: usize) -> Monitor {
+        self.count = Some(count);
+        self
+    }
+
+    /// Stops after the ticks covering `duration`, i.e. `duration` divided by the reader interval
+    /// rounded up.
+    pub fn with_duration(mut self, duration: Duration) -> Monitor {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Number of ticks after which to stop, if any. The duration is counted in ticks of
+    /// `interval` rather than measured, so that a slow tick does not end the run early.
+    fn tick_limit(&self, interval: Duration) -> Option<usize> {
+        let duration_ticks = self.duration.map(|duration| {
+            let ticks = duration.as_nanos().div_ceil(interval.as_nanos());
+            usize::try_from(ticks).unwrap_or(usize::MAX)
+        });
+        match (self.count, duration_ticks) {
+            (Some(count), Some(ticks)) => Some(count.min(ticks)),
+            (count, ticks) => count.or(ticks),
         }
     }
 
+    /// Evaluates `alerts` on every tick and passes the firing alerts to the writers.
+    pub fn with_alerts(mut self, alerts: Alerts) -> Monitor {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Runs until a writer requests a shutdown, every writer stopped or the count or duration
+    /// is reached. Returns the errors of the writers which failed, or else of the alert actions
+    /// which failed.
     pub fn run(&mut self) -> Result<()> {
+        let config = events::Config::default();
         let events = match self.reader.take() {
             None => return Err(Error::Other("Failed to initialize reader thread.")),
-            Some(reader) => events::Events::new(reader, &mut self.writer)?,
+            Some(reader) => events::Events::with_config(
+                reader,
+                self.writers.iter_mut().map(|(_, writer)| writer),
+                config,
+            )?,
         };
+        let mut writers = Writers::spawn(std::mem::take(&mut self.writers), config.reader_interval);
+        let tick_limit = self.tick_limit(config.reader_interval);
+        let mut ticks = 0;
 
         for event in events {
             match event {
-                events::Event::Tick(stats) => self.writer.update(stats)?,
+                events::Event::Tick(stats) => {
+                    let alerts = self.alerts.as_mut().map(|alerts| {
+                        alerts.update(&stats, Instant::now());
+                        alerts.firing()
+                    });
+                    writers.update(alerts, stats);
+                    ticks += 1;
+                    if !writers.is_running() || tick_limit.is_some_and(|limit| ticks >= limit) {
+                        break;
+                    }
+                }
                 events::Event::Shutdown => break,
             }
         }
 
-        Ok(())
+        let result = writers.finish();
+        // reported once the writers restored the terminal
+        let failures = self.alerts.take().map_or(vec![], Alerts::finish);
+        if result.is_err() || failures.is_empty() {
+            return result;
+        }
+        Err(Error::AlertError(format!(
+            "alert action failed: {}",
+            failures.join("; ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InterfaceInfo, InterfaceStats};
+    use std::sync::{Arc, Mutex};
+
+    struct EmptyReader(InterfaceInfo);
+
+    impl Read for EmptyReader {
+        fn get_info(&self) -> &InterfaceInfo {
+            &self.0
+        }
+
+        fn read(&self) -> InterfaceStats {
+            InterfaceStats::empty(0)
+        }
+    }
+
+    struct RecordWriter(Arc<Mutex<Vec<&'static str>>>);
+
+    impl Write for RecordWriter {
+        fn setup_shutdown(&mut self, _callback: Box<dyn Fn() + 'static + Send>) -> Result<()> {
+            Ok(())
+        }
+
+        fn update(&mut self, _stats: InterfaceStats) -> Result<()> {
+            self.0.lock().unwrap().push("update");
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.0.lock().unwrap().push("flush");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stop_after_count() {
+        let record = Arc::new(Mutex::new(vec![]));
+        let mut monitor = Monitor::new(
+            Box::new(EmptyReader(InterfaceInfo(vec![]))),
+            vec![(
+                "record".to_string(),
+                Box::new(RecordWriter(Arc::clone(&record))),
+            )],
+        )
+        .with_count(2);
+        monitor.run().unwrap();
+        assert_eq!(*record.lock().unwrap(), vec!["update", "update", "flush"]);
+    }
+
+    #[test]
+    fn test_tick_limit() {
+        let monitor = || Monitor::new(Box::new(EmptyReader(InterfaceInfo(vec![]))), vec![]);
+        let interval = Duration::from_secs(1);
+        assert_eq!(monitor().tick_limit(interval), None);
+        assert_eq!(monitor().with_count(5).tick_limit(interval), Some(5));
+        let with_duration = |millis| monitor().with_duration(Duration::from_millis(millis));
+        assert_eq!(with_duration(3000).tick_limit(interval), Some(3));
+        assert_eq!(with_duration(2999).tick_limit(interval), Some(3));
+        assert_eq!(with_duration(3001).tick_limit(interval), Some(4));
+        assert_eq!(
+            with_duration(3000).with_count(2).tick_limit(interval),
+            Some(2)
+        );
+        assert_eq!(
+            with_duration(3000).with_count(4).tick_limit(interval),
+            Some(3)
+        );
+        assert_eq!(
+            with_duration(1000).tick_limit(Duration::from_millis(300)),
+            Some(4)
+        );
     }
 }
@@ -0,0 +1,268 @@
+//! Runs each writer in its own thread, so that a failing or blocking writer does not stall the
+//! others.
+
+use std::sync::mpsc::{self, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::alert::FiringAlert;
+use crate::writer::{restore, Write};
+use crate::{Error, InterfaceStats, Result};
+
+struct Tick {
+    alerts: Option<Vec<FiringAlert>>,
+    stats: InterfaceStats,
+    /// Time covered by `stats` since the previous tick passed to the writer
+    interval: Duration,
+}
+
+struct WriterThread {
+    name: String,
+    tx: mpsc::SyncSender<Tick>,
+    thread: thread::JoinHandle<Result<()>>,
+    /// Number of ticks dropped since the last one queued
+    dropped: u32,
+}
+
+pub struct Writers {
+    threads: Vec<WriterThread>,
+    /// Interval of the reader
+    interval: Duration,
+    errors: Vec<String>,
+}
+
+impl Writers {
+    /// Number of ticks queued for a writer. Ticks are dropped for a writer which falls further
+    /// behind. Writers compute the rates from the previous stats they received, so a dropped
+    /// tick is merged into the next one, which is passed with the interval covering both.
+    const QUEUE_SIZE: usize = 4;
+
+    /// Time to wait for writers to stop.
+    const FINISH_TIMEOUT: Duration = Duration::from_secs(3);
+
+    /// Runs `writers` updated every `interval`.
+    pub fn spawn(writers: Vec<(String, Box<dyn Write + Send>)>, interval: Duration) -> Writers {
+        let threads = writers
+            .into_iter()
+            .map(|(name, mut writer)| {
+                let (tx, rx) = mpsc::sync_channel::<Tick>(Self::QUEUE_SIZE);
+                let thread = thread::spawn(move || {
+                    for tick in rx {
+                        if let Some(alerts) = tick.alerts {
+                            writer.update_alerts(&alerts)?;
+                        }
+                        writer.update_interval(tick.interval)?;
+                        writer.update(tick.stats)?;
+                    }
+                    writer.flush()
+                });
+                WriterThread {
+                    name,
+                    tx,
+                    thread,
+                    dropped: 0,
+                }
+            })
+            .collect();
+
+        Writers {
+            threads,
+            interval,
+            errors: vec![],
+        }
+    }
+
+    /// Returns `true` while at least one writer is running.
+    pub fn is_running(&self) -> bool {
+        !self.threads.is_empty()
+    }
+
+    /// Passes `stats`, and `alerts` if given, to every running writer.
+    pub fn update(&mut self, alerts: Option<Vec<FiringAlert>>, stats: InterfaceStats) {
+        let mut stopped = vec![];
+        for (i, writer) in self.threads.iter_mut().enumerate() {
+            let tick = Tick {
+                alerts: alerts.clone(),
+                stats: stats.clone(),
+                interval: self.interval * (writer.dropped + 1),
+            };
+            match writer.tx.try_send(tick) {
+                Ok(()) => writer.dropped = 0,
+                Err(TrySendError::Full(_)) => writer.dropped += 1,
+                Err(TrySendError::Disconnected(_)) => stopped.push(i),
+            }
+        }
+        for i in stopped.into_iter().rev() {
+            let writer = self.threads.remove(i);
+            self.join(writer, Instant::now() + Self::FINISH_TIMEOUT);
+        }
+    }
+
+    /// Joins `writer` if it stops before `deadline`, and otherwise leaves it running detached
+    /// after restoring the terminal.
+    fn join(&mut self, writer: WriterThread, deadline: Instant) {
+        let WriterThread {
+            name,
+            tx,
+            thread: handle,
+            ..
+        } = writer;
+        drop(tx);
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                // the writer is never dropped, so a terminal it put into raw mode is restored here
+                restore::restore();
+                restore::uninstall();
+                self.errors
+                    .push(format!("writer `{}` did not stop in time", name));
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        match handle.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => self.errors.push(format!("writer `{}`: {}", name, err)),
+            Err(_) => self.errors.push(format!("writer `{}` panicked", name)),
+        }
+    }
+
+    /// Lets every writer finish the queued ticks, and returns the errors of the writers which
+    /// failed or did not stop within `FINISH_TIMEOUT`.
+    pub fn finish(mut self) -> Result<()> {
+        let deadline = Instant::now() + Self::FINISH_TIMEOUT;
+        for writer in std::mem::take(&mut self.threads) {
+            self.join(writer, deadline);
+        }
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::WriterError(self.errors.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordWriter(Arc<Mutex<Vec<usize>>>);
+
+    impl Write for RecordWriter {
+        fn setup_shutdown(&mut self, _callback: Box<dyn Fn() + 'static + Send>) -> Result<()> {
+            Ok(())
+        }
+
+        fn update(&mut self, stats: InterfaceStats) -> Result<()> {
+            self.0.lock().unwrap().push(stats.0.len());
+            Ok(())
+        }
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn setup_shutdown(&mut self, _callback: Box<dyn Fn() + 'static + Send>) -> Result<()> {
+            Ok(())
+        }
+
+        fn update(&mut self, _stats: InterfaceStats) -> Result<()> {
+            Err(Error::Other("disk full"))
+        }
+    }
+
+    /// Records the intervals, and blocks in `update` until `gate` lets it pass.
+    struct SlowWriter {
+        intervals: Arc<Mutex<Vec<u64>>>,
+        gate: mpsc::Receiver<()>,
+    }
+
+    impl Write for SlowWriter {
+        fn setup_shutdown(&mut self, _callback: Box<dyn Fn() + 'static + Send>) -> Result<()> {
+            Ok(())
+        }
+
+        fn update_interval(&mut self, interval: Duration) -> Result<()> {
+            self.intervals.lock().unwrap().push(interval.as_secs());
+            Ok(())
+        }
+
+        fn update(&mut self, _stats: InterfaceStats) -> Result<()> {
+            self.gate.recv().map_err(|_| Error::Other("gate closed"))
+        }
+    }
+
+    fn slow_writer() -> (Writers, Arc<Mutex<Vec<u64>>>, mpsc::Sender<()>) {
+        let intervals = Arc::new(Mutex::new(vec![]));
+        let (gate_tx, gate) = mpsc::channel();
+        let writer = SlowWriter {
+            intervals: Arc::clone(&intervals),
+            gate,
+        };
+        let writers = Writers::spawn(
+            vec![("slow".to_string(), Box::new(writer))],
+            Duration::from_secs(1),
+        );
+        (writers, intervals, gate_tx)
+    }
+
+    #[test]
+    fn test_dropped_ticks_are_merged() {
+        let (mut writers, intervals, gate) = slow_writer();
+        // the first tick blocks the writer, the next ones fill the queue and the last two
+        // are dropped
+        writers.update(None, InterfaceStats::empty(0));
+        thread::sleep(Duration::from_millis(20));
+        for _ in 0..(Writers::QUEUE_SIZE + 2) {
+            writers.update(None, InterfaceStats::empty(0));
+        }
+        for _ in 0..=Writers::QUEUE_SIZE {
+            gate.send(()).unwrap();
+        }
+        thread::sleep(Duration::from_millis(20));
+        writers.update(None, InterfaceStats::empty(0));
+        gate.send(()).unwrap();
+        writers.finish().unwrap();
+        assert_eq!(*intervals.lock().unwrap(), vec![1, 1, 1, 1, 1, 3]);
+    }
+
+    #[test]
+    fn test_finish_does_not_wait_for_stuck_writer() {
+        let (mut writers, _intervals, _gate) = slow_writer();
+        writers.update(None, InterfaceStats::empty(0));
+        let started_at = Instant::now();
+        match writers.finish() {
+            Err(Error::WriterError(msg)) => assert_eq!(msg, "writer `slow` did not stop in time"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(started_at.elapsed() < Writers::FINISH_TIMEOUT * 2);
+    }
+
+    #[test]
+    fn test_failing_writer_does_not_stop_others() {
+        let record = Arc::new(Mutex::new(vec![]));
+        let mut writers = Writers::spawn(
+            vec![
+                ("failing".to_string(), Box::new(FailingWriter)),
+                (
+                    "record".to_string(),
+                    Box::new(RecordWriter(Arc::clone(&record))),
+                ),
+            ],
+            Duration::from_secs(1),
+        );
+        for len in 1..=3 {
+            writers.update(None, InterfaceStats::empty(len));
+            // let the writers catch up so that no tick is dropped
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(writers.is_running());
+        assert_eq!(writers.threads.len(), 1);
+
+        match writers.finish() {
+            Err(Error::WriterError(msg)) => assert_eq!(msg, "writer `failing`: disk full"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(*record.lock().unwrap(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,103 @@
+//! Restricts another Reader to some of its interfaces.
+
+use crate::reader::Read;
+use crate::{Error, InterfaceInfo, InterfaceStats, Result};
+
+pub struct FilterReader {
+    reader: Box<dyn Read + Send>,
+    info: InterfaceInfo,
+    /// Index in the info of `reader` of each interface kept
+    indices: Vec<usize>,
+}
+
+impl FilterReader {
+    /// Keeps the interfaces named in `names`, in the order of `reader`.
+    pub fn new(reader: Box<dyn Read + Send>, names: &[String]) -> Result<FilterReader> {
+        for name in names.iter() {
+            if reader.index(name).is_none() {
+                return Err(Error::ParseError(format!("interface `{}` not found", name)));
+            }
+        }
+        let indices: Vec<usize> = reader
+            .get_info()
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| names.contains(&item.name))
+            .map(|(i, _)| i)
+            .collect();
+        let info = InterfaceInfo(
+            indices
+                .iter()
+                .map(|&i| reader.get_info().0[i].clone())
+                .collect(),
+        );
+        Ok(FilterReader {
+            reader,
+            info,
+            indices,
+        })
+    }
+}
+
+impl Read for FilterReader {
+    fn get_info(&self) -> &InterfaceInfo {
+        &self.info
+    }
+
+    fn read(&self) -> InterfaceStats {
+        let mut stats = self.reader.read();
+        InterfaceStats(self.indices.iter().map(|&i| stats.0[i].take()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::NumBytes;
+    use crate::{InterfaceInfoItem, InterfaceStat};
+
+    struct FixedReader(InterfaceInfo);
+
+    impl Read for FixedReader {
+        fn get_info(&self) -> &InterfaceInfo {
+            &self.0
+        }
+
+        fn read(&self) -> InterfaceStats {
+            InterfaceStats(
+                (0..self.0 .0.len() as u64)
+                    .map(|i| {
+                        Some(InterfaceStat {
+                            rx: NumBytes::from(i),
+                            tx: NumBytes::from(i),
+                            drops: 0,
+                        })
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    fn fixed_reader() -> Box<dyn Read + Send> {
+        let item = |name: &str| InterfaceInfoItem::new(name);
+        Box::new(FixedReader(InterfaceInfo(vec![
+            item("lo"),
+            item("eth0"),
+            item("wlan0"),
+        ])))
+    }
+
+    #[test]
+    fn test_filter() {
+        let names = vec!["wlan0".to_string(), "lo".to_string()];
+        let reader = FilterReader::new(fixed_reader(), &names).unwrap();
+        assert_eq!(reader.get_info().0[0].name, "lo");
+        assert_eq!(reader.get_info().0[1].name, "wlan0");
+        let stats = reader.read();
+        assert_eq!(stats.0.len(), 2);
+        assert_eq!(stats.0[1].as_ref().unwrap().rx, NumBytes::from(2));
+
+        assert!(FilterReader::new(fixed_reader(), &["eth1".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! Index and `iflink` of each interface, from `RTM_GETLINK` of the `NETLINK_ROUTE` netlink
+//! socket, as `ip link` does.
+//!
+//! Unlike `/sys/class/net`, which shows the namespace sysfs was mounted in, the netlink socket
+//! shows the network namespace of the calling thread, e.g. one entered with `setns`.
+//!
+//! See `linux/rtnetlink.h` and `linux/if_link.h` for the layouts.
+
+use byteorder::{ByteOrder, NativeEndian};
+
+use crate::reader::tc::{attrs, dump, parse_with, socket, NLMSG_HDR_LEN};
+use crate::{Error, Result};
+
+const IFINFOMSG_LEN: usize = 16;
+const IFLA_LINK: u16 = 5;
+
+/// Builds a dump request of the interfaces.
+pub fn request(seq: u32) -> Vec<u8> {
+    let len = NLMSG_HDR_LEN + IFINFOMSG_LEN;
+    let mut buf = vec![0; len];
+    NativeEndian::write_u32(&mut buf[0..4], len as u32);
+    NativeEndian::write_u16(&mut buf[4..6], libc::RTM_GETLINK);
+    NativeEndian::write_u16(
+        &mut buf[6..8],
+        (libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16,
+    );
+    NativeEndian::write_u32(&mut buf[8..12], seq);
+    buf[NLMSG_HDR_LEN] = libc::AF_UNSPEC as u8;
+    buf
+}
+
+/// Parses an `ifinfomsg` followed by its attributes as `(ifindex, iflink)`. The `iflink` of an
+/// interface without `IFLA_LINK` is its own index, as in `/sys/class/net`.
+fn parse_msg(buf: &[u8]) -> Result<(u32, u32)> {
+    if buf.len() < IFINFOMSG_LEN {
+        return Err(Error::ParseError(format!(
+            "ifinfomsg is {} bytes, buffer is only {} bytes",
+            IFINFOMSG_LEN,
+            buf.len()
+        )));
+    }
+    let index = NativeEndian::read_u32(&buf[4..8]);
+    let link = attrs(&buf[IFINFOMSG_LEN..])
+        .find(|&(attr, payload)| attr == IFLA_LINK && payload.len() >= 4)
+        .map_or(index, |(_, payload)| NativeEndian::read_u32(&payload[0..4]));
+    Ok((index, link))
+}
+
+/// Parses the netlink messages in `buf`, a datagram received in reply to `request`.
+///
+/// Returns the `(ifindex, iflink)` of the interfaces, and whether `NLMSG_DONE` ended the dump.
+pub fn parse_messages(buf: &[u8]) -> Result<(Vec<(u32, u32)>, bool)> {
+    parse_with(buf, |msg_type, payload| match msg_type {
+        libc::RTM_NEWLINK => parse_msg(payload).map(Some),
+        _ => Ok(None),
+    })
+}
+
+/// Dumps the `(ifindex, iflink)` of the interfaces of the network namespace of the calling
+/// thread.
+pub fn links() -> Result<Vec<(u32, u32)>> {
+    let fd = socket()?;
+    let result = dump(fd, &request(1), parse_messages);
+    unsafe { libc::close(fd) };
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `RTM_NEWLINK` message of the interface `index`, with `IFLA_LINK` if `link` is given
+    fn message(index: u32, link: Option<u32>) -> Vec<u8> {
+        let mut buf = vec![0; NLMSG_HDR_LEN + IFINFOMSG_LEN];
+        NativeEndian::write_u16(&mut buf[4..6], libc::RTM_NEWLINK);
+        NativeEndian::write_u32(&mut buf[NLMSG_HDR_LEN + 4..NLMSG_HDR_LEN + 8], index);
+        // `IFLA_MTU`, then `IFLA_LINK`
+        for &(attr, value) in [(4, 1500)]
+            .iter()
+            .chain(link.map(|l| (IFLA_LINK, l)).iter())
+        {
+            let mut nla = vec![0; 8];
+            NativeEndian::write_u16(&mut nla[0..2], 8);
+            NativeEndian::write_u16(&mut nla[2..4], attr);
+            NativeEndian::write_u32(&mut nla[4..8], value);
+            buf.extend(nla);
+        }
+        let len = buf.len() as u32;
+        NativeEndian::write_u32(&mut buf[0..4], len);
+        buf
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let mut buf = message(1, None);
+        buf.extend(message(3, Some(24)));
+        let (links, done) = parse_messages(&buf).unwrap();
+        assert_eq!(links, vec![(1, 1), (3, 24)]);
+        assert!(!done);
+
+        let mut done_msg = vec![0; NLMSG_HDR_LEN];
+        NativeEndian::write_u32(&mut done_msg[0..4], NLMSG_HDR_LEN as u32);
+        NativeEndian::write_u16(&mut done_msg[4..6], libc::NLMSG_DONE as u16);
+        buf.extend(done_msg);
+        assert!(parse_messages(&buf).unwrap().1);
+
+        assert!(parse_messages(&buf[..NLMSG_HDR_LEN + 8]).is_err());
+    }
+
+    #[test]
+    fn test_request() {
+        let req = request(7);
+        assert_eq!(req.len(), 32);
+        assert_eq!(NativeEndian::read_u32(&req[0..4]), 32);
+        assert_eq!(NativeEndian::read_u16(&req[4..6]), libc::RTM_GETLINK);
+        assert_eq!(NativeEndian::read_u32(&req[8..12]), 7);
+    }
+}
@@ -0,0 +1,411 @@
+//! Traffic of the packets seen on the interfaces by protocol and port, from an `AF_PACKET`
+//! socket.
+//!
+//! The socket is opened at the first read, which needs root or `CAP_NET_RAW`, and is read by a
+//! thread of its own until the reader is dropped. Only the headers of the packets are copied,
+//! and their bytes are those of the IP packets, without the link layer headers.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::reader::packet::Class;
+use crate::Result;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClassCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub packets: u64,
+}
+
+impl ClassCounters {
+    pub fn bytes(&self) -> u64 {
+        self.rx_bytes + self.tx_bytes
+    }
+}
+
+/// Traffic of a class since the capture started.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassStat {
+    pub class: Class,
+    pub total: ClassCounters,
+    /// Traffic since the previous read
+    pub rate: ClassCounters,
+}
+
+/// Counters shared with the capture thread.
+#[derive(Debug, Default)]
+struct Counters {
+    classes: HashMap<Class, ClassCounters>,
+    /// Packets dropped by the kernel while the socket buffer was full
+    drops: u64,
+    /// Error which stopped the capture
+    error: Option<String>,
+}
+
+/// A running capture, stopped when dropped.
+struct Capture {
+    counters: Arc<Mutex<Counters>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        // the thread checks it at least every `capture::TIMEOUT`
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod capture {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use std::{ffi, io, mem, thread};
+
+    use super::{Capture, Counters};
+    use crate::reader::packet::classify;
+    use crate::{Error, Result};
+
+    const ETH_P_ALL: u16 = 0x0003;
+    const PACKET_OUTGOING: u8 = 4;
+    const PACKET_STATISTICS: libc::c_int = 6;
+    /// Enough for the IP and L4 headers of most packets
+    const SNAPLEN: usize = 128;
+    /// How long a read of the socket waits, and so how long the thread takes to stop
+    pub const TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn ifindex(name: &str) -> Option<u32> {
+        let name = ffi::CString::new(name).ok()?;
+        match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+            0 => None,
+            ifindex => Some(ifindex),
+        }
+    }
+
+    fn open() -> Result<libc::c_int> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::c_int::from(ETH_P_ALL.to_be()),
+            )
+        };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EPERM) {
+                return Err(Error::Other("Capturing packets needs root or CAP_NET_RAW."));
+            }
+            return Err(err.into());
+        }
+        let timeout = libc::timeval {
+            tv_sec: 0,
+            tv_usec: TIMEOUT.as_micros() as libc::suseconds_t,
+        };
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const _ as *const libc::c_void,
+                mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err.into());
+        }
+        Ok(fd)
+    }
+
+    /// Packets dropped since the previous call, from `PACKET_STATISTICS`.
+    fn drops(fd: libc::c_int) -> u64 {
+        let mut stats = libc::tpacket_stats {
+            tp_packets: 0,
+            tp_drops: 0,
+        };
+        let mut len = mem::size_of::<libc::tpacket_stats>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_PACKET,
+                PACKET_STATISTICS,
+                &mut stats as *mut _ as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret < 0 {
+            0
+        } else {
+            u64::from(stats.tp_drops)
+        }
+    }
+
+    fn run(fd: libc::c_int, ifindices: &[u32], counters: &Mutex<Counters>, stop: &AtomicBool) {
+        let mut buf = [0_u8; SNAPLEN];
+        let mut checked = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            if checked.elapsed() >= TIMEOUT {
+                let drops = drops(fd);
+                counters.lock().expect("Failed to aquire lock").drops += drops;
+                checked = Instant::now();
+            }
+            let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+            let mut addr_len = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+            // the length of the whole packet, even if it is longer than `buf`
+            let len = unsafe {
+                libc::recvfrom(
+                    fd,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    libc::MSG_TRUNC,
+                    &mut addr as *mut _ as *mut libc::sockaddr,
+                    &mut addr_len,
+                )
+            };
+            if len < 0 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => continue,
+                    _ => {
+                        counters.lock().expect("Failed to aquire lock").error =
+                            Some(err.to_string());
+                        return;
+                    }
+                }
+            }
+            let ifindex = addr.sll_ifindex as u32;
+            if !ifindices.is_empty() && !ifindices.contains(&ifindex) {
+                continue;
+            }
+            let len = len as usize;
+            let class = classify(u16::from_be(addr.sll_protocol), &buf[..len.min(SNAPLEN)]);
+            let mut counters = counters.lock().expect("Failed to aquire lock");
+            let class = counters.classes.entry(class).or_default();
+            if addr.sll_pkttype == PACKET_OUTGOING {
+                class.tx_bytes += len as u64;
+            } else {
+                class.rx_bytes += len as u64;
+            }
+            class.packets += 1;
+        }
+    }
+
+    /// Starts capturing on `interfaces`, or on every interface if empty. The socket is opened in
+    /// the network namespace of the calling thread.
+    pub fn start(interfaces: &[String]) -> Result<Capture> {
+        let ifindices: Vec<u32> = interfaces.iter().filter_map(|name| ifindex(name)).collect();
+        if !interfaces.is_empty() && ifindices.is_empty() {
+            return Err(Error::Other("None of the interfaces can be captured."));
+        }
+        let fd = open()?;
+        let counters = Arc::new(Mutex::new(Counters::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let capture = Capture {
+            counters: Arc::clone(&counters),
+            stop: Arc::clone(&stop),
+        };
+        thread::spawn(move || {
+            run(fd, &ifindices, &counters, &stop);
+            unsafe { libc::close(fd) };
+        });
+        Ok(capture)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod capture {
+    use super::Capture;
+    use crate::{Error, Result};
+
+    pub fn start(_interfaces: &[String]) -> Result<Capture> {
+        Err(Error::Other("Packet capture is only supported on Linux."))
+    }
+}
+
+pub struct CaptureReader {
+    /// Interfaces to capture on, every interface if empty. Those unknown to the kernel, such as
+    /// the interfaces of other network namespaces, are skipped.
+    interfaces: Vec<String>,
+    capture: Option<Capture>,
+    /// Counters of each class at the previous read
+    prev: HashMap<Class, ClassCounters>,
+}
+
+impl CaptureReader {
+    pub fn new(interfaces: &[String]) -> CaptureReader {
+        CaptureReader {
+            interfaces: interfaces.to_vec(),
+            capture: None,
+            prev: HashMap::new(),
+        }
+    }
+
+    /// Returns the classes seen since the first read, with the most traffic first. The capture
+    /// starts at the first read, which returns no class.
+    pub fn read(&mut self) -> Result<Vec<ClassStat>> {
+        let capture = match &self.capture {
+            Some(capture) => capture,
+            None => {
+                self.capture = Some(capture::start(&self.interfaces)?);
+                return Ok(vec![]);
+            }
+        };
+        let classes = {
+            let counters = capture.counters.lock().expect("Failed to aquire lock");
+            if let Some(err) = &counters.error {
+                return Err(crate::Error::ParseError(format!(
+                    "packet capture stopped: {}",
+                    err
+                )));
+            }
+            counters.classes.clone()
+        };
+        Ok(self.update(classes))
+    }
+
+    fn update(&mut self, classes: HashMap<Class, ClassCounters>) -> Vec<ClassStat> {
+        let mut stats: Vec<ClassStat> = classes
+            .iter()
+            .map(|(&class, &total)| {
+                let prev = self.prev.get(&class).cloned().unwrap_or_default();
+                ClassStat {
+                    class,
+                    total,
+                    rate: ClassCounters {
+                        rx_bytes: total.rx_bytes - prev.rx_bytes,
+                        tx_bytes: total.tx_bytes - prev.tx_bytes,
+                        packets: total.packets - prev.packets,
+                    },
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| {
+            (b.rate.bytes(), b.total.bytes(), a.class).cmp(&(
+                a.rate.bytes(),
+                a.total.bytes(),
+                b.class,
+            ))
+        });
+        self.prev = classes;
+        stats
+    }
+
+    /// Stops the capture, which starts again at the next read.
+    pub fn stop(&mut self) {
+        self.capture = None;
+        self.prev.clear();
+    }
+
+    /// Packets the kernel dropped since the capture started, as mone did not read them in time.
+    pub fn drops(&self) -> u64 {
+        self.capture.as_ref().map_or(0, |capture| {
+            capture
+                .counters
+                .lock()
+                .expect("Failed to aquire lock")
+                .drops
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counters(rx_bytes: u64, tx_bytes: u64, packets: u64) -> ClassCounters {
+        ClassCounters {
+            rx_bytes,
+            tx_bytes,
+            packets,
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let mut reader = CaptureReader::new(&[]);
+        let mut classes = HashMap::new();
+        classes.insert(Class::Tcp(443), counters(1000, 100, 5));
+        classes.insert(Class::Udp(53), counters(200, 200, 4));
+        let stats = reader.update(classes.clone());
+        assert_eq!(stats[0].class, Class::Tcp(443));
+        assert_eq!(stats[0].rate, counters(1000, 100, 5));
+
+        classes.insert(Class::Udp(53), counters(1200, 700, 10));
+        classes.insert(Class::Icmp, counters(84, 0, 1));
+        let stats = reader.update(classes);
+        let rates: Vec<(Class, ClassCounters)> = stats.iter().map(|s| (s.class, s.rate)).collect();
+        assert_eq!(
+            rates,
+            vec![
+                (Class::Udp(53), counters(1000, 500, 6)),
+                (Class::Icmp, counters(84, 0, 1)),
+                (Class::Tcp(443), counters(0, 0, 0)),
+            ]
+        );
+    }
+
+    /// Captures datagrams sent over the loopback interface of a network namespace made by
+    /// `unshare`, which needs user namespaces to be enabled.
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_capture_loopback() {
+        use nix::sched::{setns, CloneFlags};
+        use std::io::{BufRead, BufReader};
+        use std::net::UdpSocket;
+        use std::os::unix::io::AsRawFd;
+        use std::process::{Command, Stdio};
+        use std::{fs, thread, time::Duration};
+
+        let script = "ip link set lo up && echo ready && exec sleep 10";
+        let mut child = match Command::new("unshare")
+            .args(["-Urn", "sh", "-c", script])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => return,
+        };
+        let mut ready = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut ready)
+            .unwrap();
+        let path = format!("/proc/{}/ns/net", child.id());
+        // `setns` moves the calling thread only
+        let stats = thread::spawn(move || {
+            let file = fs::File::open(path).ok()?;
+            setns(file.as_raw_fd(), CloneFlags::CLONE_NEWNET).ok()?;
+            let mut reader = CaptureReader::new(&["lo".to_string()]);
+            // root of the namespace, but not of the host
+            reader.read().ok()?;
+            let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+            for _ in 0..10 {
+                sender
+                    .send_to(&[0; 100], receiver.local_addr().unwrap())
+                    .unwrap();
+            }
+            thread::sleep(Duration::from_millis(300));
+            reader.read().ok()
+        })
+        .join()
+        .unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let stats = match stats {
+            Some(stats) if ready.trim() == "ready" => stats,
+            // `unshare` or `setns` is not available in this sandbox
+            _ => return,
+        };
+        let udp: Vec<&ClassStat> = stats
+            .iter()
+            .filter(|s| matches!(s.class, Class::Udp(_)))
+            .collect();
+        assert_eq!(udp.len(), 1);
+        // each datagram is seen when sent and when received, with 28 bytes of IP and UDP headers
+        assert_eq!(udp[0].total, counters(1280, 1280, 20));
+    }
+}
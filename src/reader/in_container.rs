@@ -0,0 +1,391 @@
+//! Names the host side `veth` interfaces after the containers or pods on their other end.
+//!
+//! The `iflink` of a veth in `/sys/class/net` is the ifindex of its peer, which lives in the
+//! network namespace of a container. Indices are only unique within a namespace, so a pair is
+//! only confirmed when the `iflink` of the peer, read inside the namespace of a process through
+//! `/proc/<pid>/ns/net`, is the ifindex of the veth in turn. The container of the process is
+//! found in `/proc/<pid>/cgroup`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::reader::Read;
+use crate::{Error, InterfaceInfo, InterfaceStats, Result};
+
+/// A container found in `/proc/<pid>/cgroup`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Container {
+    /// Full id of the container
+    pub id: String,
+    /// Uid of the Kubernetes pod of the container
+    pub pod_uid: Option<String>,
+}
+
+/// `docker-<id>.scope`, `cri-containerd-<id>.scope`, `<id>` -> `<id>`
+fn container_id(component: &str) -> Option<&str> {
+    let id = component.trim_end_matches(".scope");
+    let id = id.rsplit('-').next()?;
+    if id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+/// `pod<uid>`, `kubepods-besteffort-pod<uid>.slice` -> `<uid>`, with `-` in place of `_`
+fn pod_uid(component: &str) -> Option<String> {
+    let component = component.trim_end_matches(".slice");
+    let i = component.rfind("pod")?;
+    let uid = component[i + 3..].replace('_', "-");
+    if uid.len() == 36 {
+        Some(uid)
+    } else {
+        None
+    }
+}
+
+/// Finds the container in the content of `/proc/<pid>/cgroup`, for both cgroup v1 and v2 and
+/// both the cgroupfs and systemd drivers.
+pub fn parse_cgroup(text: &str) -> Option<Container> {
+    text.lines().find_map(|line| {
+        let path = line.splitn(3, ':').nth(2)?;
+        let components: Vec<&str> = path.split('/').collect();
+        let id = components.iter().rev().find_map(|c| container_id(c))?;
+        Some(Container {
+            id: id.to_string(),
+            pod_uid: components.iter().rev().find_map(|c| pod_uid(c)),
+        })
+    })
+}
+
+/// Returns the `(ifindex, iflink)` of the interfaces of the network namespace `ns`, e.g.
+/// `/proc/<pid>/ns/net`, entered on a thread of its own as `setns` switches the namespace of the
+/// calling thread only.
+#[cfg(target_os = "linux")]
+pub fn namespace_links(ns: &Path) -> Result<Vec<(u32, u32)>> {
+    use nix::sched::{setns, CloneFlags};
+    use std::os::unix::io::AsRawFd;
+
+    let file = fs::File::open(ns)?;
+    std::thread::spawn(move || {
+        setns(file.as_raw_fd(), CloneFlags::CLONE_NEWNET)?;
+        crate::reader::iflink::links()
+    })
+    .join()
+    .unwrap_or(Err(Error::Other("The network namespace thread panicked.")))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn namespace_links(_ns: &Path) -> Result<Vec<(u32, u32)>> {
+    Err(Error::Other(
+        "Network namespaces are only supported on Linux.",
+    ))
+}
+
+/// Name of a container from the `config.v2.json` of docker under `root`, or of the pod from the
+/// log directories of the kubelet, `/var/log/pods/<namespace>_<name>_<uid>`.
+pub fn container_name(container: &Container, root: &Path) -> Option<String> {
+    if let Some(uid) = &container.pod_uid {
+        let pods = fs::read_dir(root.join("var/log/pods")).ok()?;
+        let suffix = format!("_{}", uid);
+        return pods
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .find_map(|dir| Some(dir.strip_suffix(&suffix)?.split_once('_')?.1.to_string()));
+    }
+    let config = root
+        .join("var/lib/docker/containers")
+        .join(&container.id)
+        .join("config.v2.json");
+    let config: serde_json::Value = serde_json::from_str(&fs::read_to_string(config).ok()?).ok()?;
+    Some(config["Name"].as_str()?.trim_start_matches('/').to_string())
+}
+
+fn read_u32(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Maps the host side veth interfaces in `sys_dir`, usually `/sys`, to the names of their
+/// containers, or the first 12 characters of their ids when the name is unknown. The interfaces
+/// of each namespace are read with `links`, usually `namespace_links`.
+///
+/// Returns an error if no namespace with a peer could be entered, e.g. without
+/// `CAP_SYS_ADMIN`.
+pub fn container_labels(
+    sys_dir: &Path,
+    proc_dir: &Path,
+    root: &Path,
+    links: impl Fn(&Path) -> Result<Vec<(u32, u32)>>,
+) -> Result<HashMap<String, String>> {
+    // (name, ifindex, iflink) of the host interfaces linked to another one, veths and vlans
+    let mut veths = vec![];
+    for entry in fs::read_dir(sys_dir.join("class/net"))? {
+        let entry = entry?;
+        match (
+            read_u32(&entry.path().join("ifindex")),
+            read_u32(&entry.path().join("iflink")),
+        ) {
+            (Some(index), Some(link)) if index != link => {
+                veths.push((entry.file_name().to_string_lossy().to_string(), index, link));
+            }
+            _ => {}
+        }
+    }
+    if veths.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // the lowest pid of each namespace, the init or pause process of a container
+    let own = fs::read_link(proc_dir.join("self/ns/net")).ok();
+    let mut namespaces = BTreeMap::new();
+    for entry in fs::read_dir(proc_dir)? {
+        let entry = entry?;
+        let pid: u32 = match entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        match fs::read_link(entry.path().join("ns/net")) {
+            Ok(ns) if Some(&ns) != own.as_ref() => {
+                let min = namespaces.entry(ns).or_insert(pid);
+                *min = pid.min(*min);
+            }
+            _ => {}
+        }
+    }
+
+    let mut labels = HashMap::new();
+    let mut error = None;
+    for &pid in namespaces.values() {
+        let dir = proc_dir.join(pid.to_string());
+        let peers = match links(&dir.join("ns/net")) {
+            Ok(peers) => peers,
+            Err(err) => {
+                error.get_or_insert(err);
+                continue;
+            }
+        };
+        // the veth is the `iflink` of its peer, and the peer the `iflink` of the veth
+        let pairs: Vec<&String> = veths
+            .iter()
+            .filter(|&&(_, index, link)| peers.contains(&(link, index)))
+            .map(|(name, _, _)| name)
+            .collect();
+        if pairs.is_empty() {
+            continue;
+        }
+        let container = match fs::read_to_string(dir.join("cgroup")) {
+            Ok(text) => parse_cgroup(&text),
+            Err(_) => None,
+        };
+        if let Some(container) = container {
+            let label = container_name(&container, root)
+                .unwrap_or_else(|| container.id.chars().take(12).collect());
+            for veth in pairs {
+                labels.insert(veth.clone(), label.clone());
+            }
+        }
+    }
+    match error {
+        Some(err) if labels.is_empty() => Err(err),
+        _ => Ok(labels),
+    }
+}
+
+/// Renames the veth interfaces of another Reader after their containers.
+pub struct ContainerReader {
+    reader: Box<dyn Read + Send>,
+    info: InterfaceInfo,
+}
+
+impl ContainerReader {
+    pub fn new(reader: Box<dyn Read + Send>) -> Result<ContainerReader> {
+        let labels = container_labels(
+            Path::new("/sys"),
+            Path::new("/proc"),
+            Path::new("/"),
+            namespace_links,
+        )?;
+        Ok(ContainerReader::with_labels(reader, &labels))
+    }
+
+    /// Renames the interfaces in `labels`. A container with several veths keeps the names of
+    /// the veths as `<container>:<veth>`.
+    pub fn with_labels(
+        reader: Box<dyn Read + Send>,
+        labels: &HashMap<String, String>,
+    ) -> ContainerReader {
+        let mut info = reader.get_info().clone();
+        for item in info.0.iter_mut() {
+            let label = match labels.get(&item.name) {
+                Some(label) => label,
+                None => continue,
+            };
+            if labels.values().filter(|l| *l == label).count() > 1 {
+                item.name = format!("{}:{}", label, item.name);
+            } else {
+                item.name = label.clone();
+            }
+        }
+        ContainerReader { reader, info }
+    }
+}
+
+impl Read for ContainerReader {
+    fn get_info(&self) -> &InterfaceInfo {
+        &self.info
+    }
+
+    fn read(&self) -> InterfaceStats {
+        self.reader.read()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_path;
+    use std::os::unix::fs::symlink;
+
+    const ID: &str = "3f4e0c6a9b1d2e8f7a6b5c4d3e2f1a0b9c8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f";
+    const POD: &str = "0c5a9d57-3b7e-4d0a-9f4e-2b6c8d1e0f3a";
+
+    #[test]
+    fn test_parse_cgroup() {
+        let docker = format!("12:pids:/docker/{}\n0::/\n", ID);
+        assert_eq!(
+            parse_cgroup(&docker),
+            Some(Container {
+                id: ID.to_string(),
+                pod_uid: None
+            })
+        );
+        let systemd = format!("0::/system.slice/docker-{}.scope\n", ID);
+        assert_eq!(parse_cgroup(&systemd).unwrap().id, ID);
+        let kubepods = format!(
+            "0::/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod{}.slice/cri-containerd-{}.scope\n",
+            POD.replace('-', "_"),
+            ID
+        );
+        let container = parse_cgroup(&kubepods).unwrap();
+        assert_eq!(
+            (container.id.as_str(), container.pod_uid),
+            (ID, Some(POD.to_string()))
+        );
+        let cgroupfs = format!("4:memory:/kubepods/burstable/pod{}/{}\n", POD, ID);
+        assert_eq!(
+            parse_cgroup(&cgroupfs).unwrap().pod_uid,
+            Some(POD.to_string())
+        );
+        assert_eq!(parse_cgroup("0::/user.slice/session-2.scope\n"), None);
+    }
+
+    #[test]
+    fn test_container_labels() {
+        let dir = temp_path("in_container");
+        let write = |path: &str, content: &str| {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        for &(name, index, link) in &[
+            ("eth0", "2", "2"),
+            ("eth0.100", "3", "2"),
+            ("veth1a2b3c", "24", "23"),
+            ("vethd4e5f6", "26", "25"),
+        ] {
+            write(&format!("sys/class/net/{}/ifindex", name), index);
+            write(&format!("sys/class/net/{}/iflink", name), link);
+        }
+        // mone itself, a docker container with a process of its own, and a pod
+        for (pid, ns, cgroup) in [
+            ("1", "net:[4026531840]", "0::/".to_string()),
+            ("10", "net:[4026532001]", format!("0::/docker/{}", ID)),
+            ("11", "net:[4026532001]", "0::/".to_string()),
+            (
+                "20",
+                "net:[4026532002]",
+                format!("0::/kubepods/pod{}/{}", POD, ID),
+            ),
+        ] {
+            write(&format!("proc/{}/cgroup", pid), &cgroup);
+            fs::create_dir_all(dir.join(format!("proc/{}/ns", pid))).unwrap();
+            symlink(ns, dir.join(format!("proc/{}/ns/net", pid))).unwrap();
+        }
+        fs::create_dir_all(dir.join("proc/self/ns")).unwrap();
+        symlink("net:[4026531840]", dir.join("proc/self/ns/net")).unwrap();
+        let links = |ns: &Path| -> Result<Vec<(u32, u32)>> {
+            let pid = ns.parent().unwrap().parent().unwrap().file_name().unwrap();
+            match pid.to_str().unwrap() {
+                "10" => Ok(vec![(1, 1), (23, 24)]),
+                "20" => Ok(vec![(1, 1), (25, 26)]),
+                pid => panic!("entered the namespace of {}", pid),
+            }
+        };
+
+        let labels = container_labels(&dir.join("sys"), &dir.join("proc"), &dir, links).unwrap();
+        assert_eq!(labels["veth1a2b3c"], &ID[..12]);
+        assert_eq!(labels["vethd4e5f6"], &ID[..12]);
+        assert_eq!(labels.len(), 2);
+
+        write(
+            &format!("var/lib/docker/containers/{}/config.v2.json", ID),
+            r#"{"ID": "3f4e", "Name": "/web"}"#,
+        );
+        fs::create_dir_all(dir.join(format!("var/log/pods/default_nginx-7c5d_{}", POD))).unwrap();
+        let labels = container_labels(&dir.join("sys"), &dir.join("proc"), &dir, links).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(labels["veth1a2b3c"], "web");
+        assert_eq!(labels["vethd4e5f6"], "nginx-7c5d");
+    }
+
+    #[test]
+    fn test_container_labels_same_peer_index() {
+        let dir = temp_path("in_container_same_peer_index");
+        let write = |path: &str, content: &str| {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        // the `eth0` of both pods is the third interface of its namespace, like `eth0.100`
+        for &(name, index, link) in &[
+            ("eth0", "2", "2"),
+            ("eth0.100", "3", "2"),
+            ("veth1a2b3c", "24", "3"),
+            ("vethd4e5f6", "26", "3"),
+        ] {
+            write(&format!("sys/class/net/{}/ifindex", name), index);
+            write(&format!("sys/class/net/{}/iflink", name), link);
+        }
+        let pod = |n: char| POD.replace('0', &n.to_string());
+        for (pid, ns, uid) in [
+            ("10", "net:[4026532001]", pod('1')),
+            ("20", "net:[4026532002]", pod('2')),
+        ] {
+            write(
+                &format!("proc/{}/cgroup", pid),
+                &format!("0::/kubepods/pod{}/{}", uid, ID),
+            );
+            fs::create_dir_all(dir.join(format!("proc/{}/ns", pid))).unwrap();
+            symlink(ns, dir.join(format!("proc/{}/ns/net", pid))).unwrap();
+            fs::create_dir_all(dir.join(format!("var/log/pods/default_pod{}_{}", pid, uid)))
+                .unwrap();
+        }
+        let links = |ns: &Path| -> Result<Vec<(u32, u32)>> {
+            let pid = ns.parent().unwrap().parent().unwrap().file_name().unwrap();
+            match pid.to_str().unwrap() {
+                "10" => Ok(vec![(1, 1), (3, 24)]),
+                _ => Ok(vec![(1, 1), (3, 26)]),
+            }
+        };
+
+        let labels = container_labels(&dir.join("sys"), &dir.join("proc"), &dir, links).unwrap();
+        assert_eq!(labels["veth1a2b3c"], "pod10");
+        assert_eq!(labels["vethd4e5f6"], "pod20");
+        assert_eq!(labels.len(), 2);
+
+        let denied =
+            |_: &Path| -> Result<Vec<(u32, u32)>> { Err(Error::Other("Operation not permitted")) };
+        assert!(container_labels(&dir.join("sys"), &dir.join("proc"), &dir, denied).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
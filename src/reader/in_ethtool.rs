@@ -0,0 +1,429 @@
+//! Input of driver specific counters from the `SIOCETHTOOL` ioctl, as `ethtool -S` shows.
+//!
+//! Each counter selected with `--counter` is a series named `<interface>:<counter>`, shown in
+//! the rx chart. A selected counter with `tx` in place of the first `rx` of its name, e.g.
+//! `tx_queue_0_bytes` for `rx_queue_0_bytes`, is shown in the tx chart of the same series, and
+//! other counters with `tx` in their names in the tx chart of their own series.
+
+use std::{fmt, str};
+
+use crate::reader::in_libc::LibcReader;
+use crate::reader::Read;
+use crate::utils::NumBytes;
+use crate::{Error, InterfaceInfo, InterfaceInfoItem, InterfaceStat, InterfaceStats, Result};
+
+/// Length of a name in `ETHTOOL_GSTRINGS`, `ETH_GSTRING_LEN`
+const GSTRING_LEN: usize = 32;
+
+/// Counters given as `[<interface>:]<counter>`, where both may contain the wildcards `*` and
+/// `?`, e.g. `eth0:rx_queue_*_bytes`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CounterPattern {
+    /// `None` means every interface
+    pub interface: Option<String>,
+    pub counter: String,
+}
+
+impl str::FromStr for CounterPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CounterPattern> {
+        let (interface, counter) = match s.split_once(':') {
+            Some((interface, counter)) => (Some(interface.to_string()), counter),
+            None => (None, s),
+        };
+        if counter.is_empty() {
+            return Err(Error::ParseError(format!("no counter in `{}`", s)));
+        }
+        Ok(CounterPattern {
+            interface,
+            counter: counter.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for CounterPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.interface {
+            Some(interface) => write!(f, "{}:{}", interface, self.counter),
+            None => write!(f, "{}", self.counter),
+        }
+    }
+}
+
+impl CounterPattern {
+    fn matches_interface(&self, interface: &str) -> bool {
+        self.interface
+            .as_ref()
+            .map_or(true, |pattern| wildcard_match(pattern, interface))
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any characters and `?` any character.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    // position of the last `*` in `pattern`, and of `name` when it was reached
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Splits the `data` of `ETHTOOL_GSTRINGS` into names.
+pub fn parse_strings(data: &[u8]) -> Vec<String> {
+    data.chunks(GSTRING_LEN)
+        .map(|name| {
+            let name = name.split(|&b| b == 0).next().unwrap_or_default();
+            String::from_utf8_lossy(name).to_string()
+        })
+        .collect()
+}
+
+/// A series of the reader, with the indices of its counters in the stats of `interface`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Series {
+    pub interface: String,
+    pub name: String,
+    pub rx: Option<usize>,
+    pub tx: Option<usize>,
+}
+
+/// Picks the counters of `interface` matching `patterns` and pairs them into series.
+pub fn select(interface: &str, names: &[String], patterns: &[CounterPattern]) -> Vec<Series> {
+    let selected: Vec<usize> = (0..names.len())
+        .filter(|&i| {
+            patterns
+                .iter()
+                .any(|p| p.matches_interface(interface) && wildcard_match(&p.counter, &names[i]))
+        })
+        .collect();
+    let find = |name: &str| selected.iter().cloned().find(|&i| names[i] == name);
+    let mut series = vec![];
+    for &i in selected.iter() {
+        let name = &names[i];
+        let (rx, tx) = if name.contains("rx") {
+            (Some(i), find(&name.replacen("rx", "tx", 1)))
+        } else if name.contains("tx") {
+            // paired with its rx counter
+            if find(&name.replacen("tx", "rx", 1)).is_some() {
+                continue;
+            }
+            (None, Some(i))
+        } else {
+            (Some(i), None)
+        };
+        series.push(Series {
+            interface: interface.to_string(),
+            name: format!("{}:{}", interface, name),
+            rx,
+            tx,
+        });
+    }
+    series
+}
+
+#[cfg(target_os = "linux")]
+mod ioctl {
+    use byteorder::{ByteOrder, NativeEndian};
+    use std::{io, mem};
+
+    use super::GSTRING_LEN;
+    use crate::Result;
+
+    const SIOCETHTOOL: libc::c_ulong = 0x8946;
+    const ETHTOOL_GSTRINGS: u32 = 0x1b;
+    const ETHTOOL_GSTATS: u32 = 0x1d;
+    const ETHTOOL_GSSET_INFO: u32 = 0x37;
+    const ETH_SS_STATS: u32 = 1;
+
+    #[repr(C)]
+    struct IfReq {
+        name: [u8; libc::IFNAMSIZ],
+        data: *mut u8,
+        _padding: [u8; 16],
+    }
+
+    /// Runs the ethtool command at the start of `buf`, an `ethtool_*` struct, on `interface`.
+    fn ethtool(interface: &str, buf: &mut [u64]) -> Result<()> {
+        let mut req = IfReq {
+            name: [0; libc::IFNAMSIZ],
+            data: buf.as_mut_ptr() as *mut u8,
+            _padding: [0; 16],
+        };
+        if interface.len() >= req.name.len() {
+            return Err(io::Error::from_raw_os_error(libc::ENODEV).into());
+        }
+        req.name[..interface.len()].copy_from_slice(interface.as_bytes());
+        let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let ret = unsafe { libc::ioctl(fd, SIOCETHTOOL as _, &mut req) };
+        let result = if ret < 0 {
+            Err(io::Error::last_os_error().into())
+        } else {
+            Ok(())
+        };
+        unsafe { libc::close(fd) };
+        result
+    }
+
+    /// Bytes of `buf` viewed as the `ethtool_*` struct.
+    fn bytes(buf: &mut [u64]) -> &mut [u8] {
+        unsafe {
+            std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, mem::size_of_val(buf))
+        }
+    }
+
+    /// Number of counters of `interface`, from `ETHTOOL_GSSET_INFO`.
+    fn n_stats(interface: &str) -> Result<usize> {
+        // `struct ethtool_sset_info` with one `data`
+        let mut buf = [0_u64; 3];
+        NativeEndian::write_u32(&mut bytes(&mut buf)[0..4], ETHTOOL_GSSET_INFO);
+        NativeEndian::write_u64(&mut bytes(&mut buf)[8..16], 1 << ETH_SS_STATS);
+        ethtool(interface, &mut buf)?;
+        let mask = NativeEndian::read_u64(&bytes(&mut buf)[8..16]);
+        if mask & (1 << ETH_SS_STATS) == 0 {
+            return Ok(0);
+        }
+        Ok(NativeEndian::read_u32(&bytes(&mut buf)[16..20]) as usize)
+    }
+
+    /// Names of the counters of `interface`.
+    pub fn strings(interface: &str) -> Result<Vec<String>> {
+        let n = n_stats(interface)?;
+        // `struct ethtool_gstrings`
+        let mut buf = vec![0_u64; (12 + n * GSTRING_LEN).div_ceil(8)];
+        NativeEndian::write_u32(&mut bytes(&mut buf)[0..4], ETHTOOL_GSTRINGS);
+        NativeEndian::write_u32(&mut bytes(&mut buf)[4..8], ETH_SS_STATS);
+        NativeEndian::write_u32(&mut bytes(&mut buf)[8..12], n as u32);
+        ethtool(interface, &mut buf)?;
+        let len = NativeEndian::read_u32(&bytes(&mut buf)[8..12]) as usize;
+        Ok(super::parse_strings(
+            &bytes(&mut buf)[12..12 + len.min(n) * GSTRING_LEN],
+        ))
+    }
+
+    /// Values of the `n` counters of `interface`.
+    pub fn stats(interface: &str, n: usize) -> Result<Vec<u64>> {
+        // `struct ethtool_stats`, whose `data` starts at 8 bytes
+        let mut buf = vec![0_u64; 1 + n];
+        NativeEndian::write_u32(&mut bytes(&mut buf)[0..4], ETHTOOL_GSTATS);
+        NativeEndian::write_u32(&mut bytes(&mut buf)[4..8], n as u32);
+        ethtool(interface, &mut buf)?;
+        let len = NativeEndian::read_u32(&bytes(&mut buf)[4..8]) as usize;
+        Ok(buf[1..1 + len.min(n)].to_vec())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod ioctl {
+    use crate::{Error, Result};
+
+    pub fn strings(_interface: &str) -> Result<Vec<String>> {
+        Err(Error::Other(
+            "The ethtool reader is only supported on Linux.",
+        ))
+    }
+
+    pub fn stats(_interface: &str, _n: usize) -> Result<Vec<u64>> {
+        Err(Error::Other(
+            "The ethtool reader is only supported on Linux.",
+        ))
+    }
+}
+
+pub struct EthtoolReader {
+    info: InterfaceInfo,
+    series: Vec<Series>,
+    /// Interfaces with a selected counter and their numbers of counters
+    interfaces: Vec<(String, usize)>,
+}
+
+impl EthtoolReader {
+    pub fn new(patterns: &[CounterPattern]) -> Result<EthtoolReader> {
+        if patterns.is_empty() {
+            return Err(Error::Other(
+                "The ethtool reader needs at least one `--counter`.",
+            ));
+        }
+        let libc = LibcReader::new()?;
+        let mut series = vec![];
+        let mut interfaces = vec![];
+        for item in libc.get_info().0.iter() {
+            if !patterns.iter().any(|p| p.matches_interface(&item.name)) {
+                continue;
+            }
+            // interfaces without driver counters, e.g. `lo`
+            let names = match ioctl::strings(&item.name) {
+                Ok(names) => names,
+                Err(_) => continue,
+            };
+            let selected = select(&item.name, &names, patterns);
+            if !selected.is_empty() {
+                interfaces.push((item.name.clone(), names.len()));
+                series.extend(selected);
+            }
+        }
+        if series.is_empty() {
+            let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+            return Err(Error::ParseError(format!(
+                "no ethtool counter matches `{}`",
+                patterns.join("`, `")
+            )));
+        }
+        let info = InterfaceInfo(
+            series
+                .iter()
+                .map(|s| InterfaceInfoItem::new(&s.name))
+                .collect(),
+        );
+        Ok(EthtoolReader {
+            info,
+            series,
+            interfaces,
+        })
+    }
+}
+
+impl Read for EthtoolReader {
+    fn get_info(&self) -> &InterfaceInfo {
+        &self.info
+    }
+
+    fn read(&self) -> InterfaceStats {
+        let values: Vec<(&str, Option<Vec<u64>>)> = self
+            .interfaces
+            .iter()
+            .map(|(name, n)| (name.as_str(), ioctl::stats(name, *n).ok()))
+            .collect();
+        InterfaceStats(
+            self.series
+                .iter()
+                .map(|s| {
+                    let values = values
+                        .iter()
+                        .find(|(name, _)| *name == s.interface)
+                        .and_then(|(_, values)| values.as_ref())?;
+                    let value = |i: Option<usize>| match i {
+                        Some(i) => values.get(i).cloned(),
+                        None => Some(0),
+                    };
+                    Some(InterfaceStat {
+                        rx: NumBytes::from(value(s.rx)?),
+                        tx: NumBytes::from(value(s.tx)?),
+                        drops: 0,
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("rx_queue_*_bytes", "rx_queue_12_bytes"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("rx?_bytes", "rx0_bytes"));
+        assert!(wildcard_match("*missed*", "rx_missed_errors"));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(!wildcard_match("rx_queue_*_bytes", "rx_queue_0_packets"));
+        assert!(!wildcard_match("rx?_bytes", "rx_bytes"));
+        assert!(!wildcard_match("eth0", "eth01"));
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        let p: CounterPattern = "eth*:rx_queue_*".parse().unwrap();
+        assert_eq!(p.interface, Some("eth*".to_string()));
+        assert!(p.matches_interface("eth1"));
+        assert!(!p.matches_interface("wlan0"));
+        let p: CounterPattern = "rx_missed_errors".parse().unwrap();
+        assert_eq!(p.interface, None);
+        assert_eq!(p.to_string(), "rx_missed_errors");
+        assert!("eth0:".parse::<CounterPattern>().is_err());
+    }
+
+    #[test]
+    fn test_parse_strings() {
+        let mut data = vec![0_u8; 2 * GSTRING_LEN];
+        data[..10].copy_from_slice(b"rx_packets");
+        data[GSTRING_LEN..GSTRING_LEN + 9].copy_from_slice(b"rx_missed");
+        assert_eq!(parse_strings(&data), names(&["rx_packets", "rx_missed"]));
+    }
+
+    #[test]
+    fn test_select() {
+        // `ethtool -S` of virtio_net
+        let counters = names(&[
+            "rx_queue_0_packets",
+            "rx_queue_0_bytes",
+            "rx_queue_0_drops",
+            "tx_queue_0_packets",
+            "tx_queue_0_bytes",
+            "tx_queue_0_kicks",
+            "rx_missed",
+        ]);
+        let patterns = [
+            "eth0:*_queue_0_bytes",
+            "tx_queue_0_kicks",
+            "*missed",
+            "wlan0:*",
+        ];
+        let patterns: Vec<CounterPattern> = patterns.iter().map(|p| p.parse().unwrap()).collect();
+        let series = select("eth0", &counters, &patterns);
+        let summary: Vec<(&str, Option<usize>, Option<usize>)> = series
+            .iter()
+            .map(|s| (s.name.as_str(), s.rx, s.tx))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("eth0:rx_queue_0_bytes", Some(1), Some(4)),
+                ("eth0:tx_queue_0_kicks", None, Some(5)),
+                ("eth0:rx_missed", Some(6), None),
+            ]
+        );
+        assert_eq!(select("eth1", &counters, &patterns[..1]), vec![]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_ioctl() {
+        // the first interface with driver counters, if any
+        let libc = LibcReader::new().unwrap();
+        for item in libc.get_info().0.iter() {
+            let names = match ioctl::strings(&item.name) {
+                Ok(names) if !names.is_empty() => names,
+                _ => continue,
+            };
+            let values = ioctl::stats(&item.name, names.len()).unwrap();
+            assert_eq!(values.len(), names.len());
+            assert!(names.iter().all(|name| !name.is_empty()));
+            return;
+        }
+    }
+}
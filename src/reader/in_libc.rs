@@ -2,9 +2,11 @@
 
 use libc::c_void;
 use nix::net::if_::InterfaceFlags;
-use nix::sys::socket::{AddressFamily, SockAddr};
+use nix::sys::socket::{AddressFamily, LinkAddr, SockAddr};
+use std::net::IpAddr;
 use std::{ffi, ptr};
 
+use crate::reader::sysfs::LinkSettings;
 use crate::reader::Read;
 use crate::utils::NumBytes;
 use crate::Result;
@@ -14,6 +16,8 @@ use crate::{InterfaceInfo, InterfaceInfoItem, InterfaceStat, InterfaceStats};
 pub struct IfData {
     ifi_ibytes: u32,
     ifi_obytes: u32,
+    /// packets dropped on input (and output, if available)
+    ifi_drops: u32,
 }
 
 impl IfData {
@@ -31,6 +35,7 @@ impl IfData {
             Ok(link_stats) => Some(IfData {
                 ifi_ibytes: link_stats.rx_bytes,
                 ifi_obytes: link_stats.tx_bytes,
+                ifi_drops: link_stats.rx_dropped.wrapping_add(link_stats.tx_dropped),
             }),
         }
     }
@@ -47,6 +52,7 @@ impl IfData {
         Some(IfData {
             ifi_ibytes: data.ifi_ibytes,
             ifi_obytes: data.ifi_obytes,
+            ifi_drops: data.ifi_iqdrops,
         })
     }
 }
@@ -174,18 +180,58 @@ impl Iterator for InterfaceAddressIterator {
     }
 }
 
+/// Returns every address of every interface, including the interfaces which are down.
+pub fn interface_addresses() -> Result<Vec<InterfaceAddress>> {
+    let addrs = get_interfaces()?;
+    let mut ret = vec![];
+    let mut next = addrs.base;
+    while let Some(ifaddr) = unsafe { next.as_ref() } {
+        next = ifaddr.ifa_next;
+        ret.push(InterfaceAddress::from_libc_ifaddrs(ifaddr));
+    }
+    Ok(ret)
+}
+
+/// Collects the addresses and flags of the interface `name` from `addrs`.
+pub fn interface_info_item(name: &str, addrs: &[InterfaceAddress]) -> InterfaceInfoItem {
+    let mut item = InterfaceInfoItem::new(name);
+    for addr in addrs.iter().filter(|addr| addr.interface_name == name) {
+        item.flags |= addr.flags;
+        match &addr.address {
+            Some(SockAddr::Inet(inet)) => match inet.to_std().ip() {
+                IpAddr::V4(ip) => item.ipv4.push(ip),
+                IpAddr::V6(ip) => item.ipv6.push(ip),
+            },
+            Some(SockAddr::Link(link)) if is_ether(link) => item.mac = Some(link.to_string()),
+            _ => {}
+        }
+    }
+    item
+}
+
+#[cfg(target_os = "linux")]
+fn is_ether(link: &LinkAddr) -> bool {
+    link.halen() == 6
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_ether(_link: &LinkAddr) -> bool {
+    true
+}
+
 pub struct LibcReader {
     info: InterfaceInfo,
 }
 
 impl LibcReader {
     pub fn new() -> Result<LibcReader> {
+        let addrs = interface_addresses()?;
         let mut info = vec![];
 
         for addr in get_interfaces()? {
-            info.push(InterfaceInfoItem {
-                name: addr.interface_name,
-            });
+            let mut item = interface_info_item(&addr.interface_name, &addrs);
+            item.link = LinkSettings::read(&addr.interface_name);
+            info.push(item);
         }
 
         Ok(LibcReader {
@@ -216,6 +262,7 @@ impl Read for LibcReader {
                         stats.0[i] = Some(InterfaceStat {
                             rx: NumBytes::from(data.ifi_ibytes as u64),
                             tx: NumBytes::from(data.ifi_obytes as u64),
+                            drops: u64::from(data.ifi_drops),
                         })
                     }
                 },
@@ -232,9 +279,8 @@ mod tests {
 
     #[test]
     fn test_libc_reader_new() {
-        match LibcReader::new() {
-            Err(err) => assert!(false, "`LibcReader::new()` returned an error: {}", err),
-            Ok(_) => assert!(true),
+        if let Err(err) = LibcReader::new() {
+            panic!("`LibcReader::new()` returned an error: {}", err);
         }
     }
 }
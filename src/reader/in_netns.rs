@@ -0,0 +1,296 @@
+//! Interfaces of other network namespaces, labelled `<namespace>:<interface>`.
+//!
+//! `setns` switches the namespace of the calling thread only, so each namespace is read by a
+//! thread of its own, which enters the namespace once and then reads it with a `LibcReader` on
+//! request.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::{fs, str, thread};
+
+use crate::reader::sysfs::LinkSettings;
+use crate::reader::Read;
+use crate::{Error, InterfaceInfo, InterfaceStats, Result};
+
+/// Directory of the namespaces named by `ip netns add`.
+pub const NETNS_DIR: &str = "/var/run/netns";
+
+/// A network namespace given with `--netns`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Netns {
+    /// `all`, every namespace in `NETNS_DIR`
+    All,
+    /// A namespace in `NETNS_DIR`
+    Named(String),
+    /// The namespace of a process, `/proc/<pid>/ns/net`
+    Pid(u32),
+    /// A namespace file, e.g. a bind mount made by a container runtime
+    Path(PathBuf),
+}
+
+impl str::FromStr for Netns {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Netns> {
+        if s.is_empty() {
+            return Err(Error::ParseError("empty network namespace".to_string()));
+        }
+        Ok(if s == "all" {
+            Netns::All
+        } else if let Ok(pid) = s.parse() {
+            Netns::Pid(pid)
+        } else if s.contains('/') {
+            Netns::Path(PathBuf::from(s))
+        } else {
+            Netns::Named(s.to_string())
+        })
+    }
+}
+
+impl Netns {
+    /// Prefix of the names of its interfaces.
+    pub fn label(&self) -> String {
+        match self {
+            Netns::All => "all".to_string(),
+            Netns::Named(name) => name.clone(),
+            Netns::Pid(pid) => pid.to_string(),
+            Netns::Path(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string_lossy().to_string()),
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Netns::All => PathBuf::from(NETNS_DIR),
+            Netns::Named(name) => Path::new(NETNS_DIR).join(name),
+            Netns::Pid(pid) => PathBuf::from(format!("/proc/{}/ns/net", pid)),
+            Netns::Path(path) => path.clone(),
+        }
+    }
+}
+
+/// Replaces `Netns::All` with the namespaces in `dir`, sorted by name.
+pub fn expand(netns: &[Netns], dir: &Path) -> Vec<Netns> {
+    let mut expanded = vec![];
+    for ns in netns.iter() {
+        if *ns != Netns::All {
+            expanded.push(ns.clone());
+            continue;
+        }
+        let mut names: Vec<String> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .collect(),
+            // no namespace has been named yet
+            Err(_) => vec![],
+        };
+        names.sort();
+        expanded.extend(names.into_iter().map(Netns::Named));
+    }
+    expanded
+}
+
+/// A thread reading a namespace.
+struct Worker {
+    info: InterfaceInfo,
+    requests: Sender<()>,
+    stats: Receiver<InterfaceStats>,
+}
+
+#[cfg(target_os = "linux")]
+fn enter(path: &Path) -> Result<crate::reader::in_libc::LibcReader> {
+    use crate::reader::in_libc::LibcReader;
+    use nix::sched::{setns, CloneFlags};
+    use std::os::unix::io::AsRawFd;
+
+    let file = fs::File::open(path)?;
+    setns(file.as_raw_fd(), CloneFlags::CLONE_NEWNET)?;
+    LibcReader::new()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn enter(_path: &Path) -> Result<crate::reader::in_libc::LibcReader> {
+    Err(Error::Other(
+        "Network namespaces are only supported on Linux.",
+    ))
+}
+
+impl Worker {
+    fn spawn(ns: &Netns) -> Result<Worker> {
+        let path = ns.path();
+        let (info_tx, info_rx) = mpsc::channel();
+        let (requests, requests_rx) = mpsc::channel();
+        let (stats_tx, stats) = mpsc::channel();
+        thread::spawn(move || {
+            let reader = match enter(&path) {
+                Ok(reader) => reader,
+                Err(err) => {
+                    let _ = info_tx.send(Err(err));
+                    return;
+                }
+            };
+            let _ = info_tx.send(Ok(reader.get_info().clone()));
+            // ends when the `Worker` is dropped
+            for () in requests_rx {
+                if stats_tx.send(reader.read()).is_err() {
+                    break;
+                }
+            }
+        });
+        let info = match info_rx.recv() {
+            Ok(info) => info,
+            Err(_) => Err(Error::Other("The network namespace thread panicked.")),
+        };
+        let mut info = info.map_err(|err| {
+            Error::ParseError(format!(
+                "failed to enter network namespace `{}`: {}",
+                ns.path().display(),
+                err
+            ))
+        })?;
+        for item in info.0.iter_mut() {
+            item.name = format!("{}:{}", ns.label(), item.name);
+            // `/sys` shows the namespace it was mounted in, not this one
+            item.link = LinkSettings::default();
+        }
+        Ok(Worker {
+            info,
+            requests,
+            stats,
+        })
+    }
+
+    fn read(&self) -> InterfaceStats {
+        let empty = || InterfaceStats::empty(self.info.0.len());
+        if self.requests.send(()).is_err() {
+            return empty();
+        }
+        self.stats.recv().unwrap_or_else(|_| empty())
+    }
+}
+
+/// Reads the interfaces of another Reader, usually of the namespace mone runs in, followed by
+/// the interfaces of each namespace.
+pub struct NetnsReader {
+    reader: Box<dyn Read + Send>,
+    workers: Vec<Worker>,
+    info: InterfaceInfo,
+}
+
+impl NetnsReader {
+    pub fn new(reader: Box<dyn Read + Send>, netns: &[Netns]) -> Result<NetnsReader> {
+        let workers = expand(netns, Path::new(NETNS_DIR))
+            .iter()
+            .map(Worker::spawn)
+            .collect::<Result<Vec<_>>>()?;
+        let mut info = reader.get_info().clone();
+        for worker in workers.iter() {
+            info.0.extend(worker.info.0.iter().cloned());
+        }
+        Ok(NetnsReader {
+            reader,
+            workers,
+            info,
+        })
+    }
+}
+
+impl Read for NetnsReader {
+    fn get_info(&self) -> &InterfaceInfo {
+        &self.info
+    }
+
+    fn read(&self) -> InterfaceStats {
+        let mut stats = self.reader.read();
+        for worker in self.workers.iter() {
+            stats.0.extend(worker.read().0);
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::in_libc::LibcReader;
+    use crate::test_utils::temp_path;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_parse_netns() {
+        assert_eq!("all".parse::<Netns>().unwrap(), Netns::All);
+        assert_eq!(
+            "blue".parse::<Netns>().unwrap(),
+            Netns::Named("blue".into())
+        );
+        assert_eq!("4242".parse::<Netns>().unwrap(), Netns::Pid(4242));
+        let ns: Netns = "/run/docker/netns/1a2b".parse().unwrap();
+        assert_eq!(ns.label(), "1a2b");
+        assert_eq!(Netns::Pid(4242).path(), Path::new("/proc/4242/ns/net"));
+        assert_eq!(
+            Netns::Named("blue".into()).path(),
+            Path::new("/var/run/netns/blue")
+        );
+        assert!("".parse::<Netns>().is_err());
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = temp_path("in_netns");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("red"), "").unwrap();
+        fs::write(dir.join("blue"), "").unwrap();
+        let netns = expand(&[Netns::Pid(1), Netns::All], &dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            netns,
+            vec![
+                Netns::Pid(1),
+                Netns::Named("blue".into()),
+                Netns::Named("red".into())
+            ]
+        );
+    }
+
+    /// Reads a namespace made by `unshare`, which needs user namespaces to be enabled.
+    #[test]
+    fn test_netns_reader() {
+        let script = "ip link set lo up && echo ready && exec sleep 10";
+        let mut child = match Command::new("unshare")
+            .args(["-Urn", "sh", "-c", script])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => return,
+        };
+        let mut ready = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut ready)
+            .unwrap();
+        let pid = child.id();
+
+        let host = Box::new(LibcReader::new().unwrap());
+        let n_host = host.get_info().0.len();
+        let result = NetnsReader::new(host, &[Netns::Pid(pid)]);
+        child.kill().unwrap();
+        child.wait().unwrap();
+        if ready.trim() != "ready" {
+            // `unshare` or `ip` is not available in this sandbox
+            return;
+        }
+        let reader = result.unwrap();
+        let names: Vec<&str> = reader.get_info().0[n_host..]
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect();
+        assert_eq!(names, vec![format!("{}:lo", pid)]);
+        let stats = reader.read();
+        assert_eq!(stats.0.len(), n_host + 1);
+        assert!(stats.0[n_host].is_some());
+    }
+}
@@ -0,0 +1,196 @@
+//! Network usage of each process, as nethogs shows.
+//!
+//! The sockets of each process are found from the `socket:[<inode>]` links in
+//! `/proc/<pid>/fd`, and the bytes of its TCP sockets from the `tcp_info` of `NETLINK_SOCK_DIAG`.
+//! UDP sockets are counted, but the kernel has no per-socket byte counters for them.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::reader::procfs::{self, Protocol};
+use crate::Result;
+
+/// Bytes of a TCP socket since it was opened.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SocketBytes {
+    pub rx: u64,
+    pub tx: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessStat {
+    pub pid: u32,
+    pub name: String,
+    pub tcp_sockets: usize,
+    pub udp_sockets: usize,
+    /// Bytes received and sent since the previous read
+    pub rx: u64,
+    pub tx: u64,
+}
+
+pub struct ProcessReader {
+    proc_dir: PathBuf,
+    /// Bytes of each socket inode at the previous read, `None` before the first read
+    prev: Option<HashMap<u64, SocketBytes>>,
+}
+
+#[cfg(target_os = "linux")]
+fn tcp_socket_bytes() -> Result<HashMap<u64, SocketBytes>> {
+    Ok(crate::reader::sock_diag::tcp_sockets()?
+        .into_iter()
+        .filter_map(|msg| {
+            let info = msg.info?;
+            Some((
+                msg.inode,
+                SocketBytes {
+                    rx: info.bytes_received,
+                    tx: info.bytes_acked,
+                },
+            ))
+        })
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn tcp_socket_bytes() -> Result<HashMap<u64, SocketBytes>> {
+    Err(crate::Error::Other(
+        "Per-process stats are only supported on Linux.",
+    ))
+}
+
+impl ProcessReader {
+    pub fn new() -> ProcessReader {
+        ProcessReader::with_proc_dir(Path::new("/proc"))
+    }
+
+    pub fn with_proc_dir(proc_dir: &Path) -> ProcessReader {
+        ProcessReader {
+            proc_dir: proc_dir.to_path_buf(),
+            prev: None,
+        }
+    }
+
+    /// Returns the processes with sockets, the ones with the most traffic since the previous
+    /// read first. The traffic of the first read is 0.
+    pub fn read(&mut self) -> Result<Vec<ProcessStat>> {
+        let bytes = tcp_socket_bytes()?;
+        let stats = self.stats(&bytes)?;
+        self.prev = Some(bytes);
+        Ok(stats)
+    }
+
+    fn stats(&self, bytes: &HashMap<u64, SocketBytes>) -> Result<Vec<ProcessStat>> {
+        let protocols: HashMap<u64, Protocol> = procfs::read_net_sockets(&self.proc_dir)?
+            .into_iter()
+            .map(|entry| (entry.inode, entry.protocol))
+            .collect();
+        let processes = procfs::read_process_sockets(&self.proc_dir)?;
+        let owners = procfs::inode_owners(&processes);
+
+        let mut stats: Vec<ProcessStat> = processes
+            .iter()
+            .map(|process| {
+                let mut stat = ProcessStat {
+                    pid: process.pid,
+                    name: process.name.clone(),
+                    tcp_sockets: 0,
+                    udp_sockets: 0,
+                    rx: 0,
+                    tx: 0,
+                };
+                for inode in process.inodes.iter() {
+                    // sockets shared with another process are counted once
+                    if owners[inode] != process.pid {
+                        continue;
+                    }
+                    match protocols.get(inode) {
+                        Some(Protocol::Tcp) => stat.tcp_sockets += 1,
+                        Some(Protocol::Udp) => stat.udp_sockets += 1,
+                        // unix and other sockets
+                        None => continue,
+                    }
+                    if let (Some(prev), Some(cur)) = (&self.prev, bytes.get(inode)) {
+                        // sockets opened since the previous read count from 0
+                        let prev = prev.get(inode).cloned().unwrap_or_default();
+                        stat.rx += cur.rx.saturating_sub(prev.rx);
+                        stat.tx += cur.tx.saturating_sub(prev.tx);
+                    }
+                }
+                stat
+            })
+            .filter(|stat| stat.tcp_sockets + stat.udp_sockets > 0)
+            .collect();
+        stats.sort_by(|a, b| (b.rx + b.tx).cmp(&(a.rx + a.tx)).then(a.pid.cmp(&b.pid)));
+        Ok(stats)
+    }
+}
+
+impl Default for ProcessReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_path;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0277 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 100 1 0000000000000000 100 0 0 10 0
+   1: 0200A8C0:A0C2 2200A8C0:01BB 01 00000000:00000000 02:000A7F5C 00000000  1000        0 200 2 0000000000000000 20 4 30 10 -1
+   2: 0200A8C0:A0C4 2200A8C0:01BB 01 00000000:00000000 02:000A7F5C 00000000  1000        0 201 2 0000000000000000 20 4 30 10 -1
+";
+    const UDP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  1: 00000000:0044 00000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 300 2 0000000000000000 0
+";
+
+    fn bytes(sockets: &[(u64, u64, u64)]) -> HashMap<u64, SocketBytes> {
+        sockets
+            .iter()
+            .map(|&(inode, rx, tx)| (inode, SocketBytes { rx, tx }))
+            .collect()
+    }
+
+    #[test]
+    fn test_stats() {
+        let dir = temp_path("in_process");
+        fs::create_dir_all(dir.join("net")).unwrap();
+        fs::write(dir.join("net").join("tcp"), TCP).unwrap();
+        fs::write(dir.join("net").join("udp"), UDP).unwrap();
+        for &(pid, name, inodes) in &[
+            ("10", "sshd", &[100][..]),
+            ("20", "curl", &[200, 201, 999][..]),
+            ("30", "dhclient", &[300][..]),
+        ] {
+            let fd = dir.join(pid).join("fd");
+            fs::create_dir_all(&fd).unwrap();
+            fs::write(dir.join(pid).join("comm"), format!("{}\n", name)).unwrap();
+            for (i, inode) in inodes.iter().enumerate() {
+                symlink(format!("socket:[{}]", inode), fd.join(i.to_string())).unwrap();
+            }
+        }
+
+        let mut reader = ProcessReader::with_proc_dir(&dir);
+        let stats = reader.stats(&bytes(&[(200, 1000, 10)])).unwrap();
+        assert_eq!(stats.len(), 3);
+        assert!(stats.iter().all(|stat| stat.rx == 0 && stat.tx == 0));
+        reader.prev = Some(bytes(&[(200, 1000, 10)]));
+
+        let stats = reader
+            .stats(&bytes(&[(200, 5000, 20), (201, 300, 0), (100, 1, 1)]))
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let curl = &stats[0];
+        assert_eq!((curl.pid, curl.name.as_str()), (20, "curl"));
+        assert_eq!((curl.tcp_sockets, curl.udp_sockets), (2, 0));
+        assert_eq!((curl.rx, curl.tx), (4300, 10));
+        assert_eq!((stats[1].pid, stats[1].rx, stats[1].tx), (10, 1, 1));
+        assert_eq!(
+            (stats[2].name.as_str(), stats[2].udp_sockets),
+            ("dhclient", 1)
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Input the stats recorded by `mone record`.
+
+use std::path::Path;
+use std::time::Instant;
+
+use crate::reader::Read;
+use crate::record::Recording;
+use crate::utils::NumBytes;
+use crate::{InterfaceInfo, InterfaceStats, Result};
+
+/// Replays a recording in real time: `read` returns the last sample recorded at or before the
+/// time elapsed since the reader was created.
+pub struct ReplayReader {
+    info: InterfaceInfo,
+    recording: Recording,
+    started_at: Instant,
+    /// How many times faster than recorded
+    speed: f64,
+}
+
+impl ReplayReader {
+    pub fn open(path: &Path) -> Result<ReplayReader> {
+        Ok(ReplayReader::new(Recording::open(path)?))
+    }
+
+    pub fn new(recording: Recording) -> ReplayReader {
+        ReplayReader {
+            info: recording.header.info(),
+            recording,
+            started_at: Instant::now(),
+            speed: 1.0,
+        }
+    }
+
+    /// Replays `speed` times faster. The counters are divided by `speed`, so that the rates stay
+    /// those recorded, averaged over `speed` seconds.
+    pub fn with_speed(mut self, speed: f64) -> ReplayReader {
+        self.speed = speed;
+        self
+    }
+
+    /// Seconds the replay of the first to the last sample takes
+    pub fn duration(&self) -> f64 {
+        let samples = &self.recording.samples;
+        (samples[samples.len() - 1].time - samples[0].time) / self.speed
+    }
+
+    fn stats_at(&self, elapsed: f64) -> InterfaceStats {
+        let samples = &self.recording.samples;
+        let time = samples[0].time + elapsed * self.speed;
+        let i = samples
+            .iter()
+            .rposition(|sample| sample.time <= time)
+            .unwrap_or(0);
+        let mut stats = samples[i].stats();
+        if self.speed != 1.0 {
+            let scale = |value: u64| (value as f64 / self.speed) as u64;
+            for stat in stats.0.iter_mut().flatten() {
+                stat.rx = NumBytes::from(scale(stat.rx.value()));
+                stat.tx = NumBytes::from(scale(stat.tx.value()));
+                stat.drops = scale(stat.drops);
+            }
+        }
+        stats
+    }
+}
+
+impl Read for ReplayReader {
+    fn get_info(&self) -> &InterfaceInfo {
+        &self.info
+    }
+
+    fn read(&self) -> InterfaceStats {
+        self.stats_at(self.started_at.elapsed().as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDING: &str = r#"{"version":1,"started_at":0,"interfaces":["eth0"]}
+{"time":0.0,"stats":[{"rx":0,"tx":0,"drops":0}]}
+{"time":1.0,"stats":[{"rx":100,"tx":0,"drops":0}]}
+{"time":2.5,"stats":[{"rx":300,"tx":0,"drops":0}]}
+"#;
+
+    #[test]
+    fn test_stats_at() {
+        let reader = ReplayReader::new(Recording::from_reader(RECORDING.as_bytes()).unwrap());
+        let rx = |elapsed: f64| reader.stats_at(elapsed).0[0].as_ref().unwrap().rx.value();
+        assert_eq!(reader.duration(), 2.5);
+        assert_eq!(rx(0.0), 0);
+        assert_eq!(rx(1.0), 100);
+        assert_eq!(rx(2.0), 100);
+        assert_eq!(rx(3.0), 300);
+        assert_eq!(reader.read().0.len(), 1);
+
+        let reader = reader.with_speed(2.0);
+        let rx = |elapsed: f64| reader.stats_at(elapsed).0[0].as_ref().unwrap().rx.value();
+        assert_eq!(reader.duration(), 1.25);
+        assert_eq!(rx(0.5), 50);
+        assert_eq!(rx(1.0), 50);
+        assert_eq!(rx(1.25), 150);
+    }
+}
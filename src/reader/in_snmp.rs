@@ -0,0 +1,222 @@
+//! Protocol counters of the kernel from `/proc/net/snmp` and `/proc/net/netstat`, as nstat
+//! shows.
+//!
+//! Both files consist of pairs of lines, a header with the counter names and a line with their
+//! values, e.g. `Tcp: ... RetransSegs ...` and `Tcp: ... 3 ...`. Counters are named as nstat
+//! does, the prefix followed by the name, e.g. `TcpRetransSegs`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result};
+
+/// Counters read by `ProtocolReader`, grouped by protocol.
+pub const COUNTERS: [(&str, &[&str]); 5] = [
+    (
+        "Ip",
+        &["InReceives", "OutRequests", "InDiscards", "OutNoRoutes"],
+    ),
+    ("Icmp", &["InMsgs", "OutMsgs", "InErrors", "InDestUnreachs"]),
+    (
+        "Tcp",
+        &[
+            "InSegs",
+            "OutSegs",
+            "RetransSegs",
+            "EstabResets",
+            "OutRsts",
+            "InErrs",
+        ],
+    ),
+    ("TcpExt", &["TCPTimeouts", "TCPSynRetrans", "ListenDrops"]),
+    (
+        "Udp",
+        &[
+            "InDatagrams",
+            "OutDatagrams",
+            "NoPorts",
+            "InErrors",
+            "RcvbufErrors",
+            "SndbufErrors",
+        ],
+    ),
+];
+
+/// Parses the content of `/proc/net/snmp` or `/proc/net/netstat` into nstat style names and
+/// values.
+pub fn parse_snmp(text: &str) -> Result<HashMap<String, i64>> {
+    let invalid = |line: &str| Error::ParseError(format!("invalid protocol counters `{}`", line));
+    let mut counters = HashMap::new();
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    while let Some(header) = lines.next() {
+        let values = lines.next().ok_or_else(|| invalid(header))?;
+        let mut names = header.split_whitespace();
+        let mut values = values.split_whitespace();
+        let prefix = names.next().ok_or_else(|| invalid(header))?;
+        if values.next() != Some(prefix) {
+            return Err(invalid(header));
+        }
+        let prefix = prefix.trim_end_matches(':');
+        for name in names {
+            // `Tcp: ... MaxConn` is -1 as it has no limit
+            let value = values
+                .next()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| invalid(header))?;
+            counters.insert(format!("{}{}", prefix, name), value);
+        }
+    }
+    Ok(counters)
+}
+
+/// A counter of `COUNTERS`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolStat {
+    /// `Ip`, `Icmp`, `Tcp`, `TcpExt` or `Udp`
+    pub protocol: &'static str,
+    /// e.g. `RetransSegs`
+    pub name: &'static str,
+    /// Value since boot
+    pub total: i64,
+    /// Difference since the previous read
+    pub rate: u64,
+}
+
+pub struct ProtocolReader {
+    proc_dir: PathBuf,
+    /// Counters at the previous read, `None` before the first read
+    prev: Option<HashMap<String, i64>>,
+}
+
+impl ProtocolReader {
+    pub fn new() -> ProtocolReader {
+        ProtocolReader::with_proc_dir(Path::new("/proc"))
+    }
+
+    pub fn with_proc_dir(proc_dir: &Path) -> ProtocolReader {
+        ProtocolReader {
+            proc_dir: proc_dir.to_path_buf(),
+            prev: None,
+        }
+    }
+
+    /// Returns the counters of `COUNTERS` in its order. Counters the kernel does not have are
+    /// skipped, and the rates of the first read are 0.
+    pub fn read(&mut self) -> Result<Vec<ProtocolStat>> {
+        let mut counters = parse_snmp(&fs::read_to_string(self.proc_dir.join("net/snmp"))?)?;
+        // `/proc/net/netstat` only has the extended counters such as `TcpExtTCPTimeouts`
+        if let Ok(text) = fs::read_to_string(self.proc_dir.join("net/netstat")) {
+            counters.extend(parse_snmp(&text)?);
+        }
+        let stats = self.stats(&counters);
+        self.prev = Some(counters);
+        Ok(stats)
+    }
+
+    fn stats(&self, counters: &HashMap<String, i64>) -> Vec<ProtocolStat> {
+        let mut stats = vec![];
+        for &(protocol, names) in COUNTERS.iter() {
+            for &name in names {
+                let key = format!("{}{}", protocol, name);
+                let total = match counters.get(&key) {
+                    Some(&total) => total,
+                    None => continue,
+                };
+                let prev = self.prev.as_ref().and_then(|prev| prev.get(&key));
+                // 32 bit counters of old kernels may wrap
+                let rate = prev.map_or(0, |&prev| (total - prev).max(0) as u64);
+                stats.push(ProtocolStat {
+                    protocol,
+                    name,
+                    total,
+                    rate,
+                });
+            }
+        }
+        stats
+    }
+}
+
+impl Default for ProtocolReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_path;
+
+    // trimmed `/proc/net/snmp` and `/proc/net/netstat` of Linux 6.18
+    const SNMP: &str = "Ip: Forwarding DefaultTTL InReceives InHdrErrors InAddrErrors ForwDatagrams InUnknownProtos InDiscards InDelivers OutRequests OutDiscards OutNoRoutes
+Ip: 2 64 9832 0 0 0 0 0 9832 9588 0 0
+Icmp: InMsgs InErrors InCsumErrors InDestUnreachs OutMsgs
+Icmp: 5 0 0 5 3
+Tcp: RtoAlgorithm RtoMin RtoMax MaxConn ActiveOpens PassiveOpens AttemptFails EstabResets CurrEstab InSegs OutSegs RetransSegs InErrs OutRsts InCsumErrors
+Tcp: 1 200 120000 -1 64 46 0 23 2 9792 9641 3 0 11 0
+Udp: InDatagrams NoPorts InErrors OutDatagrams RcvbufErrors SndbufErrors InCsumErrors IgnoredMulti MemErrors
+Udp: 40 0 0 40 0 0 0 0 0
+";
+    const NETSTAT: &str = "TcpExt: SyncookiesSent TCPTimeouts
+TcpExt: 0 7
+IpExt: InNoRoutes InOctets OutOctets
+IpExt: 0 106556638 70106240
+";
+
+    #[test]
+    fn test_parse_snmp() {
+        let counters = parse_snmp(SNMP).unwrap();
+        assert_eq!(counters["IpInReceives"], 9832);
+        assert_eq!(counters["TcpMaxConn"], -1);
+        assert_eq!(counters["TcpRetransSegs"], 3);
+        assert_eq!(counters["UdpMemErrors"], 0);
+        let counters = parse_snmp(NETSTAT).unwrap();
+        assert_eq!(counters["TcpExtTCPTimeouts"], 7);
+        assert_eq!(counters["IpExtInOctets"], 106556638);
+
+        assert!(parse_snmp("Ip: Forwarding DefaultTTL\n").is_err());
+        assert!(parse_snmp("Ip: Forwarding DefaultTTL\nIp: 2\n").is_err());
+        assert!(parse_snmp("Ip: Forwarding\nTcp: 2\n").is_err());
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = temp_path("in_snmp");
+        fs::create_dir_all(dir.join("net")).unwrap();
+        fs::write(dir.join("net/snmp"), SNMP).unwrap();
+        fs::write(dir.join("net/netstat"), NETSTAT).unwrap();
+        let mut reader = ProtocolReader::with_proc_dir(&dir);
+        let stats = reader.read().unwrap();
+        assert!(stats.iter().all(|stat| stat.rate == 0));
+        let names: Vec<&str> = stats.iter().take(6).map(|stat| stat.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "InReceives",
+                "OutRequests",
+                "InDiscards",
+                "OutNoRoutes",
+                "InMsgs",
+                "OutMsgs"
+            ]
+        );
+
+        fs::write(
+            dir.join("net/snmp"),
+            SNMP.replace("9792 9641 3 0 11", "10792 10641 53 0 11"),
+        )
+        .unwrap();
+        let stats = reader.read().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let stat = |name: &str| stats.iter().find(|s| s.protocol == "Tcp" && s.name == name);
+        assert_eq!(stat("InSegs").unwrap().rate, 1000);
+        assert_eq!(stat("RetransSegs").unwrap().total, 53);
+        assert_eq!(stat("RetransSegs").unwrap().rate, 50);
+        assert_eq!(stat("OutRsts").unwrap().rate, 0);
+        assert!(stats
+            .iter()
+            .any(|s| s.name == "TCPTimeouts" && s.total == 7));
+    }
+}
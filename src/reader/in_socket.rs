@@ -0,0 +1,146 @@
+//! TCP connections with their state, round trip time, congestion window, retransmissions and
+//! throughput, as `ss -ti` shows.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use crate::reader::in_process::SocketBytes;
+use crate::Result;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SocketStat {
+    /// `ESTAB`, `LISTEN`, ...
+    pub state: &'static str,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    /// Smoothed round trip time in milliseconds
+    pub rtt: f64,
+    /// Congestion window in segments
+    pub cwnd: u32,
+    /// Segments retransmitted since the socket was opened
+    pub retrans: u32,
+    /// Bytes since the socket was opened
+    pub total: SocketBytes,
+    /// Bytes since the previous read
+    pub rx: u64,
+    pub tx: u64,
+}
+
+#[cfg(target_os = "linux")]
+fn tcp_sockets() -> Result<Vec<SocketStat>> {
+    use crate::reader::sock_diag;
+
+    Ok(sock_diag::tcp_sockets()?
+        .into_iter()
+        .map(|msg| {
+            let info = msg.info.unwrap_or_default();
+            SocketStat {
+                state: sock_diag::state_name(msg.state),
+                local: msg.local,
+                remote: msg.remote,
+                rtt: f64::from(info.rtt) / 1000.0,
+                cwnd: info.snd_cwnd,
+                retrans: info.total_retrans,
+                total: SocketBytes {
+                    rx: info.bytes_received,
+                    tx: info.bytes_acked,
+                },
+                rx: 0,
+                tx: 0,
+            }
+        })
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn tcp_sockets() -> Result<Vec<SocketStat>> {
+    Err(crate::Error::Other(
+        "TCP connection stats are only supported on Linux.",
+    ))
+}
+
+type Flow = (SocketAddr, SocketAddr);
+
+#[derive(Default)]
+pub struct SocketReader {
+    /// Bytes of each connection at the previous read, `None` before the first read
+    prev: Option<HashMap<Flow, SocketBytes>>,
+}
+
+impl SocketReader {
+    pub fn new() -> SocketReader {
+        SocketReader::default()
+    }
+
+    /// Returns the TCP sockets, the ones with the highest throughput since the previous read
+    /// first. The throughput of the first read is 0.
+    pub fn read(&mut self) -> Result<Vec<SocketStat>> {
+        let sockets = tcp_sockets()?;
+        Ok(self.update(sockets))
+    }
+
+    fn update(&mut self, mut sockets: Vec<SocketStat>) -> Vec<SocketStat> {
+        if let Some(prev) = &self.prev {
+            for socket in sockets.iter_mut() {
+                // connections opened since the previous read count from 0
+                let prev = prev
+                    .get(&(socket.local, socket.remote))
+                    .cloned()
+                    .unwrap_or_default();
+                socket.rx = socket.total.rx.saturating_sub(prev.rx);
+                socket.tx = socket.total.tx.saturating_sub(prev.tx);
+            }
+        }
+        self.prev = Some(
+            sockets
+                .iter()
+                .map(|socket| ((socket.local, socket.remote), socket.total))
+                .collect(),
+        );
+        sockets.sort_by(|a, b| {
+            (b.rx + b.tx)
+                .cmp(&(a.rx + a.tx))
+                .then((b.total.rx + b.total.tx).cmp(&(a.total.rx + a.total.tx)))
+        });
+        sockets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socket(remote: &str, rx: u64, tx: u64) -> SocketStat {
+        SocketStat {
+            state: "ESTAB",
+            local: "192.168.0.2:41154".parse().unwrap(),
+            remote: remote.parse().unwrap(),
+            rtt: 12.5,
+            cwnd: 10,
+            retrans: 0,
+            total: SocketBytes { rx, tx },
+            rx: 0,
+            tx: 0,
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let mut reader = SocketReader::new();
+        let sockets = reader.update(vec![
+            socket("192.168.0.34:443", 100, 10),
+            socket("192.168.0.35:443", 5000, 0),
+        ]);
+        assert_eq!(sockets[0].total.rx, 5000);
+        assert!(sockets.iter().all(|s| s.rx == 0 && s.tx == 0));
+
+        let sockets = reader.update(vec![
+            socket("192.168.0.34:443", 4100, 20),
+            socket("192.168.0.35:443", 5000, 0),
+            socket("192.168.0.36:443", 10, 0),
+        ]);
+        let rates: Vec<(u64, u64)> = sockets.iter().map(|s| (s.rx, s.tx)).collect();
+        assert_eq!(rates, vec![(4000, 10), (10, 0), (0, 0)]);
+        assert_eq!(sockets[0].remote, "192.168.0.34:443".parse().unwrap());
+    }
+}
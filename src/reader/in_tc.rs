@@ -0,0 +1,229 @@
+//! Stats of the qdiscs and classes of traffic control, with their rates.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::Result;
+
+/// Parent of root qdiscs, `TC_H_ROOT`
+const TC_H_ROOT: u32 = 0xffff_ffff;
+/// Parent of ingress and clsact qdiscs, `TC_H_INGRESS`
+const TC_H_INGRESS: u32 = 0xffff_fff1;
+
+/// A handle such as `1:10`, the major and minor numbers in hex.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Handle(pub u32);
+
+impl Handle {
+    pub const ROOT: Handle = Handle(TC_H_ROOT);
+}
+
+impl fmt::Display for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            TC_H_ROOT => write!(f, "root"),
+            TC_H_INGRESS => write!(f, "ingress"),
+            handle => write!(f, "{:x}:{:x}", handle >> 16, handle & 0xffff),
+        }
+    }
+}
+
+/// Counters of a qdisc or class.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TcCounters {
+    pub bytes: u64,
+    pub packets: u64,
+    pub drops: u64,
+    pub overlimits: u64,
+    pub requeues: u64,
+    /// Packets in the queue
+    pub qlen: u32,
+    /// Bytes in the queue
+    pub backlog: u32,
+}
+
+/// A qdisc or class of an `RTM_NEWQDISC` or `RTM_NEWTCLASS` message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TcMsg {
+    /// Whether it is a class rather than a qdisc
+    pub class: bool,
+    pub ifindex: u32,
+    pub handle: Handle,
+    pub parent: Handle,
+    /// e.g. `htb` or `fq_codel`
+    pub kind: String,
+    pub counters: TcCounters,
+}
+
+/// Dumps the qdiscs and classes of every interface with the names of the interfaces.
+#[cfg(target_os = "linux")]
+fn tc_messages() -> Result<Vec<(String, TcMsg)>> {
+    use crate::reader::tc;
+
+    let mut names = HashMap::new();
+    Ok(tc::qdiscs_and_classes()?
+        .into_iter()
+        .map(|msg| {
+            let interface = names
+                .entry(msg.ifindex)
+                .or_insert_with(|| tc::interface_name(msg.ifindex))
+                .clone();
+            (interface, msg)
+        })
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn tc_messages() -> Result<Vec<(String, TcMsg)>> {
+    Err(crate::Error::Other(
+        "Traffic control stats are only supported on Linux.",
+    ))
+}
+
+/// A qdisc or class with the changes of its counters since the previous read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TcStat {
+    pub interface: String,
+    pub msg: TcMsg,
+    /// Differences of `bytes`, `packets`, `drops` and `overlimits` since the previous read;
+    /// `qlen` and `backlog` are the current ones
+    pub rate: TcCounters,
+}
+
+type TcKey = (u32, bool, Handle, Handle);
+
+#[derive(Default)]
+pub struct TcReader {
+    /// Counters of each qdisc and class at the previous read, `None` before the first read
+    prev: Option<HashMap<TcKey, TcCounters>>,
+}
+
+impl TcReader {
+    pub fn new() -> TcReader {
+        TcReader::default()
+    }
+
+    /// Returns the qdiscs and classes ordered by interface and handle, each qdisc followed by
+    /// its classes. The rates of the first read are 0.
+    pub fn read(&mut self) -> Result<Vec<TcStat>> {
+        let msgs = tc_messages()?;
+        Ok(self.update(msgs))
+    }
+
+    fn update(&mut self, msgs: Vec<(String, TcMsg)>) -> Vec<TcStat> {
+        let key = |msg: &TcMsg| (msg.ifindex, msg.class, msg.handle, msg.parent);
+        let mut stats: Vec<TcStat> = msgs
+            .into_iter()
+            .map(|(interface, msg)| {
+                let c = msg.counters;
+                let rate = match self.prev.as_ref().and_then(|prev| prev.get(&key(&msg))) {
+                    // counters are reset when a qdisc is replaced
+                    Some(prev) => TcCounters {
+                        bytes: c.bytes.saturating_sub(prev.bytes),
+                        packets: c.packets.saturating_sub(prev.packets),
+                        drops: c.drops.saturating_sub(prev.drops),
+                        overlimits: c.overlimits.saturating_sub(prev.overlimits),
+                        requeues: c.requeues.saturating_sub(prev.requeues),
+                        qlen: c.qlen,
+                        backlog: c.backlog,
+                    },
+                    None => TcCounters {
+                        qlen: c.qlen,
+                        backlog: c.backlog,
+                        ..TcCounters::default()
+                    },
+                };
+                TcStat {
+                    interface,
+                    msg,
+                    rate,
+                }
+            })
+            .collect();
+        self.prev = Some(
+            stats
+                .iter()
+                .map(|stat| (key(&stat.msg), stat.msg.counters))
+                .collect(),
+        );
+        // the qdisc `1:` comes before its classes `1:1`, `1:10`, ...
+        let major = |stat: &TcStat| {
+            if stat.msg.class {
+                stat.msg.handle.0 & 0xffff_0000
+            } else {
+                stat.msg.handle.0
+            }
+        };
+        stats.sort_by(|a, b| {
+            (a.msg.ifindex, major(a), a.msg.class, a.msg.handle).cmp(&(
+                b.msg.ifindex,
+                major(b),
+                b.msg.class,
+                b.msg.handle,
+            ))
+        });
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_os = "linux")]
+    use crate::reader::tc;
+
+    /// Recorded replies, see the tests of `tc`
+    #[cfg(all(target_os = "linux", target_endian = "little"))]
+    const QDISCS: &[u8] = include_bytes!("fixtures/tc_qdisc.bin");
+    #[cfg(all(target_os = "linux", target_endian = "little"))]
+    const CLASSES: &[u8] = include_bytes!("fixtures/tc_class.bin");
+
+    #[test]
+    fn test_handle() {
+        assert_eq!(Handle(0x0001_0010).to_string(), "1:10");
+        assert_eq!(Handle(0x8001_0000).to_string(), "8001:0");
+        assert_eq!(Handle(TC_H_ROOT).to_string(), "root");
+        assert_eq!(Handle(TC_H_INGRESS).to_string(), "ingress");
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_endian = "little"))]
+    fn test_update() {
+        let msgs = || -> Vec<(String, TcMsg)> {
+            let (mut msgs, _) = tc::parse_messages(CLASSES).unwrap();
+            msgs.extend(tc::parse_messages(QDISCS).unwrap().0);
+            msgs.into_iter()
+                .filter(|msg| msg.ifindex == 3)
+                .map(|msg| ("v0".to_string(), msg))
+                .collect()
+        };
+        let mut reader = TcReader::new();
+        let stats = reader.update(msgs());
+        let order: Vec<(bool, String)> = stats
+            .iter()
+            .map(|s| (s.msg.class, s.msg.handle.to_string()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (false, "1:0".to_string()),
+                (true, "1:1".to_string()),
+                (true, "1:10".to_string()),
+                (false, "10:0".to_string()),
+            ]
+        );
+        assert_eq!(stats[0].rate.bytes, 0);
+        assert_eq!(stats[0].rate.backlog, 50016);
+
+        let mut next = msgs();
+        for (_, msg) in next.iter_mut() {
+            msg.counters.bytes += 12_500;
+            msg.counters.drops += 10;
+        }
+        let stats = reader.update(next);
+        assert!(stats
+            .iter()
+            .all(|s| s.rate.bytes == 12_500 && s.rate.drops == 10));
+        assert_eq!(stats[0].rate.packets, 0);
+    }
+}
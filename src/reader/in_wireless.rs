@@ -0,0 +1,201 @@
+//! Link quality of wireless interfaces from `/proc/net/wireless`, as iwconfig shows.
+//!
+//! The bitrate is not in the file and is read with the `SIOCGIWRATE` ioctl of the wireless
+//! extensions, which cfg80211 drivers still answer.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result};
+
+/// A line of `/proc/net/wireless`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WirelessStat {
+    pub name: String,
+    /// Link quality, out of 70 with cfg80211 drivers
+    pub quality: f64,
+    /// Signal level in dBm
+    pub level: f64,
+    /// Noise level in dBm, `None` if the driver does not report it
+    pub noise: Option<f64>,
+    /// Packets discarded after too many retries, since the interface came up
+    pub retries: u64,
+    /// Packets discarded for other reasons, such as a wrong nwid or failed decryption
+    pub discarded: u64,
+    /// Beacons missed from the access point
+    pub missed_beacons: u64,
+    /// Bitrate of the last transmission in bits per second
+    pub bitrate: Option<u64>,
+    /// Retries since the previous read
+    pub retry_rate: u64,
+}
+
+/// `-52.` -> `-52.0`. The `.` marks a value updated since the previous read.
+fn parse_level(s: &str) -> Option<f64> {
+    s.trim_end_matches('.').parse().ok()
+}
+
+/// Parses the content of `/proc/net/wireless`.
+pub fn parse_wireless(text: &str) -> Result<Vec<WirelessStat>> {
+    text.lines()
+        .skip(2)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let stat = || -> Option<WirelessStat> {
+                let (name, values) = line.split_once(':')?;
+                let fields: Vec<&str> = values.split_whitespace().collect();
+                let count = |i: usize| -> Option<u64> { fields.get(i)?.parse().ok() };
+                let discarded = [4, 5, 6, 8]
+                    .iter()
+                    .map(|&i| count(i))
+                    .sum::<Option<u64>>()?;
+                Some(WirelessStat {
+                    name: name.trim().to_string(),
+                    quality: parse_level(fields.get(1)?)?,
+                    level: parse_level(fields.get(2)?)?,
+                    // -256 is the 0 of the unsigned byte the kernel keeps the noise in
+                    noise: parse_level(fields.get(3)?).filter(|&noise| noise > -256.0),
+                    retries: count(7)?,
+                    discarded,
+                    missed_beacons: count(9)?,
+                    bitrate: None,
+                    retry_rate: 0,
+                })
+            };
+            stat().ok_or_else(|| Error::ParseError(format!("invalid wireless stats `{}`", line)))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn bitrate(name: &str) -> Option<u64> {
+    const SIOCGIWRATE: libc::c_ulong = 0x8B21;
+
+    /// `struct iwreq` with the `struct iw_param` of the rate
+    #[repr(C)]
+    #[derive(Default)]
+    struct IwReq {
+        name: [u8; libc::IFNAMSIZ],
+        value: i32,
+        fixed: u8,
+        disabled: u8,
+        flags: u16,
+        _padding: [u8; 8],
+    }
+
+    let mut req = IwReq::default();
+    if name.len() >= req.name.len() {
+        return None;
+    }
+    req.name[..name.len()].copy_from_slice(name.as_bytes());
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return None;
+    }
+    let ret = unsafe { libc::ioctl(fd, SIOCGIWRATE as _, &mut req) };
+    unsafe { libc::close(fd) };
+    if ret < 0 || req.disabled != 0 || req.value <= 0 {
+        None
+    } else {
+        Some(req.value as u64)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bitrate(_name: &str) -> Option<u64> {
+    None
+}
+
+pub struct WirelessReader {
+    proc_dir: PathBuf,
+    /// Retries of each interface at the previous read, `None` before the first read
+    prev: Option<HashMap<String, u64>>,
+}
+
+impl WirelessReader {
+    pub fn new() -> WirelessReader {
+        WirelessReader::with_proc_dir(Path::new("/proc"))
+    }
+
+    pub fn with_proc_dir(proc_dir: &Path) -> WirelessReader {
+        WirelessReader {
+            proc_dir: proc_dir.to_path_buf(),
+            prev: None,
+        }
+    }
+
+    /// Returns the wireless interfaces, none if the host has no wireless interface or the
+    /// kernel no wireless extensions. The retry rates of the first read are 0.
+    pub fn read(&mut self) -> Result<Vec<WirelessStat>> {
+        let mut stats = match fs::read_to_string(self.proc_dir.join("net/wireless")) {
+            Ok(text) => parse_wireless(&text)?,
+            Err(_) => vec![],
+        };
+        for stat in stats.iter_mut() {
+            stat.bitrate = bitrate(&stat.name);
+            if let Some(&prev) = self.prev.as_ref().and_then(|prev| prev.get(&stat.name)) {
+                stat.retry_rate = stat.retries.saturating_sub(prev);
+            }
+        }
+        self.prev = Some(
+            stats
+                .iter()
+                .map(|stat| (stat.name.clone(), stat.retries))
+                .collect(),
+        );
+        Ok(stats)
+    }
+}
+
+impl Default for WirelessReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_path;
+
+    const WIRELESS: &str = include_str!("fixtures/proc_net_wireless");
+
+    #[test]
+    fn test_parse_wireless() {
+        let stats = parse_wireless(WIRELESS).unwrap();
+        assert_eq!(stats.len(), 2);
+        let stat = &stats[0];
+        assert_eq!(stat.name, "wlp2s0");
+        assert_eq!((stat.quality, stat.level, stat.noise), (58.0, -52.0, None));
+        assert_eq!(
+            (stat.retries, stat.discarded, stat.missed_beacons),
+            (14, 3, 2)
+        );
+        let stat = &stats[1];
+        assert_eq!(stat.name, "wlan1");
+        assert_eq!((stat.level, stat.noise), (-79.0, Some(-95.0)));
+        assert_eq!(
+            (stat.retries, stat.discarded, stat.missed_beacons),
+            (271, 4, 17)
+        );
+
+        assert_eq!(parse_wireless(&WIRELESS[..160]).unwrap(), vec![]);
+        assert!(parse_wireless(&format!("{}wlan2: 0000   31.\n", WIRELESS)).is_err());
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = temp_path("in_wireless");
+        fs::create_dir_all(dir.join("net")).unwrap();
+        let mut reader = WirelessReader::with_proc_dir(&dir);
+        assert_eq!(reader.read().unwrap(), vec![]);
+
+        fs::write(dir.join("net/wireless"), WIRELESS).unwrap();
+        assert_eq!(reader.read().unwrap()[1].retry_rate, 0);
+        fs::write(dir.join("net/wireless"), WIRELESS.replace("271", "300")).unwrap();
+        let stats = reader.read().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((stats[0].retry_rate, stats[1].retry_rate), (0, 29));
+    }
+}
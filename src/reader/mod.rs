@@ -1,9 +1,30 @@
 use crate::{InterfaceInfo, InterfaceStats};
 
+pub mod filter;
+pub mod in_capture;
+pub mod in_container;
+pub mod in_ethtool;
 pub mod in_libc;
+pub mod in_netns;
+pub mod in_process;
+pub mod in_replay;
+pub mod in_snmp;
+pub mod in_socket;
+pub mod in_tc;
+pub mod in_wireless;
+pub mod packet;
+pub mod pcap;
+pub mod procfs;
+pub mod sysfs;
 
+#[cfg(target_os = "linux")]
+pub mod iflink;
 #[cfg(target_os = "linux")]
 mod link;
+#[cfg(target_os = "linux")]
+pub mod sock_diag;
+#[cfg(target_os = "linux")]
+pub mod tc;
 
 pub trait Read {
     fn get_info(&self) -> &InterfaceInfo;
@@ -0,0 +1,187 @@
+//! Classification of packets by their L3 and L4 protocols and ports.
+
+use std::fmt;
+
+pub const ETH_P_IP: u16 = 0x0800;
+pub const ETH_P_ARP: u16 = 0x0806;
+pub const ETH_P_IPV6: u16 = 0x86dd;
+pub const ETH_P_8021Q: u16 = 0x8100;
+pub const ETH_P_8021AD: u16 = 0x88a8;
+
+const IPPROTO_HOPOPTS: u8 = 0;
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_ROUTING: u8 = 43;
+const IPPROTO_FRAGMENT: u8 = 44;
+const IPPROTO_ICMPV6: u8 = 58;
+const IPPROTO_DSTOPTS: u8 = 60;
+
+/// Kind of traffic a packet belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Class {
+    /// TCP with the lower of its ports, usually the port of the server
+    Tcp(u16),
+    /// UDP with the lower of its ports
+    Udp(u16),
+    Icmp,
+    Icmpv6,
+    /// Another IP protocol, or TCP or UDP without ports as in fragments, by its number
+    Ip(u8),
+    Arp,
+    /// Another protocol, by its EtherType
+    Ether(u16),
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Class::Tcp(port) => write!(f, "TCP/{}", port),
+            Class::Udp(port) => write!(f, "UDP/{}", port),
+            Class::Icmp => write!(f, "ICMP"),
+            Class::Icmpv6 => write!(f, "ICMPv6"),
+            Class::Ip(protocol) => write!(f, "IP/{}", protocol),
+            Class::Arp => write!(f, "ARP"),
+            Class::Ether(ethertype) => write!(f, "0x{:04x}", ethertype),
+        }
+    }
+}
+
+/// Reads the big endian `u16` at the start of `buf`, which has at least 2 bytes.
+pub fn be_u16(buf: &[u8]) -> u16 {
+    u16::from_be_bytes([buf[0], buf[1]])
+}
+
+/// Classifies the L4 header `data` of IP protocol `protocol`.
+fn classify_l4(protocol: u8, data: &[u8]) -> Class {
+    let port = || {
+        if data.len() < 4 {
+            return None;
+        }
+        let (src, dst) = (be_u16(&data[0..2]), be_u16(&data[2..4]));
+        Some(src.min(dst))
+    };
+    match protocol {
+        IPPROTO_ICMP => Class::Icmp,
+        IPPROTO_ICMPV6 => Class::Icmpv6,
+        IPPROTO_TCP => port().map_or(Class::Ip(protocol), Class::Tcp),
+        IPPROTO_UDP => port().map_or(Class::Ip(protocol), Class::Udp),
+        _ => Class::Ip(protocol),
+    }
+}
+
+fn classify_ipv4(data: &[u8]) -> Option<Class> {
+    if data.len() < 20 {
+        return None;
+    }
+    let protocol = data[9];
+    let header_len = usize::from(data[0] & 0x0f) * 4;
+    let offset = be_u16(&data[6..8]) & 0x1fff;
+    if offset != 0 || header_len < 20 {
+        return Some(Class::Ip(protocol));
+    }
+    Some(classify_l4(
+        protocol,
+        data.get(header_len..).unwrap_or_default(),
+    ))
+}
+
+fn classify_ipv6(data: &[u8]) -> Option<Class> {
+    if data.len() < 40 {
+        return None;
+    }
+    let mut next = data[6];
+    let mut data = &data[40..];
+    // skips the extension headers
+    loop {
+        match next {
+            IPPROTO_HOPOPTS | IPPROTO_ROUTING | IPPROTO_DSTOPTS => {
+                if data.len() < 2 {
+                    return Some(Class::Ip(next));
+                }
+                let len = (usize::from(data[1]) + 1) * 8;
+                next = data[0];
+                data = data.get(len..).unwrap_or_default();
+            }
+            IPPROTO_FRAGMENT => {
+                if data.len() < 8 {
+                    return Some(Class::Ip(next));
+                }
+                next = data[0];
+                if be_u16(&data[2..4]) >> 3 != 0 {
+                    return Some(Class::Ip(next));
+                }
+                data = &data[8..];
+            }
+            _ => return Some(classify_l4(next, data)),
+        }
+    }
+}
+
+/// Classifies `data`, a packet of EtherType `ethertype` without its link layer header, as
+/// `AF_PACKET` sockets of type `SOCK_DGRAM` receive it.
+pub fn classify(ethertype: u16, data: &[u8]) -> Class {
+    let class = match ethertype {
+        ETH_P_IP => classify_ipv4(data),
+        ETH_P_IPV6 => classify_ipv6(data),
+        ETH_P_ARP => Some(Class::Arp),
+        // VLAN tags which were not stripped by the NIC
+        ETH_P_8021Q | ETH_P_8021AD if data.len() >= 4 => {
+            Some(classify(be_u16(&data[2..4]), &data[4..]))
+        }
+        _ => None,
+    };
+    class.unwrap_or(Class::Ether(ethertype))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IPv4 header of 20 bytes followed by `l4`.
+    fn ipv4(protocol: u8, flags_offset: u16, l4: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, protocol, 0, 0];
+        packet[6..8].copy_from_slice(&flags_offset.to_be_bytes());
+        packet.extend_from_slice(&[127, 0, 0, 1, 127, 0, 0, 1]);
+        packet.extend_from_slice(l4);
+        packet
+    }
+
+    #[test]
+    fn test_classify_ipv4() {
+        // 54321 -> 443
+        let ports = [0xd4, 0x31, 0x01, 0xbb, 0, 0, 0, 0];
+        assert_eq!(classify(ETH_P_IP, &ipv4(6, 0, &ports)), Class::Tcp(443));
+        assert_eq!(
+            classify(ETH_P_IP, &ipv4(17, 0x4000, &ports)),
+            Class::Udp(443)
+        );
+        assert_eq!(classify(ETH_P_IP, &ipv4(1, 0, &[8, 0])), Class::Icmp);
+        assert_eq!(classify(ETH_P_IP, &ipv4(47, 0, &[])), Class::Ip(47));
+        // a fragment other than the first has no ports
+        assert_eq!(classify(ETH_P_IP, &ipv4(17, 0x00b9, &ports)), Class::Ip(17));
+        assert_eq!(classify(ETH_P_IP, &ipv4(6, 0, &ports[..2])), Class::Ip(6));
+        assert_eq!(classify(ETH_P_IP, &[0x45, 0]), Class::Ether(ETH_P_IP));
+        let mut tagged = vec![0, 10, 0x08, 0x00];
+        tagged.extend(ipv4(17, 0, &[0, 53, 0xc3, 0x50]));
+        assert_eq!(classify(ETH_P_8021Q, &tagged), Class::Udp(53));
+    }
+
+    #[test]
+    fn test_classify_ipv6() {
+        // hop-by-hop options, then UDP 53 -> 50000
+        let mut packet = vec![0x60, 0, 0, 0, 0, 0, 0, 64];
+        packet.extend_from_slice(&[0; 32]);
+        packet.extend_from_slice(&[17, 0, 0, 0, 0, 0, 0, 0]);
+        packet.extend_from_slice(&[0, 53, 0xc3, 0x50, 0, 0, 0, 0]);
+        assert_eq!(classify(ETH_P_IPV6, &packet), Class::Udp(53));
+        packet[6] = 58;
+        assert_eq!(classify(ETH_P_IPV6, &packet[..40]), Class::Icmpv6);
+        // the second fragment of a TCP segment
+        packet[6] = 44;
+        packet[40..44].copy_from_slice(&[6, 0, 0x05, 0x00]);
+        assert_eq!(classify(ETH_P_IPV6, &packet), Class::Ip(6));
+        assert_eq!(classify(ETH_P_ARP, &[]), Class::Arp);
+        assert_eq!(classify(0x88cc, &[]).to_string(), "0x88cc");
+    }
+}
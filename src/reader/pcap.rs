@@ -0,0 +1,639 @@
+//! Packets of pcap and pcapng files, as tcpdump and Wireshark write them, summed up into a
+//! `Recording` for `mone replay` and `mone report`.
+//!
+//! The packets are read as a stream and summed up into a sample per second, so that the memory
+//! taken grows with the duration of a capture rather than its size. Packets count with the
+//! lengths they had on the wire, including their link layer headers, and as received unless the
+//! file marks them as sent, as pcapng files and Linux cooked captures do.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read};
+
+use crate::reader::packet::{be_u16, classify, Class, ETH_P_IP, ETH_P_IPV6};
+use crate::record::{Header, RecordedStat, Recording, Sample};
+use crate::{Breakdown, Error, Result};
+
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAPNG_SHB: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER: u32 = 0x1a2b_3c4d;
+const PCAPNG_IDB: u32 = 1;
+const PCAPNG_SPB: u32 = 3;
+const PCAPNG_EPB: u32 = 6;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+/// Packet type of Linux cooked captures of sent packets, `PACKET_OUTGOING`
+const PACKET_OUTGOING: u8 = 4;
+/// Larger blocks and packets are taken for a corrupt file.
+const MAX_LEN: usize = 64 << 20;
+/// Series of the protocols other than those with the most traffic
+const MAX_CLASSES: usize = 8;
+
+fn be_u32(buf: &[u8]) -> u32 {
+    u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+fn le_u32(buf: &[u8]) -> u32 {
+    u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+fn le_u16(buf: &[u8]) -> u16 {
+    u16::from_le_bytes([buf[0], buf[1]])
+}
+
+/// Whether a file starting with `magic` is a pcap or pcapng file.
+pub fn is_capture(magic: &[u8]) -> bool {
+    if magic.len() < 4 {
+        return false;
+    }
+    [be_u32(magic), le_u32(magic)]
+        .iter()
+        .any(|&m| m == PCAP_MAGIC || m == PCAP_MAGIC_NANOS || m == PCAPNG_SHB)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Packet {
+    /// Unix time in seconds
+    pub time: f64,
+    /// Index of the interface in `Packets::interfaces`
+    pub interface: usize,
+    /// Length on the wire
+    pub len: u64,
+    pub outgoing: bool,
+    /// `None` if the link type is not supported
+    pub class: Option<Class>,
+}
+
+fn classify_ip(data: &[u8]) -> Option<Class> {
+    match data.first()? >> 4 {
+        4 => Some(classify(ETH_P_IP, data)),
+        6 => Some(classify(ETH_P_IPV6, data)),
+        _ => None,
+    }
+}
+
+/// Classifies `frame` of link type `linktype`, and tells whether it was sent if the link layer
+/// header says so.
+fn decode(linktype: u32, frame: &[u8]) -> (Option<Class>, bool) {
+    match linktype {
+        LINKTYPE_ETHERNET if frame.len() >= 14 => {
+            (Some(classify(be_u16(&frame[12..14]), &frame[14..])), false)
+        }
+        LINKTYPE_LINUX_SLL if frame.len() >= 16 => (
+            Some(classify(be_u16(&frame[14..16]), &frame[16..])),
+            be_u16(&frame[0..2]) == u16::from(PACKET_OUTGOING),
+        ),
+        LINKTYPE_LINUX_SLL2 if frame.len() >= 20 => (
+            Some(classify(be_u16(&frame[0..2]), &frame[20..])),
+            frame[10] == PACKET_OUTGOING,
+        ),
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => (classify_ip(frame), false),
+        // the address family in the byte order of the host which captured the packets
+        LINKTYPE_NULL | LINKTYPE_LOOP if frame.len() >= 4 => (classify_ip(&frame[4..]), false),
+        _ => (None, false),
+    }
+}
+
+/// Reads `buf.len()` bytes, or returns `false` at the end of the file.
+fn read_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::ParseError(format!("invalid capture: {}", message))
+}
+
+#[derive(Clone, Debug)]
+struct Interface {
+    name: String,
+    linktype: u32,
+    /// Seconds per unit of the timestamps
+    resolution: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Pcap,
+    Pcapng,
+}
+
+/// Iterator over the packets of a pcap or pcapng file.
+pub struct Packets<R: Read> {
+    reader: R,
+    format: Format,
+    big_endian: bool,
+    interfaces: Vec<Interface>,
+    /// Index of the first interface of the current pcapng section
+    section: usize,
+    /// Time of the previous packet, for those without a timestamp
+    time: f64,
+}
+
+impl<R: Read> Packets<R> {
+    /// Reads the header of the file. `name` names the interface of pcap files, which do not
+    /// name it.
+    pub fn new(mut reader: R, name: &str) -> Result<Packets<R>> {
+        let mut magic = [0; 4];
+        if !read_or_eof(&mut reader, &mut magic)? {
+            return Err(invalid("empty file"));
+        }
+        let mut packets = Packets {
+            reader,
+            format: Format::Pcap,
+            big_endian: false,
+            interfaces: vec![],
+            section: 0,
+            time: 0.0,
+        };
+        if be_u32(&magic) == PCAPNG_SHB {
+            packets.format = Format::Pcapng;
+            packets.read_section_header()?;
+            return Ok(packets);
+        }
+        let nanos = match (be_u32(&magic), le_u32(&magic)) {
+            (PCAP_MAGIC, _) | (PCAP_MAGIC_NANOS, _) => {
+                packets.big_endian = true;
+                be_u32(&magic) == PCAP_MAGIC_NANOS
+            }
+            (_, PCAP_MAGIC) => false,
+            (_, PCAP_MAGIC_NANOS) => true,
+            _ => return Err(invalid("not a pcap or pcapng file")),
+        };
+        let mut header = [0; 20];
+        if !read_or_eof(&mut packets.reader, &mut header)? {
+            return Err(invalid("truncated header"));
+        }
+        packets.interfaces.push(Interface {
+            name: name.to_string(),
+            // the upper bits may tell about the frame check sequences
+            linktype: packets.u32(&header[16..20]) & 0xffff,
+            resolution: if nanos { 1e-9 } else { 1e-6 },
+        });
+        Ok(packets)
+    }
+
+    /// Names of the interfaces read so far.
+    pub fn interfaces(&self) -> Vec<String> {
+        self.interfaces.iter().map(|i| i.name.clone()).collect()
+    }
+
+    fn u16(&self, buf: &[u8]) -> u16 {
+        if self.big_endian {
+            be_u16(buf)
+        } else {
+            le_u16(buf)
+        }
+    }
+
+    fn u32(&self, buf: &[u8]) -> u32 {
+        if self.big_endian {
+            be_u32(buf)
+        } else {
+            le_u32(buf)
+        }
+    }
+
+    fn read_vec(&mut self, len: usize) -> Result<Vec<u8>> {
+        if len > MAX_LEN {
+            return Err(invalid("too long a packet or block"));
+        }
+        let mut buf = vec![0; len];
+        if !read_or_eof(&mut self.reader, &mut buf)? {
+            return Err(invalid("truncated packet or block"));
+        }
+        Ok(buf)
+    }
+
+    fn next_pcap(&mut self) -> Result<Option<Packet>> {
+        let mut header = [0; 16];
+        if !read_or_eof(&mut self.reader, &mut header)? {
+            return Ok(None);
+        }
+        let interface = &self.interfaces[0];
+        let (linktype, resolution) = (interface.linktype, interface.resolution);
+        let frame = self.read_vec(self.u32(&header[8..12]) as usize)?;
+        let (class, outgoing) = decode(linktype, &frame);
+        Ok(Some(Packet {
+            time: f64::from(self.u32(&header[0..4]))
+                + f64::from(self.u32(&header[4..8])) * resolution,
+            interface: 0,
+            len: u64::from(self.u32(&header[12..16])),
+            outgoing,
+            class,
+        }))
+    }
+
+    /// Reads the rest of a section header block, whose type was read, and starts a section.
+    fn read_section_header(&mut self) -> Result<()> {
+        let mut header = [0; 8];
+        if !read_or_eof(&mut self.reader, &mut header)? {
+            return Err(invalid("truncated section header"));
+        }
+        self.big_endian = match be_u32(&header[4..8]) {
+            PCAPNG_BYTE_ORDER => true,
+            m if m.swap_bytes() == PCAPNG_BYTE_ORDER => false,
+            _ => return Err(invalid("invalid byte order magic")),
+        };
+        let len = self.u32(&header[0..4]) as usize;
+        if len < 16 {
+            return Err(invalid("too short a section header"));
+        }
+        self.read_vec(len - 12)?;
+        self.section = self.interfaces.len();
+        Ok(())
+    }
+
+    /// Options of a pcapng block, as codes and values.
+    fn options<'a>(&self, mut data: &'a [u8]) -> Vec<(u16, &'a [u8])> {
+        let mut options = vec![];
+        while data.len() >= 4 {
+            let (code, len) = (self.u16(&data[0..2]), usize::from(self.u16(&data[2..4])));
+            let padded = 4 + len.div_ceil(4) * 4;
+            if code == 0 || data.len() < 4 + len {
+                break;
+            }
+            options.push((code, &data[4..4 + len]));
+            data = data.get(padded..).unwrap_or_default();
+        }
+        options
+    }
+
+    fn read_interface(&mut self, body: &[u8]) -> Result<()> {
+        if body.len() < 8 {
+            return Err(invalid("too short an interface description"));
+        }
+        let mut interface = Interface {
+            name: format!("if{}", self.interfaces.len()),
+            linktype: u32::from(self.u16(&body[0..2])),
+            resolution: 1e-6,
+        };
+        for (code, value) in self.options(&body[8..]) {
+            match code {
+                // if_name
+                2 => {
+                    let name = value.split(|&b| b == 0).next().unwrap_or_default();
+                    interface.name = String::from_utf8_lossy(name).to_string();
+                }
+                // if_tsresol, a power of 10, or of 2 if the high bit is set
+                9 if !value.is_empty() => {
+                    let exp = -i32::from(value[0] & 0x7f);
+                    interface.resolution = if value[0] & 0x80 == 0 {
+                        10_f64.powi(exp)
+                    } else {
+                        2_f64.powi(exp)
+                    };
+                }
+                _ => {}
+            }
+        }
+        self.interfaces.push(interface);
+        Ok(())
+    }
+
+    fn interface(&self, id: usize) -> Result<&Interface> {
+        self.interfaces
+            .get(self.section + id)
+            .ok_or_else(|| invalid("packet of an undescribed interface"))
+    }
+
+    fn next_pcapng(&mut self) -> Result<Option<Packet>> {
+        loop {
+            let mut header = [0; 4];
+            if !read_or_eof(&mut self.reader, &mut header)? {
+                return Ok(None);
+            }
+            if be_u32(&header) == PCAPNG_SHB {
+                self.read_section_header()?;
+                continue;
+            }
+            let block_type = self.u32(&header);
+            if !read_or_eof(&mut self.reader, &mut header)? {
+                return Err(invalid("truncated block"));
+            }
+            let len = self.u32(&header) as usize;
+            if len < 12 || len % 4 != 0 {
+                return Err(invalid("invalid block length"));
+            }
+            let block = self.read_vec(len - 8)?;
+            let body = &block[..len - 12];
+            match block_type {
+                PCAPNG_IDB => self.read_interface(body)?,
+                PCAPNG_EPB if body.len() >= 20 => {
+                    let interface = self.interface(self.u32(&body[0..4]) as usize)?;
+                    let ticks = (u64::from(self.u32(&body[4..8])) << 32)
+                        | u64::from(self.u32(&body[8..12]));
+                    let time = ticks as f64 * interface.resolution;
+                    let captured = (self.u32(&body[12..16]) as usize).min(body.len() - 20);
+                    let frame = &body[20..20 + captured];
+                    let (class, mut outgoing) = decode(interface.linktype, frame);
+                    let options = body.get(20 + captured.div_ceil(4) * 4..);
+                    for (code, value) in self.options(options.unwrap_or_default()) {
+                        // epb_flags, whose lowest bits are 1 if inbound and 2 if outbound
+                        if code == 2 && value.len() == 4 && self.u32(value) & 3 != 0 {
+                            outgoing = self.u32(value) & 3 == 2;
+                        }
+                    }
+                    self.time = time;
+                    return Ok(Some(Packet {
+                        time,
+                        interface: self.section + self.u32(&body[0..4]) as usize,
+                        len: u64::from(self.u32(&body[16..20])),
+                        outgoing,
+                        class,
+                    }));
+                }
+                PCAPNG_SPB if body.len() >= 4 => {
+                    let interface = self.interface(0)?;
+                    let frame = &body[4..];
+                    let (class, outgoing) = decode(interface.linktype, frame);
+                    return Ok(Some(Packet {
+                        time: self.time,
+                        interface: self.section,
+                        len: u64::from(self.u32(&body[0..4])),
+                        outgoing,
+                        class,
+                    }));
+                }
+                // statistics, name resolution and custom blocks
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for Packets<R> {
+    type Item = Result<Packet>;
+
+    fn next(&mut self) -> Option<Result<Packet>> {
+        let packet = match self.format {
+            Format::Pcap => self.next_pcap(),
+            Format::Pcapng => self.next_pcapng(),
+        };
+        packet.transpose()
+    }
+}
+
+/// Sums up the packets of a pcap or pcapng file into samples of every second of the capture,
+/// with a series for each interface or protocol. `name` names the interface of pcap files.
+pub fn recording<R: Read>(reader: R, name: &str, by: Breakdown) -> Result<Recording> {
+    let mut packets = Packets::new(reader, name)?;
+    let mut first = None;
+    // bytes received and sent in each second of each series
+    let mut seconds: BTreeMap<u64, HashMap<String, RecordedStat>> = BTreeMap::new();
+    let mut totals: HashMap<String, u64> = HashMap::new();
+    while let Some(packet) = packets.next() {
+        let packet = packet?;
+        let first = *first.get_or_insert(packet.time);
+        let key = match by {
+            Breakdown::interface => packets.interfaces[packet.interface].name.clone(),
+            Breakdown::protocol => match packet.class {
+                Some(class) => class.to_string(),
+                None => "other".to_string(),
+            },
+        };
+        // the sample at `t` has the packets up to `t`, so that the first one is all zeros
+        let second = (packet.time - first).max(0.0) as u64 + 1;
+        let stat = seconds
+            .entry(second)
+            .or_default()
+            .entry(key.clone())
+            .or_insert(RecordedStat {
+                rx: 0,
+                tx: 0,
+                drops: 0,
+            });
+        if packet.outgoing {
+            stat.tx += packet.len;
+        } else {
+            stat.rx += packet.len;
+        }
+        *totals.entry(key).or_default() += packet.len;
+    }
+    let first = first.ok_or_else(|| invalid("no packet"))?;
+
+    let series: Vec<String> = match by {
+        Breakdown::interface => {
+            // interfaces of several sections may have the same name
+            let mut names: Vec<String> = vec![];
+            for name in packets.interfaces() {
+                if totals.contains_key(&name) && !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            names
+        }
+        Breakdown::protocol => {
+            let mut classes: Vec<String> = totals.keys().cloned().collect();
+            classes.sort_by(|a, b| (totals[b], a).cmp(&(totals[a], b)));
+            if classes.len() > MAX_CLASSES {
+                // the packets of unsupported link types are already `other`
+                classes.retain(|class| class != "other");
+                classes.truncate(MAX_CLASSES - 1);
+                classes.push("other".to_string());
+            }
+            classes
+        }
+    };
+    // the protocols left out count as `other`
+    let index = |key: &str| {
+        series
+            .iter()
+            .position(|s| s == key)
+            .or_else(|| series.iter().position(|s| s == "other"))
+    };
+
+    let zero = RecordedStat {
+        rx: 0,
+        tx: 0,
+        drops: 0,
+    };
+    let mut stats = vec![zero; series.len()];
+    let sample = |time: u64, stats: &[RecordedStat]| Sample {
+        time: time as f64,
+        stats: stats.iter().map(|&stat| Some(stat)).collect(),
+    };
+    let mut samples = vec![sample(0, &stats)];
+    for (second, keys) in seconds {
+        // no traffic since the previous sample
+        if second > 1 && samples[samples.len() - 1].time < (second - 1) as f64 {
+            samples.push(sample(second - 1, &stats));
+        }
+        for (key, stat) in keys {
+            if let Some(i) = index(&key) {
+                stats[i].rx += stat.rx;
+                stats[i].tx += stat.tx;
+            }
+        }
+        samples.push(sample(second, &stats));
+    }
+    Ok(Recording {
+        header: Header {
+            version: Header::VERSION,
+            started_at: first as u64,
+            interfaces: series,
+        },
+        samples,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PCAP: &[u8] = include_bytes!("fixtures/capture.pcap");
+    const PCAPNG: &[u8] = include_bytes!("fixtures/capture.pcapng");
+
+    /// Rx and tx of each series at each sample.
+    fn summary(recording: &Recording) -> Vec<(f64, Vec<(u64, u64)>)> {
+        recording
+            .samples
+            .iter()
+            .map(|sample| {
+                let stats = sample
+                    .stats
+                    .iter()
+                    .map(|s| s.unwrap())
+                    .map(|s| (s.rx, s.tx));
+                (sample.time, stats.collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pcap() {
+        assert!(is_capture(PCAP) && is_capture(PCAPNG));
+        assert!(!is_capture(b"{\"version\":1}"));
+        let packets: Vec<Packet> = Packets::new(PCAP, "capture")
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let classes: Vec<Option<Class>> = packets.iter().map(|p| p.class).collect();
+        assert_eq!(
+            classes,
+            vec![
+                Some(Class::Tcp(443)),
+                Some(Class::Udp(53)),
+                Some(Class::Tcp(443)),
+                Some(Class::Arp)
+            ]
+        );
+        assert_eq!(packets[1].time, 1_700_000_000.5);
+        assert_eq!(packets[0].len, 1514);
+
+        let r = recording(PCAP, "capture", Breakdown::interface).unwrap();
+        assert_eq!(r.header.interfaces, vec!["capture"]);
+        assert_eq!(r.header.started_at, 1_700_000_000);
+        assert_eq!(
+            summary(&r),
+            vec![
+                (0.0, vec![(0, 0)]),
+                (1.0, vec![(1608, 0)]),
+                (2.0, vec![(2626, 0)]),
+                (3.0, vec![(2686, 0)]),
+            ]
+        );
+        let r = recording(PCAP, "capture", Breakdown::protocol).unwrap();
+        assert_eq!(r.header.interfaces, vec!["TCP/443", "UDP/53", "ARP"]);
+    }
+
+    #[test]
+    fn test_pcapng() {
+        let packets: Vec<Packet> = Packets::new(PCAPNG, "capture")
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let summary_of = |p: &Packet| (p.interface, p.outgoing, p.class);
+        assert_eq!(
+            packets.iter().map(summary_of).collect::<Vec<_>>(),
+            vec![
+                (0, false, Some(Class::Tcp(443))),
+                (1, true, Some(Class::Udp(53))),
+                (0, true, Some(Class::Icmp)),
+            ]
+        );
+        // nanoseconds on `lo`
+        assert_eq!(packets[1].time, 1_700_000_000.25);
+
+        let r = recording(PCAPNG, "capture", Breakdown::interface).unwrap();
+        assert_eq!(r.header.interfaces, vec!["eth0", "lo"]);
+        assert_eq!(
+            summary(&r),
+            vec![
+                (0.0, vec![(0, 0), (0, 0)]),
+                (1.0, vec![(1514, 0), (0, 44)]),
+                (2.0, vec![(1514, 98), (0, 44)]),
+            ]
+        );
+        let r = recording(PCAPNG, "capture", Breakdown::protocol).unwrap();
+        assert_eq!(r.header.interfaces, vec!["TCP/443", "ICMP", "UDP/53"]);
+
+        assert!(Packets::new(&PCAPNG[..100], "capture")
+            .unwrap()
+            .any(|p| p.is_err()));
+        assert!(recording(&b"\x0a\x0d\x0d\x0a"[..], "capture", Breakdown::interface).is_err());
+    }
+
+    #[test]
+    fn test_gap() {
+        // the same packet 0 and 3.5 seconds into the capture
+        let mut pcap = PCAP[..24 + 16 + 54].to_vec();
+        pcap.extend_from_slice(&PCAP[24..24 + 16 + 54]);
+        pcap[24 + 70..24 + 74].copy_from_slice(&1_700_000_003_u32.to_le_bytes());
+        pcap[24 + 74..24 + 78].copy_from_slice(&500_000_u32.to_le_bytes());
+        let r = recording(&pcap[..], "capture", Breakdown::interface).unwrap();
+        assert_eq!(
+            summary(&r),
+            vec![
+                (0.0, vec![(0, 0)]),
+                (1.0, vec![(1514, 0)]),
+                (3.0, vec![(1514, 0)]),
+                (4.0, vec![(3028, 0)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_classes() {
+        let udp = |port: u16| {
+            let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 17, 0, 0];
+            packet.extend_from_slice(&[127, 0, 0, 1, 127, 0, 0, 1]);
+            packet.extend_from_slice(&port.to_be_bytes());
+            packet.extend_from_slice(&port.to_be_bytes());
+            packet
+        };
+        // raw IP packets, of 9 UDP ports and the most traffic of neither IPv4 nor IPv6
+        let mut packets: Vec<(Vec<u8>, u32)> = (1..=9)
+            .map(|port| (udp(port), 1000 - u32::from(port)))
+            .collect();
+        packets.push((vec![0; 20], 5000));
+        let mut pcap = vec![];
+        for &field in &[PCAP_MAGIC, 0x0004_0002, 0, 0, 65535, LINKTYPE_RAW] {
+            pcap.extend_from_slice(&field.to_le_bytes());
+        }
+        for (packet, len) in &packets {
+            for &field in &[0, 0, packet.len() as u32, *len] {
+                pcap.extend_from_slice(&field.to_le_bytes());
+            }
+            pcap.extend_from_slice(packet);
+        }
+
+        let r = recording(&pcap[..], "capture", Breakdown::protocol).unwrap();
+        // once, last, with UDP/8 and UDP/9
+        let mut series: Vec<String> = (1..=7).map(|port| format!("UDP/{}", port)).collect();
+        series.push("other".to_string());
+        assert_eq!(r.header.interfaces, series);
+        assert_eq!(summary(&r)[1].1[7], (5000 + 992 + 991, 0));
+    }
+}
@@ -0,0 +1,219 @@
+//! Sockets of processes from `/proc/net/{tcp,tcp6,udp,udp6}` and `/proc/<pid>/fd`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+use crate::{Error, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// A line of `/proc/net/tcp` and the like.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SocketEntry {
+    pub protocol: Protocol,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    /// `TCP_ESTABLISHED`, `TCP_LISTEN`, ... from `include/net/tcp_states.h`
+    pub state: u8,
+    pub uid: u32,
+    pub inode: u64,
+}
+
+/// A process with the inodes of its sockets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessSockets {
+    pub pid: u32,
+    /// `/proc/<pid>/comm`
+    pub name: String,
+    pub inodes: Vec<u64>,
+}
+
+/// Parses an address such as `0100007F:0277`, where the address is the hex of the `__be32`s
+/// in host byte order.
+fn parse_address(s: &str) -> Option<SocketAddr> {
+    let mut parts = s.splitn(2, ':');
+    let (addr, port) = (parts.next()?, parts.next()?);
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let word = |i: usize| -> Option<[u8; 4]> {
+        let word = u32::from_str_radix(addr.get(i * 8..(i + 1) * 8)?, 16).ok()?;
+        Some(word.to_ne_bytes())
+    };
+    let ip = match addr.len() {
+        8 => IpAddr::V4(Ipv4Addr::from(word(0)?)),
+        32 => {
+            let mut octets = [0; 16];
+            for i in 0..4 {
+                octets[i * 4..(i + 1) * 4].copy_from_slice(&word(i)?);
+            }
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// Parses the content of `/proc/net/tcp`, `/proc/net/udp6` or the like.
+pub fn parse_net_sockets(text: &str, protocol: Protocol) -> Result<Vec<SocketEntry>> {
+    text.lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let entry = || -> Option<SocketEntry> {
+                Some(SocketEntry {
+                    protocol,
+                    local: parse_address(fields.get(1)?)?,
+                    remote: parse_address(fields.get(2)?)?,
+                    state: u8::from_str_radix(fields.get(3)?, 16).ok()?,
+                    uid: fields.get(7)?.parse().ok()?,
+                    inode: fields.get(9)?.parse().ok()?,
+                })
+            };
+            entry().ok_or_else(|| Error::ParseError(format!("invalid socket entry `{}`", line)))
+        })
+        .collect()
+}
+
+/// Reads the TCP and UDP sockets of both IPv4 and IPv6 in `proc_dir`, usually `/proc`.
+///
+/// Missing files, e.g. `tcp6` when IPv6 is disabled, are skipped.
+pub fn read_net_sockets(proc_dir: &Path) -> Result<Vec<SocketEntry>> {
+    let mut entries = vec![];
+    for &(file, protocol) in &[
+        ("tcp", Protocol::Tcp),
+        ("tcp6", Protocol::Tcp),
+        ("udp", Protocol::Udp),
+        ("udp6", Protocol::Udp),
+    ] {
+        if let Ok(text) = fs::read_to_string(proc_dir.join("net").join(file)) {
+            entries.extend(parse_net_sockets(&text, protocol)?);
+        }
+    }
+    Ok(entries)
+}
+
+/// `socket:[12345]` -> `12345`
+pub fn socket_inode(link: &str) -> Option<u64> {
+    link.strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+/// Reads the processes in `proc_dir` with at least one socket.
+///
+/// Processes whose `fd` directory can not be read, e.g. those of other users when not run as
+/// root, or which exit while being read, are skipped.
+pub fn read_process_sockets(proc_dir: &Path) -> Result<Vec<ProcessSockets>> {
+    let mut processes = vec![];
+    for entry in fs::read_dir(proc_dir)? {
+        let entry = entry?;
+        let pid: u32 = match entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let fds = match fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        let inodes: Vec<u64> = fds
+            .filter_map(|fd| fs::read_link(fd.ok()?.path()).ok())
+            .filter_map(|link| socket_inode(link.to_str()?))
+            .collect();
+        if inodes.is_empty() {
+            continue;
+        }
+        let name = fs::read_to_string(entry.path().join("comm"))
+            .map(|comm| comm.trim_end().to_string())
+            .unwrap_or_default();
+        processes.push(ProcessSockets { pid, name, inodes });
+    }
+    processes.sort_by_key(|process| process.pid);
+    Ok(processes)
+}
+
+/// Maps the inode of each socket to the pid of the process owning it.
+///
+/// A socket shared by several processes, e.g. after a `fork`, is attributed to the lowest pid.
+pub fn inode_owners(processes: &[ProcessSockets]) -> HashMap<u64, u32> {
+    let mut owners = HashMap::new();
+    for process in processes.iter() {
+        for &inode in process.inodes.iter() {
+            owners.entry(inode).or_insert(process.pid);
+        }
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_path;
+    use std::os::unix::fs::symlink;
+
+    // trimmed `/proc/net/tcp` and `/proc/net/udp6` of a little-endian host
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0277 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 17350 1 0000000000000000 100 0 0 10 0
+   1: 0200A8C0:A0C2 2200A8C0:01BB 01 00000000:00000000 02:000A7F5C 00000000  1000        0 93021 2 0000000000000000 20 4 30 10 -1
+";
+    const UDP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  103: 00000000000000000000000001000000:0143 00000000000000000000000000000000:0000 07 00000000:00000000 00:00000000 00000000   101        0 21042 2 0000000000000000 0
+";
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn test_parse_net_sockets() {
+        let entries = parse_net_sockets(TCP, Protocol::Tcp).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].local, "127.0.0.1:631".parse().unwrap());
+        assert_eq!(entries[0].state, 0x0a);
+        assert_eq!(entries[1].local, "192.168.0.2:41154".parse().unwrap());
+        assert_eq!(entries[1].remote, "192.168.0.34:443".parse().unwrap());
+        assert_eq!((entries[1].uid, entries[1].inode), (1000, 93021));
+
+        let entries = parse_net_sockets(UDP6, Protocol::Udp).unwrap();
+        assert_eq!(entries[0].local, "[::1]:323".parse().unwrap());
+        assert_eq!(entries[0].inode, 21042);
+
+        assert!(parse_net_sockets("header\n  0: 0100007F 00000000:0000\n", Protocol::Tcp).is_err());
+    }
+
+    #[test]
+    fn test_socket_inode() {
+        assert_eq!(socket_inode("socket:[93021]"), Some(93021));
+        assert_eq!(socket_inode("pipe:[93021]"), None);
+        assert_eq!(socket_inode("/dev/null"), None);
+    }
+
+    #[test]
+    fn test_read_process_sockets() {
+        let dir = temp_path("procfs");
+        for &(pid, name, links) in &[
+            ("42", "curl", &["socket:[93021]", "/dev/null"][..]),
+            ("7", "sshd", &["socket:[17350]", "socket:[93021]"][..]),
+            ("8", "cat", &["pipe:[1]"][..]),
+        ] {
+            let fd = dir.join(pid).join("fd");
+            fs::create_dir_all(&fd).unwrap();
+            fs::write(dir.join(pid).join("comm"), format!("{}\n", name)).unwrap();
+            for (i, link) in links.iter().enumerate() {
+                symlink(link, fd.join(i.to_string())).unwrap();
+            }
+        }
+
+        let processes = read_process_sockets(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(processes.len(), 2);
+        assert_eq!((processes[0].pid, processes[0].name.as_str()), (7, "sshd"));
+        assert_eq!(processes[1].inodes, vec![93021]);
+        let owners = inode_owners(&processes);
+        assert_eq!(owners[&93021], 7);
+        assert_eq!(owners[&17350], 7);
+    }
+}
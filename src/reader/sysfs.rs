@@ -0,0 +1,91 @@
+//! Link settings from `/sys/class/net/<interface>`.
+//!
+//! The files do not exist on other platforms than Linux, in which case the settings are
+//! `None`.
+
+use std::path::Path;
+use std::{fmt, fs};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Duplex {
+    Half,
+    Full,
+}
+
+impl fmt::Display for Duplex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Duplex::Half => write!(f, "half"),
+            Duplex::Full => write!(f, "full"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinkSettings {
+    pub mtu: Option<u32>,
+    /// Link speed in Mbit/s
+    pub speed: Option<u64>,
+    pub duplex: Option<Duplex>,
+}
+
+impl LinkSettings {
+    const SYSFS_NET: &'static str = "/sys/class/net";
+
+    pub fn read(interface: &str) -> LinkSettings {
+        LinkSettings::read_dir(&Path::new(Self::SYSFS_NET).join(interface))
+    }
+
+    /// Reads the settings from `dir`. Values which can not be read, e.g. the speed of a link
+    /// which is down, are `None`.
+    fn read_dir(dir: &Path) -> LinkSettings {
+        let read = |name: &str| {
+            fs::read_to_string(dir.join(name))
+                .ok()
+                .map(|s| s.trim().to_string())
+        };
+        LinkSettings {
+            mtu: read("mtu").and_then(|s| s.parse().ok()),
+            // virtual links report `-1` or fail with `EINVAL`
+            speed: read("speed")
+                .and_then(|s| s.parse().ok())
+                .filter(|&speed| speed > 0),
+            duplex: match read("duplex").as_deref() {
+                Some("full") => Some(Duplex::Full),
+                Some("half") => Some(Duplex::Half),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_path;
+
+    #[test]
+    fn test_read_dir() {
+        let dir = temp_path("sysfs");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("mtu"), "1500\n").unwrap();
+        fs::write(dir.join("speed"), "10000\n").unwrap();
+        fs::write(dir.join("duplex"), "full\n").unwrap();
+        assert_eq!(
+            LinkSettings::read_dir(&dir),
+            LinkSettings {
+                mtu: Some(1500),
+                speed: Some(10000),
+                duplex: Some(Duplex::Full),
+            }
+        );
+
+        fs::write(dir.join("speed"), "-1\n").unwrap();
+        fs::write(dir.join("duplex"), "unknown\n").unwrap();
+        let settings = LinkSettings::read_dir(&dir);
+        assert_eq!((settings.speed, settings.duplex), (None, None));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(LinkSettings::read_dir(&dir), LinkSettings::default());
+    }
+}
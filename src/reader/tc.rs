@@ -0,0 +1,331 @@
+//! Queueing disciplines and classes of traffic control with their stats, from `RTM_GETQDISC` and
+//! `RTM_GETTCLASS` of the `NETLINK_ROUTE` netlink socket, as `tc -s qdisc` and `tc -s class` do.
+//!
+//! See `linux/rtnetlink.h`, `linux/pkt_sched.h` and `linux/gen_stats.h` for the layouts.
+
+use byteorder::{ByteOrder, NativeEndian};
+use std::collections::BTreeSet;
+use std::{ffi, io, mem};
+
+use crate::reader::in_tc::{Handle, TcCounters, TcMsg};
+use crate::{Error, Result};
+
+pub(crate) const NLMSG_HDR_LEN: usize = 16;
+const TCMSG_LEN: usize = 20;
+const TCA_KIND: u16 = 1;
+const TCA_STATS: u16 = 3;
+const TCA_STATS2: u16 = 7;
+const TCA_STATS_BASIC: u16 = 1;
+const TCA_STATS_QUEUE: u16 = 3;
+
+pub(crate) fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Iterates over the netlink attributes in `buf` as `(type, payload)`.
+pub(crate) fn attrs(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if buf.len() < 4 {
+            return None;
+        }
+        let len = NativeEndian::read_u16(&buf[0..2]) as usize;
+        if len < 4 || len > buf.len() {
+            return None;
+        }
+        // without `NLA_F_NESTED` and `NLA_F_NET_BYTEORDER`
+        let attr = (NativeEndian::read_u16(&buf[2..4]) & 0x3fff, &buf[4..len]);
+        buf = &buf[align(len).min(buf.len())..];
+        Some(attr)
+    })
+}
+
+/// Builds a dump request of `msg_type`, `RTM_GETQDISC` or `RTM_GETTCLASS`, of the interface
+/// `ifindex`, or of every interface if 0.
+pub fn request(msg_type: u16, ifindex: u32, seq: u32) -> Vec<u8> {
+    let len = NLMSG_HDR_LEN + TCMSG_LEN;
+    let mut buf = vec![0; len];
+    NativeEndian::write_u32(&mut buf[0..4], len as u32);
+    NativeEndian::write_u16(&mut buf[4..6], msg_type);
+    NativeEndian::write_u16(
+        &mut buf[6..8],
+        (libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16,
+    );
+    NativeEndian::write_u32(&mut buf[8..12], seq);
+    buf[NLMSG_HDR_LEN] = libc::AF_UNSPEC as u8;
+    NativeEndian::write_u32(&mut buf[NLMSG_HDR_LEN + 4..NLMSG_HDR_LEN + 8], ifindex);
+    buf
+}
+
+/// Parses a `tcmsg` followed by its attributes.
+fn parse_msg(class: bool, buf: &[u8]) -> Result<TcMsg> {
+    if buf.len() < TCMSG_LEN {
+        return Err(Error::ParseError(format!(
+            "tcmsg is {} bytes, buffer is only {} bytes",
+            TCMSG_LEN,
+            buf.len()
+        )));
+    }
+    let mut msg = TcMsg {
+        class,
+        ifindex: NativeEndian::read_u32(&buf[4..8]),
+        handle: Handle(NativeEndian::read_u32(&buf[8..12])),
+        parent: Handle(NativeEndian::read_u32(&buf[12..16])),
+        kind: String::new(),
+        counters: TcCounters::default(),
+    };
+    let mut has_stats2 = false;
+    for (attr, payload) in attrs(&buf[TCMSG_LEN..]) {
+        match attr {
+            TCA_KIND => {
+                let kind = payload.split(|&b| b == 0).next().unwrap_or_default();
+                msg.kind = String::from_utf8_lossy(kind).to_string();
+            }
+            TCA_STATS2 => {
+                has_stats2 = true;
+                for (attr, payload) in attrs(payload) {
+                    let c = &mut msg.counters;
+                    match attr {
+                        // `struct gnet_stats_basic`
+                        TCA_STATS_BASIC if payload.len() >= 12 => {
+                            c.bytes = NativeEndian::read_u64(&payload[0..8]);
+                            c.packets = u64::from(NativeEndian::read_u32(&payload[8..12]));
+                        }
+                        // `struct gnet_stats_queue`
+                        TCA_STATS_QUEUE if payload.len() >= 20 => {
+                            c.qlen = NativeEndian::read_u32(&payload[0..4]);
+                            c.backlog = NativeEndian::read_u32(&payload[4..8]);
+                            c.drops = u64::from(NativeEndian::read_u32(&payload[8..12]));
+                            c.requeues = u64::from(NativeEndian::read_u32(&payload[12..16]));
+                            c.overlimits = u64::from(NativeEndian::read_u32(&payload[16..20]));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            // `struct tc_stats` of kernels without `TCA_STATS2`
+            TCA_STATS if !has_stats2 && payload.len() >= 36 => {
+                msg.counters = TcCounters {
+                    bytes: NativeEndian::read_u64(&payload[0..8]),
+                    packets: u64::from(NativeEndian::read_u32(&payload[8..12])),
+                    drops: u64::from(NativeEndian::read_u32(&payload[12..16])),
+                    overlimits: u64::from(NativeEndian::read_u32(&payload[16..20])),
+                    requeues: 0,
+                    qlen: NativeEndian::read_u32(&payload[28..32]),
+                    backlog: NativeEndian::read_u32(&payload[32..36]),
+                };
+            }
+            _ => {}
+        }
+    }
+    Ok(msg)
+}
+
+/// Parses the netlink messages in `buf`, a datagram received in reply to a dump request, with
+/// `parse` called with the type and payload of each message but `NLMSG_DONE` and `NLMSG_ERROR`.
+///
+/// Returns the messages parsed, and whether `NLMSG_DONE` ended the dump.
+pub(crate) fn parse_with<T>(
+    mut buf: &[u8],
+    mut parse: impl FnMut(u16, &[u8]) -> Result<Option<T>>,
+) -> Result<(Vec<T>, bool)> {
+    let mut msgs = vec![];
+    while buf.len() >= NLMSG_HDR_LEN {
+        let len = NativeEndian::read_u32(&buf[0..4]) as usize;
+        if len < NLMSG_HDR_LEN || len > buf.len() {
+            return Err(Error::ParseError(format!(
+                "invalid netlink message length {}",
+                len
+            )));
+        }
+        let payload = &buf[NLMSG_HDR_LEN..len];
+        match NativeEndian::read_u16(&buf[4..6]) {
+            t if i32::from(t) == libc::NLMSG_DONE => return Ok((msgs, true)),
+            t if i32::from(t) == libc::NLMSG_ERROR => {
+                let errno = if payload.len() >= 4 {
+                    -NativeEndian::read_i32(&payload[0..4])
+                } else {
+                    0
+                };
+                return Err(io::Error::from_raw_os_error(errno).into());
+            }
+            t => msgs.extend(parse(t, payload)?),
+        }
+        buf = &buf[align(len).min(buf.len())..];
+    }
+    Ok((msgs, false))
+}
+
+/// Parses the netlink messages in `buf`, a datagram received in reply to `request`.
+///
+/// Returns the qdiscs or classes, and whether `NLMSG_DONE` ended the dump.
+pub fn parse_messages(buf: &[u8]) -> Result<(Vec<TcMsg>, bool)> {
+    parse_with(buf, |msg_type, payload| match msg_type {
+        libc::RTM_NEWQDISC => parse_msg(false, payload).map(Some),
+        libc::RTM_NEWTCLASS => parse_msg(true, payload).map(Some),
+        _ => Ok(None),
+    })
+}
+
+/// Opens a `NETLINK_ROUTE` socket in the network namespace of the calling thread.
+pub(crate) fn socket() -> Result<libc::c_int> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(fd)
+}
+
+/// Dumps the qdiscs of every interface, followed by the classes of the interfaces with a
+/// classful qdisc.
+pub fn qdiscs_and_classes() -> Result<Vec<TcMsg>> {
+    let fd = socket()?;
+    let result =
+        dump(fd, &request(libc::RTM_GETQDISC, 0, 1), parse_messages).and_then(|mut msgs| {
+            // the kernel dumps the classes of a single interface only
+            let ifindices: BTreeSet<u32> = msgs.iter().map(|msg| msg.ifindex).collect();
+            for ifindex in ifindices {
+                let req = request(libc::RTM_GETTCLASS, ifindex, 1);
+                msgs.extend(dump(fd, &req, parse_messages)?);
+            }
+            Ok(msgs)
+        });
+    unsafe { libc::close(fd) };
+    result
+}
+
+/// Parses a datagram of netlink messages, returning them and whether the dump ended.
+pub(crate) type Parse<T> = fn(&[u8]) -> Result<(Vec<T>, bool)>;
+
+/// Sends the dump request `req` on `fd` and parses the replies with `parse` until the dump ends.
+pub(crate) fn dump<T>(fd: libc::c_int, req: &[u8], parse: Parse<T>) -> Result<Vec<T>> {
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    let sent = unsafe {
+        libc::sendto(
+            fd,
+            req.as_ptr() as *const libc::c_void,
+            req.len(),
+            0,
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let mut msgs = vec![];
+    let mut buf = vec![0_u8; 32 * 1024];
+    loop {
+        let len = unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let (received, done) = parse(&buf[..len as usize])?;
+        msgs.extend(received);
+        if done || len == 0 {
+            return Ok(msgs);
+        }
+    }
+}
+
+/// Name of the interface `ifindex`, or the index itself if the interface is gone.
+pub fn interface_name(ifindex: u32) -> String {
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    let ret = unsafe { libc::if_indextoname(ifindex, name.as_mut_ptr()) };
+    if ret.is_null() {
+        return ifindex.to_string();
+    }
+    unsafe { ffi::CStr::from_ptr(name.as_ptr()) }
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replies of Linux 6.18 to `request(RTM_GETQDISC, 0, 1)` and `request(RTM_GETTCLASS, 3, 1)`
+    /// in a namespace with `lo`, and a veth pair `v0`/`v1` shaped by
+    /// `htb default 10`, its classes `1:1` and `1:10` of 100kbit, and `pfifo limit 50` at
+    /// `1:10`, after 500 UDP datagrams of 1000 bytes
+    #[cfg(target_endian = "little")]
+    const QDISCS: &[u8] = include_bytes!("fixtures/tc_qdisc.bin");
+    #[cfg(target_endian = "little")]
+    const CLASSES: &[u8] = include_bytes!("fixtures/tc_class.bin");
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn test_parse_recorded() {
+        let (qdiscs, done) = parse_messages(QDISCS).unwrap();
+        assert!(done);
+        let summary: Vec<(u32, String, String, &str)> = qdiscs
+            .iter()
+            .map(|q| {
+                (
+                    q.ifindex,
+                    q.handle.to_string(),
+                    q.parent.to_string(),
+                    q.kind.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "0:0".to_string(), "root".to_string(), "noqueue"),
+                (2, "0:0".to_string(), "root".to_string(), "noqueue"),
+                (3, "1:0".to_string(), "root".to_string(), "htb"),
+                (3, "10:0".to_string(), "1:10".to_string(), "pfifo"),
+            ]
+        );
+        assert!(qdiscs.iter().all(|q| !q.class));
+        assert_eq!(
+            qdiscs[2].counters,
+            TcCounters {
+                bytes: 4258,
+                packets: 5,
+                drops: 448,
+                overlimits: 4,
+                requeues: 0,
+                qlen: 48,
+                backlog: 50016,
+            }
+        );
+
+        let (classes, done) = parse_messages(CLASSES).unwrap();
+        assert!(done);
+        assert_eq!(classes.len(), 2);
+        assert!(classes.iter().all(|c| c.class && c.kind == "htb"));
+        assert_eq!(
+            (classes[0].handle, classes[0].parent),
+            (Handle(0x10010), Handle(0x10001))
+        );
+        assert_eq!(classes[0].counters.drops, 448);
+        assert_eq!(classes[0].counters.backlog, 50016);
+        assert_eq!(
+            (classes[1].handle, classes[1].parent),
+            (Handle(0x10001), Handle::ROOT)
+        );
+        assert_eq!(classes[1].counters.overlimits, 1);
+
+        assert!(parse_messages(&QDISCS[..40]).is_err());
+    }
+
+    #[test]
+    fn test_request() {
+        let req = request(libc::RTM_GETTCLASS, 3, 7);
+        assert_eq!(req.len(), 36);
+        assert_eq!(NativeEndian::read_u32(&req[0..4]), 36);
+        assert_eq!(NativeEndian::read_u16(&req[4..6]), 42);
+        assert_eq!(NativeEndian::read_u16(&req[6..8]), 0x301);
+        assert_eq!(NativeEndian::read_u32(&req[8..12]), 7);
+        assert_eq!(NativeEndian::read_u32(&req[20..24]), 3);
+    }
+}
@@ -0,0 +1,174 @@
+//! File format of `mone record`, read by `mone replay` and `mone report`.
+//!
+//! A recording is JSON Lines: a header with the interfaces, then one sample of the raw counters
+//! per line. pcap and pcapng files are read as recordings too, see `reader::pcap`.
+//!
+//! ```text
+//! {"version":1,"started_at":1700000000,"interfaces":["lo","eth0"]}
+//! {"time":0.0,"stats":[{"rx":0,"tx":0,"drops":0},null]}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::reader::pcap;
+use crate::utils::NumBytes;
+use crate::{
+    Breakdown, Error, InterfaceInfo, InterfaceInfoItem, InterfaceStat, InterfaceStats, Result,
+};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Header {
+    pub version: u32,
+    /// Unix time the recording started at
+    pub started_at: u64,
+    pub interfaces: Vec<String>,
+}
+
+impl Header {
+    pub const VERSION: u32 = 1;
+
+    pub fn new(info: &InterfaceInfo, started_at: u64) -> Header {
+        Header {
+            version: Self::VERSION,
+            started_at,
+            interfaces: info.0.iter().map(|item| item.name.clone()).collect(),
+        }
+    }
+
+    pub fn info(&self) -> InterfaceInfo {
+        InterfaceInfo(
+            self.interfaces
+                .iter()
+                .map(|name| InterfaceInfoItem::new(name))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RecordedStat {
+    pub rx: u64,
+    pub tx: u64,
+    pub drops: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Sample {
+    /// Seconds since the recording started
+    pub time: f64,
+    pub stats: Vec<Option<RecordedStat>>,
+}
+
+impl Sample {
+    pub fn new(time: f64, stats: &InterfaceStats) -> Sample {
+        Sample {
+            time,
+            stats: stats
+                .0
+                .iter()
+                .map(|stat| {
+                    stat.as_ref().map(|stat| RecordedStat {
+                        rx: stat.rx.value(),
+                        tx: stat.tx.value(),
+                        drops: stat.drops,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn stats(&self) -> InterfaceStats {
+        InterfaceStats(
+            self.stats
+                .iter()
+                .map(|stat| {
+                    stat.map(|stat| InterfaceStat {
+                        rx: NumBytes::from(stat.rx),
+                        tx: NumBytes::from(stat.tx),
+                        drops: stat.drops,
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+pub struct Recording {
+    pub header: Header,
+    pub samples: Vec<Sample>,
+}
+
+impl Recording {
+    pub fn open(path: &Path) -> Result<Recording> {
+        Recording::load(path, Breakdown::interface)
+    }
+
+    /// Opens a recording, or a pcap or pcapng file with a series for each interface or protocol
+    /// as `by` says.
+    pub fn load(path: &Path, by: Breakdown) -> Result<Recording> {
+        let file = File::open(path).map_err(|err| {
+            Error::ParseError(format!("failed to open `{}`: {}", path.display(), err))
+        })?;
+        let mut reader = BufReader::new(file);
+        if pcap::is_capture(reader.fill_buf()?) {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            return pcap::recording(reader, &name, by);
+        }
+        if by != Breakdown::interface {
+            return Err(Error::Other(
+                "Only pcap and pcapng files can be broken down by protocol.",
+            ));
+        }
+        Recording::from_reader(reader)
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Recording> {
+        let mut lines = reader.lines().enumerate();
+        let parse_err = |i: usize, err: serde_json::Error| {
+            Error::ParseError(format!("line {}: {}", i + 1, err))
+        };
+
+        let header: Header = match lines.next() {
+            Some((i, line)) => serde_json::from_str(&line?).map_err(|err| parse_err(i, err))?,
+            None => return Err(Error::ParseError("empty recording".to_string())),
+        };
+        if header.version != Header::VERSION {
+            return Err(Error::ParseError(format!(
+                "unsupported recording version {}",
+                header.version
+            )));
+        }
+
+        let mut samples: Vec<Sample> = vec![];
+        for (i, line) in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let sample: Sample = serde_json::from_str(&line).map_err(|err| parse_err(i, err))?;
+            if sample.stats.len() != header.interfaces.len() {
+                return Err(Error::ParseError(format!(
+                    "line {}: expected {} interfaces, got {}",
+                    i + 1,
+                    header.interfaces.len(),
+                    sample.stats.len()
+                )));
+            }
+            samples.push(sample);
+        }
+        if samples.is_empty() {
+            return Err(Error::ParseError("recording has no samples".to_string()));
+        }
+        Ok(Recording { header, samples })
+    }
+}
+
+pub fn write_line<W: io::Write, T: Serialize>(writer: &mut W, value: &T) -> Result<()> {
+    let line = serde_json::to_string(value)
+        .map_err(|err| Error::ParseError(format!("failed to serialize: {}", err)))?;
+    writeln!(writer, "{}", line)?;
+    Ok(())
+}
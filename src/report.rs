@@ -0,0 +1,170 @@
+//! `mone report`: a summary of a recording made with `mone record`.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::record::{RecordedStat, Recording};
+use crate::utils::NumBytes;
+use crate::{counter_diff, Breakdown, Result};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InterfaceReport {
+    pub name: String,
+    pub rx_total: u64,
+    pub tx_total: u64,
+    /// Highest rates between two samples in bytes per second
+    pub rx_peak: f64,
+    pub tx_peak: f64,
+    pub drops: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub samples: usize,
+    /// Seconds from the first to the last sample
+    pub duration: f64,
+    pub interfaces: Vec<InterfaceReport>,
+}
+
+impl Report {
+    pub fn new(recording: &Recording) -> Report {
+        let samples = &recording.samples;
+        let mut interfaces: Vec<InterfaceReport> = recording
+            .header
+            .interfaces
+            .iter()
+            .map(|name| InterfaceReport {
+                name: name.clone(),
+                ..InterfaceReport::default()
+            })
+            .collect();
+
+        for pair in samples.windows(2) {
+            let dt = pair[1].time - pair[0].time;
+            for (i, report) in interfaces.iter_mut().enumerate() {
+                let (prev, cur): (RecordedStat, RecordedStat) =
+                    match (pair[0].stats[i], pair[1].stats[i]) {
+                        (Some(prev), Some(cur)) => (prev, cur),
+                        _ => continue,
+                    };
+                let rx = counter_diff(prev.rx, cur.rx);
+                let tx = counter_diff(prev.tx, cur.tx);
+                report.rx_total += rx;
+                report.tx_total += tx;
+                report.drops += counter_diff(prev.drops, cur.drops);
+                if dt > 0.0 {
+                    report.rx_peak = report.rx_peak.max(rx as f64 / dt);
+                    report.tx_peak = report.tx_peak.max(tx as f64 / dt);
+                }
+            }
+        }
+
+        Report {
+            samples: samples.len(),
+            duration: samples[samples.len() - 1].time - samples[0].time,
+            interfaces,
+        }
+    }
+
+    fn average(&self, total: u64) -> f64 {
+        if self.duration > 0.0 {
+            total as f64 / self.duration
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = |v: f64| format!("{}", NumBytes::from(v.round() as u64));
+        let name_width = self
+            .interfaces
+            .iter()
+            .map(|report| report.name.len())
+            .chain(Some("interface".len()))
+            .max()
+            .unwrap_or(0);
+        let w = NumBytes::<u64>::display_width() + 2;
+
+        writeln!(f, "{} samples over {:.1} s", self.samples, self.duration)?;
+        writeln!(
+            f,
+            "{:<nw$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>6}",
+            "interface",
+            "total rx",
+            "total tx",
+            "avg rx/s",
+            "avg tx/s",
+            "peak rx/s",
+            "peak tx/s",
+            "drops",
+            nw = name_width,
+            w = w
+        )?;
+        for report in self.interfaces.iter() {
+            writeln!(
+                f,
+                "{:<nw$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>6}",
+                report.name,
+                bytes(report.rx_total as f64),
+                bytes(report.tx_total as f64),
+                bytes(self.average(report.rx_total)),
+                bytes(self.average(report.tx_total)),
+                bytes(report.rx_peak),
+                bytes(report.tx_peak),
+                report.drops,
+                nw = name_width,
+                w = w
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn run(path: &Path, by: Breakdown) -> Result<()> {
+    print!("{}", Report::new(&Recording::load(path, by)?));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDING: &str = r#"{"version":1,"started_at":0,"interfaces":["lo","eth0"]}
+{"time":0.0,"stats":[null,{"rx":0,"tx":0,"drops":0}]}
+{"time":1.0,"stats":[null,{"rx":1024,"tx":0,"drops":0}]}
+{"time":2.0,"stats":[null,{"rx":4096,"tx":2048,"drops":3}]}
+"#;
+
+    #[test]
+    fn test_report() {
+        let report = Report::new(&Recording::from_reader(RECORDING.as_bytes()).unwrap());
+        assert_eq!(report.samples, 3);
+        assert_eq!(report.duration, 2.0);
+        assert_eq!(report.interfaces[0].rx_total, 0);
+        let eth0 = &report.interfaces[1];
+        assert_eq!((eth0.rx_total, eth0.tx_total, eth0.drops), (4096, 2048, 3));
+        assert_eq!((eth0.rx_peak, eth0.tx_peak), (3072.0, 2048.0));
+        assert_eq!(
+            format!("{}", report).lines().nth(3).unwrap(),
+            "eth0        4.00 KiB   2.00 KiB   2.00 KiB   1.00 KiB   3.00 KiB   2.00 KiB      3"
+        );
+    }
+
+    #[test]
+    fn test_report_counter_reset() {
+        // the counters of a 32-bit reader wrap around, and the drops are reset
+        let recording = r#"{"version":1,"started_at":0,"interfaces":["eth0"]}
+{"time":0.0,"stats":[{"rx":4294966272,"tx":0,"drops":5}]}
+{"time":1.0,"stats":[{"rx":4294967040,"tx":0,"drops":7}]}
+{"time":2.0,"stats":[{"rx":2048,"tx":0,"drops":1}]}
+{"time":3.0,"stats":[{"rx":3072,"tx":0,"drops":1}]}
+"#;
+        let report = Report::new(&Recording::from_reader(recording.as_bytes()).unwrap());
+        let eth0 = &report.interfaces[0];
+        assert_eq!(eth0.rx_total, 768 + 2048 + 1024);
+        assert_eq!(eth0.rx_peak, 2048.0);
+        assert_eq!(eth0.drops, 2 + 1);
+    }
+}
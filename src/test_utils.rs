@@ -0,0 +1,20 @@
+//! Fixtures shared by the unit tests.
+
+use std::path::PathBuf;
+use std::{env, process};
+
+use crate::{InterfaceInfo, InterfaceInfoItem};
+
+/// Interfaces `lo` and `eth0`
+pub fn info() -> InterfaceInfo {
+    InterfaceInfo(vec![
+        InterfaceInfoItem::new("lo"),
+        InterfaceInfoItem::new("eth0"),
+    ])
+}
+
+/// Path in the temporary directory unique to this process and `name`, e.g. the name of the
+/// module of the test.
+pub fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("mone-{}-{}", process::id(), name))
+}
@@ -1,6 +1,10 @@
 use num::{Num, ToPrimitive};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
 use std::{fmt, ops};
 
+use crate::{Error, Result};
+
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub struct NumBytes<T: Num>(T);
 
@@ -17,46 +21,128 @@ impl<T: Num> ops::Sub<NumBytes<T>> for NumBytes<T> {
     }
 }
 
-fn integer_len(v: f64) -> usize {
-    let v = v.trunc() as u64;
-    let mut p = 10;
-    for l in 1.. {
-        if v < p {
-            return l;
+/// Units to show amounts of bytes in, selected with `--bits` and `--si`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Units {
+    /// Bits instead of bytes
+    pub bits: bool,
+    /// Decimal (kB, MB, ...) instead of binary (KiB, MiB, ...) prefixes
+    pub si: bool,
+}
+
+/// `Units` used by the `Display` of `NumBytes`, set once at startup with `set_units`
+static UNITS: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_units(units: Units) {
+    UNITS.store(units.bits as u8 | (units.si as u8) << 1, Ordering::Relaxed);
+}
+
+pub fn units() -> Units {
+    let units = UNITS.load(Ordering::Relaxed);
+    Units {
+        bits: units & 1 != 0,
+        si: units & 2 != 0,
+    }
+}
+
+impl Units {
+    const IEC_BYTES: [&'static str; 9] =
+        ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
+    const SI_BYTES: [&'static str; 9] = ["B", "kB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB"];
+    const IEC_BITS: [&'static str; 9] = [
+        "bit", "Kibit", "Mibit", "Gibit", "Tibit", "Pibit", "Eibit", "Zibit", "Yibit",
+    ];
+    const SI_BITS: [&'static str; 9] = [
+        "bit", "kbit", "Mbit", "Gbit", "Tbit", "Pbit", "Ebit", "Zbit", "Ybit",
+    ];
+    const VALUE_WIDTH: usize = 4;
+
+    fn names(&self) -> &'static [&'static str; 9] {
+        match (self.bits, self.si) {
+            (false, false) => &Self::IEC_BYTES,
+            (false, true) => &Self::SI_BYTES,
+            (true, false) => &Self::IEC_BITS,
+            (true, true) => &Self::SI_BITS,
+        }
+    }
+
+    /// 1000 or 1024
+    pub fn base(&self) -> f64 {
+        if self.si {
+            1000.0
+        } else {
+            1024.0
         }
-        p *= 10;
     }
-    return 0;
+
+    /// Bytes per unit without prefix, i.e. 1 or 1/8
+    pub fn unit_bytes(&self) -> f64 {
+        if self.bits {
+            1.0 / 8.0
+        } else {
+            1.0
+        }
+    }
+
+    fn unit_width(&self) -> usize {
+        self.names()
+            .iter()
+            .map(|name| name.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn display_width(&self) -> usize {
+        Self::VALUE_WIDTH + 1 + self.unit_width()
+    }
+
+    /// `Bytes` or `Bits`, e.g. for axis titles
+    pub fn name(&self) -> &'static str {
+        if self.bits {
+            "Bits"
+        } else {
+            "Bytes"
+        }
+    }
+
+    /// Formats `bytes` with 3 significant digits, padded to `display_width`, e.g. `1.23 KiB`,
+    /// `12.3 KiB` or ` 123 KiB`.
+    pub fn format(&self, bytes: f64) -> String {
+        let names = self.names();
+        let mut v = bytes / self.unit_bytes();
+        let mut i = 0;
+        // `999.5` and above would be rounded to 4 digits, e.g. `1023 B`, so `0.98 KiB` is shown
+        // instead
+        while v >= 999.5 && i < names.len() - 1 {
+            v /= self.base();
+            i += 1;
+        }
+        let value = if v < 9.995 {
+            format!("{:.2}", v)
+        } else if v < 99.95 {
+            format!("{:.1}", v)
+        } else {
+            format!("{:>w$.0}", v, w = Self::VALUE_WIDTH)
+        };
+        format!("{} {:<w$}", value, names[i], w = self.unit_width())
+    }
+
+    /// Formats `bytes` per second without padding, e.g. `1.23 KiB/s`.
+    pub fn format_rate(&self, bytes: f64) -> String {
+        let s = self.format(bytes);
+        format!("{}/s", s.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
 }
 
 impl<T: Num + ToPrimitive> NumBytes<T> {
-    const UNITS: [&'static str; 9] = ["B", "KiB", "MiB", "GiB", "TiB", "Pib", "Eib", "ZiB", "YiB"];
-    const VALUE_WIDTH: usize = 4;
-    pub const DISPLAY_WIDTH: usize = Self::VALUE_WIDTH + 1 + 3;
+    /// Width of the `Display` output in the current units
+    pub fn display_width() -> usize {
+        units().display_width()
+    }
 
     pub fn pretty(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut v: f64 = self.0.to_f64().ok_or(fmt::Error)?;
-        for u in Self::UNITS.iter() {
-            if v < 1024.0 {
-                let ret = match integer_len(v) {
-                    // with decimal points
-                    l @ (1...2) => write!(
-                        f,
-                        "{:l$.r$} {:<3}",
-                        v,
-                        u,
-                        l = l,
-                        r = Self::VALUE_WIDTH - l - 1
-                    ),
-                    // without decimal points
-                    3...4 => write!(f, "{:>4} {:<3}", v.trunc(), u),
-                    _ => Err(fmt::Error),
-                };
-                return ret;
-            }
-            v = v / 1024.0;
-        }
-        Err(fmt::Error)
+        let v: f64 = self.0.to_f64().ok_or(fmt::Error)?;
+        write!(f, "{}", units().format(v))
     }
 
     pub fn to_f64(&self) -> Option<f64> {
@@ -64,6 +150,12 @@ impl<T: Num + ToPrimitive> NumBytes<T> {
     }
 }
 
+impl<T: Num + Copy> NumBytes<T> {
+    pub fn value(&self) -> T {
+        self.0
+    }
+}
+
 impl<T: Num + ToPrimitive> fmt::Display for NumBytes<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.pretty(f)
@@ -76,14 +168,62 @@ impl<T: Num + ToPrimitive> fmt::Debug for NumBytes<T> {
     }
 }
 
+/// Splits `s` into a number and its unit, e.g. `"80 MiB"` into `(80.0, "MiB")`.
+fn split_unit(s: &str) -> Result<(f64, &str)> {
+    let s = s.trim();
+    let pos = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    match s[..pos].parse::<f64>() {
+        Ok(v) => Ok((v, s[pos..].trim())),
+        Err(_) => Err(Error::ParseError(format!("invalid number `{}`", s))),
+    }
+}
+
+/// Parses an amount of bytes such as `512`, `1.5 KiB`, `80MiB/s` or `1GB`.
+///
+/// `K`, `M`, `G`, ... and `KiB`, `MiB`, `GiB`, ... are binary (IEC) units and `KB`, `MB`, `GB`,
+/// ... are decimal (SI) units. A trailing `/s` is ignored.
+pub fn parse_bytes(s: &str) -> Result<f64> {
+    let (v, unit) = split_unit(s.trim().trim_end_matches("/s"))?;
+    let (prefix, base) = if let Some(prefix) = unit.strip_suffix("iB") {
+        (prefix, 1024.0)
+    } else if unit.len() == 2 && unit.ends_with('B') {
+        (&unit[..1], 1000.0)
+    } else {
+        (unit.trim_end_matches('B'), 1024.0)
+    };
+    let exp = match prefix {
+        "" => 0,
+        "K" | "k" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        "E" => 6,
+        _ => return Err(Error::ParseError(format!("invalid unit in `{}`", s))),
+    };
+    Ok(v * f64::powi(base, exp))
+}
+
+/// Parses a duration such as `500ms`, `30s`, `5m` or `1h`. A number without unit is seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let (v, unit) = split_unit(s)?;
+    let secs = match unit {
+        "ms" => v / 1000.0,
+        "" | "s" => v,
+        "m" => v * 60.0,
+        "h" => v * 60.0 * 60.0,
+        "d" => v * 60.0 * 60.0 * 24.0,
+        _ => return Err(Error::ParseError(format!("invalid duration `{}`", s))),
+    };
+    Ok(Duration::from_millis((secs * 1000.0).round() as u64))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_integer_len() {
-        assert_eq!(integer_len(573.0458984375), 3);
-    }
+    use proptest::prelude::*;
 
     #[test]
     fn test_pretty() {
@@ -91,8 +231,84 @@ mod tests {
             format!("{}", NumBytes::from((1.234 * 1024.0) as u64)),
             "1.23 KiB"
         );
-        assert_eq!(format!("{}", NumBytes::from(1023)), "1023 B  ");
+        assert_eq!(format!("{}", NumBytes::from(1023)), "1.00 KiB");
+        assert_eq!(format!("{}", NumBytes::from(999)), " 999 B  ");
         assert_eq!(format!("{}", NumBytes::from(123)), " 123 B  ");
         assert_eq!(format!("{}", NumBytes::from(600_882_176)), " 573 MiB");
+        assert_eq!(format!("{}", NumBytes::from(1_u64 << 50)), "1.00 PiB");
+    }
+
+    #[test]
+    fn test_units() {
+        let si = Units {
+            bits: false,
+            si: true,
+        };
+        let bits = Units {
+            bits: true,
+            si: false,
+        };
+        let si_bits = Units {
+            bits: true,
+            si: true,
+        };
+        assert_eq!(si.format(1000.0), "1.00 kB");
+        assert_eq!(si.format(999_499.0), " 999 kB");
+        assert_eq!(bits.format(128.0), "1.00 Kibit");
+        assert_eq!(si_bits.format(125e6), "1.00 Gbit");
+        assert_eq!(si_bits.format(12.0), "96.0 bit ");
+        assert_eq!(si_bits.format_rate(12.0), "96.0 bit/s");
+        assert_eq!(Units::default().format(9.996 * 1024.0), "10.0 KiB");
+        assert_eq!(Units::default().format(99.96 * 1024.0), " 100 KiB");
+        assert_eq!(Units::default().format(1e30), "827181 YiB");
+    }
+
+    fn all_units() -> impl Strategy<Value = Units> {
+        (any::<bool>(), any::<bool>()).prop_map(|(bits, si)| Units { bits, si })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_format_width(units in all_units(), bytes in 0_u64..(1 << 63)) {
+            prop_assert_eq!(units.format(bytes as f64).len(), units.display_width());
+        }
+
+        #[test]
+        fn prop_format_value(units in all_units(), bytes in 0_u64..(1 << 63)) {
+            let s = units.format(bytes as f64);
+            let (value, unit) = s.split_at(Units::VALUE_WIDTH);
+            let decimals = value.split('.').nth(1).map_or(0, str::len) as i32;
+            let value: f64 = value.trim().parse().unwrap();
+            let exp = units.names().iter().position(|name| *name == unit.trim()).unwrap();
+            let scale = units.base().powi(exp as i32) * units.unit_bytes();
+            // rounded to the last digit shown
+            let error = (value * scale - bytes as f64).abs();
+            prop_assert!(error <= 0.5 * 10_f64.powi(-decimals) * scale * (1.0 + 1e-9));
+            prop_assert!(value < 999.5);
+            prop_assert!(exp == 0 || value >= 0.97);
+        }
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("0").unwrap(), 0.0);
+        assert_eq!(parse_bytes("512").unwrap(), 512.0);
+        assert_eq!(parse_bytes("1.5 KiB").unwrap(), 1536.0);
+        assert_eq!(parse_bytes("80MiB/s").unwrap(), 80.0 * 1024.0 * 1024.0);
+        assert_eq!(parse_bytes("2M").unwrap(), 2.0 * 1024.0 * 1024.0);
+        assert_eq!(parse_bytes("1GB").unwrap(), 1e9);
+        assert_eq!(parse_bytes("10 kB/s").unwrap(), 1e4);
+        assert!(parse_bytes("MiB").is_err());
+        assert!(parse_bytes("1 XB").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(5400));
+        assert!(parse_duration("5 parsecs").is_err());
     }
 }
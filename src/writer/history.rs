@@ -0,0 +1,106 @@
+//! Ring buffers of recent stats shared by the TUI and sparkline writers.
+
+use slice_deque::{sdeq, SliceDeque};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{InterfaceInfo, InterfaceStats};
+
+/// Range of the values of `History`, log2 of 1 KiB -- 1 GiB, as the TUI shows them by default.
+pub const Y_WINDOW: [f64; 2] = [10.0, 30.0];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricHistory {
+    data: Vec<SliceDeque<(f64, f64)>>,
+}
+
+impl<'a> MetricHistory {
+    pub fn empty(info: &'a InterfaceInfo, n_histories: usize) -> MetricHistory {
+        MetricHistory {
+            data: vec![sdeq![(0.0,0.0); n_histories]; info.0.len()],
+        }
+    }
+
+    pub fn get_data(&'a self, index: usize) -> &'a [(f64, f64)] {
+        &self.data[index]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Metric {
+    Rx,
+    Tx,
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Metric::Rx => write!(f, "rx"),
+            Metric::Tx => write!(f, "tx"),
+        }
+    }
+}
+
+impl Metric {
+    pub fn variants() -> [Metric; 2] {
+        [Metric::Rx, Metric::Tx]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct History {
+    pub current: i32,
+    data: HashMap<Metric, MetricHistory>,
+}
+
+impl<'a> History {
+    pub fn empty(info: &'a InterfaceInfo, n_histories: usize) -> History {
+        History {
+            current: 0,
+            data: Metric::variants()
+                .iter()
+                .map(|&metric| (metric, MetricHistory::empty(info, n_histories)))
+                .collect(),
+        }
+    }
+
+    pub fn get_data(&'a self, metric: Metric, index: usize) -> &'a [(f64, f64)] {
+        self.data
+            .get(&metric)
+            .expect("Could not find a metric.")
+            .get_data(index)
+    }
+
+    fn pop_front(&mut self) {
+        for (_, h) in self.data.iter_mut() {
+            for d in h.data.iter_mut() {
+                d.pop_front();
+            }
+        }
+    }
+
+    fn push_back(&mut self, diff: InterfaceStats) {
+        self.current += 1;
+        for (metric, h) in self.data.iter_mut() {
+            for (i, d) in h.data.iter_mut().enumerate() {
+                let num = match &diff.0[i] {
+                    Some(stat) => match metric {
+                        Metric::Rx => stat.rx,
+                        Metric::Tx => stat.tx,
+                    },
+                    None => 0.into(),
+                };
+                let val = match num.to_f64() {
+                    Some(val) => val.max(1.0).log2(),
+                    None => 0.0,
+                };
+                d.push_back((f64::from(self.current), val));
+            }
+        }
+    }
+
+    pub fn push_back_pop_front(&mut self, diff: InterfaceStats) {
+        self.pop_front();
+        self.push_back(diff);
+    }
+}
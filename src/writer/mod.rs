@@ -1,9 +1,59 @@
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+
+use crate::alert::FiringAlert;
 use crate::{InterfaceStats, Result};
 
+mod history;
+pub mod out_record;
 pub mod out_simple;
+pub mod out_spark;
+pub mod out_status;
 pub mod out_tui;
+pub mod panel;
+pub mod restore;
+pub mod theme;
+pub mod tui_backend;
 
 pub trait Write {
     fn setup_shutdown(&mut self, callback: Box<dyn Fn() + 'static + Send>) -> Result<()>;
     fn update(&mut self, stats: InterfaceStats) -> Result<()>;
+
+    /// Called before `update` on every tick with the alerts currently firing.
+    fn update_alerts(&mut self, _alerts: &[FiringAlert]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called before `update` on every tick with the time covered by the stats since the
+    /// previous update. It spans several reader intervals when ticks were dropped for a writer
+    /// falling behind, so writers showing rates divide the differences by it.
+    fn update_interval(&mut self, _interval: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called once when the monitor stops.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+type Callback = Box<dyn Fn() + 'static + Send>;
+
+static CTRLC_CALLBACKS: Mutex<Vec<Callback>> = Mutex::new(Vec::new());
+static CTRLC_HANDLER: Once = Once::new();
+
+/// Calls `callback` on Ctrl+C.
+///
+/// `ctrlc::set_handler` can only be called once per process, while several writers may run at
+/// the same time.
+pub fn on_ctrlc(callback: Callback) {
+    CTRLC_CALLBACKS.lock().unwrap().push(callback);
+    CTRLC_HANDLER.call_once(|| {
+        ctrlc::set_handler(|| {
+            for callback in CTRLC_CALLBACKS.lock().unwrap().iter() {
+                (*callback)();
+            }
+        })
+        .expect("Failed to set Ctrl+C handler");
+    });
 }
@@ -0,0 +1,99 @@
+//! Record the raw stats to a file for `mone replay` and `mone report`.
+
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::record::{write_line, Header, Sample};
+use crate::writer::{on_ctrlc, Write};
+use crate::{InterfaceInfo, InterfaceStats, Result};
+
+pub struct RecordWriter<T: io::Write> {
+    writer: T,
+    /// Time of the next sample since the first one, summed from the intervals of the updates
+    /// so that a writer falling behind still records when the stats were read
+    time: Duration,
+}
+
+impl<T: io::Write> RecordWriter<T> {
+    /// Writes the header and `initial_stats` as the first sample.
+    pub fn new(
+        mut writer: T,
+        info: &InterfaceInfo,
+        initial_stats: InterfaceStats,
+    ) -> Result<RecordWriter<T>> {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        write_line(&mut writer, &Header::new(info, started_at))?;
+        write_line(&mut writer, &Sample::new(0.0, &initial_stats))?;
+        Ok(RecordWriter {
+            writer,
+            time: Duration::from_secs(0),
+        })
+    }
+}
+
+impl<T: io::Write> Write for RecordWriter<T> {
+    fn setup_shutdown(&mut self, callback: Box<dyn Fn() + 'static + Send>) -> Result<()> {
+        on_ctrlc(callback);
+        Ok(())
+    }
+
+    fn update_interval(&mut self, interval: Duration) -> Result<()> {
+        self.time += interval;
+        Ok(())
+    }
+
+    fn update(&mut self, stats: InterfaceStats) -> Result<()> {
+        write_line(
+            &mut self.writer,
+            &Sample::new(self.time.as_secs_f64(), &stats),
+        )?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Recording;
+    use crate::utils::NumBytes;
+    use crate::{InterfaceInfoItem, InterfaceStat};
+
+    #[test]
+    fn test_record() {
+        let info = InterfaceInfo(vec![
+            InterfaceInfoItem::new("lo"),
+            InterfaceInfoItem::new("eth0"),
+        ]);
+        let stats = |rx: u64| {
+            InterfaceStats(vec![
+                None,
+                Some(InterfaceStat {
+                    rx: NumBytes::from(rx),
+                    tx: NumBytes::from(2 * rx),
+                    drops: 1,
+                }),
+            ])
+        };
+        let mut writer = RecordWriter::new(Vec::new(), &info, stats(0)).unwrap();
+        writer.update_interval(Duration::from_secs(1)).unwrap();
+        writer.update(stats(1024)).unwrap();
+        writer.update_interval(Duration::from_secs(2)).unwrap();
+        writer.update(stats(4096)).unwrap();
+
+        let recording = Recording::from_reader(&writer.writer[..]).unwrap();
+        assert_eq!(recording.header.info(), info);
+        assert_eq!(recording.samples.len(), 3);
+        assert_eq!(recording.samples[0].time, 0.0);
+        assert_eq!(recording.samples[1].stats(), stats(1024));
+        assert_eq!(recording.samples[2].time, 3.0);
+    }
+}
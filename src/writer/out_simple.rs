@@ -1,38 +1,118 @@
-use ctrlc;
 use std::io;
+use std::time::Duration;
 
-use crate::writer::Write;
-use crate::{InterfaceInfo, InterfaceStats, Result};
+use crate::writer::{on_ctrlc, Write};
+use crate::{InterfaceInfo, InterfaceStats, Result, Utilization};
 
 pub struct SimpleWriter<T: io::Write> {
     writer: T,
+    info: InterfaceInfo,
+    /// Utilization in percent above which a link is marked with `!`
+    threshold: f64,
     prev_stats: InterfaceStats,
+    /// Time covered by the stats of the next update since `prev_stats`
+    interval: Duration,
 }
 
 impl<T: io::Write> SimpleWriter<T> {
     pub fn new(
         mut writer: T,
+        threshold: f64,
         info: &InterfaceInfo,
         initial_stats: InterfaceStats,
     ) -> Result<SimpleWriter<T>> {
         writeln!(writer, "{}", info)?;
         Ok(SimpleWriter {
             writer,
+            info: info.clone(),
+            threshold,
             prev_stats: initial_stats,
+            interval: Duration::from_secs(1),
         })
     }
+
+    /// Formats the stats, each followed by the utilization if the link speed is known.
+    fn format(&self, diff: &InterfaceStats) -> String {
+        let columns: Vec<String> = diff
+            .0
+            .iter()
+            .zip(self.info.0.iter())
+            .map(|(stat, item)| {
+                let column = InterfaceStats::format_stat(stat.as_ref());
+                match stat
+                    .as_ref()
+                    .and_then(|stat| Utilization::new(stat, &item.link))
+                {
+                    Some(utilization) if utilization.max() > self.threshold => {
+                        format!("{} {}!", column, utilization)
+                    }
+                    Some(utilization) => format!("{} {}", column, utilization),
+                    None => column,
+                }
+            })
+            .collect();
+        columns.join(InterfaceStats::DELIMITER)
+    }
 }
 
 impl<T: io::Write> Write for SimpleWriter<T> {
     fn setup_shutdown(&mut self, callback: Box<dyn Fn() + 'static + Send>) -> Result<()> {
-        ctrlc::set_handler(move || (*callback)()).expect("Failed to set Ctrl+C handler");
+        on_ctrlc(callback);
+        Ok(())
+    }
+
+    fn update_interval(&mut self, interval: Duration) -> Result<()> {
+        self.interval = interval;
         Ok(())
     }
 
     fn update(&mut self, stats: InterfaceStats) -> Result<()> {
-        let diff = &stats - &self.prev_stats;
+        let diff = (&stats - &self.prev_stats).per_sec(self.interval);
         self.prev_stats = stats;
-        writeln!(self.writer, "{}", diff).unwrap_or(());
+        let line = self.format(&diff);
+        writeln!(self.writer, "{}", line)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::NumBytes;
+    use crate::{InterfaceInfoItem, InterfaceStat};
+
+    fn stats(rx: u64) -> InterfaceStats {
+        let stat = InterfaceStat {
+            rx: NumBytes::from(rx),
+            tx: NumBytes::from(0),
+            drops: 0,
+        };
+        InterfaceStats(vec![Some(stat.clone()), Some(stat)])
+    }
+
+    #[test]
+    fn test_utilization() {
+        let mut eth0 = InterfaceInfoItem::new("eth0");
+        eth0.link.speed = Some(100);
+        let info = InterfaceInfo(vec![InterfaceInfoItem::new("lo"), eth0]);
+        let mut writer = SimpleWriter::new(vec![], 80.0, &info, stats(0)).unwrap();
+        writer.update(stats(1_250_000)).unwrap();
+        writer.update(stats(12_500_000)).unwrap();
+
+        let output = String::from_utf8(writer.writer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[1],
+            "1.19 MiB 0.00 B   | 1.19 MiB 0.00 B    10.0%   0.0%"
+        );
+        assert_eq!(
+            lines[2],
+            "10.7 MiB 0.00 B   | 10.7 MiB 0.00 B    90.0%   0.0%!"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Output compact sparklines of recent rx / tx, redrawn in place without the alternate screen.
+
+use std::io;
+use std::time::Duration;
+
+use crate::utils::NumBytes;
+use crate::writer::history::{History, Metric, Y_WINDOW};
+use crate::writer::{on_ctrlc, Write};
+use crate::{InterfaceInfo, InterfaceStats, Result};
+
+pub struct SparkWriter<T: io::Write> {
+    writer: T,
+    info: InterfaceInfo,
+    prev_stats: InterfaceStats,
+    /// Time covered by the stats of the next update since `prev_stats`
+    interval: Duration,
+    last_diff: InterfaceStats,
+    history: History,
+    drawn: bool,
+}
+
+impl<T: io::Write> SparkWriter<T> {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    /// Creates a writer showing the last `width` stats of each interface.
+    pub fn new(
+        writer: T,
+        width: usize,
+        info: &InterfaceInfo,
+        initial_stats: InterfaceStats,
+    ) -> Result<SparkWriter<T>> {
+        Ok(SparkWriter {
+            writer,
+            info: info.clone(),
+            last_diff: InterfaceStats::empty(info.0.len()),
+            prev_stats: initial_stats,
+            interval: Duration::from_secs(1),
+            history: History::empty(info, width),
+            drawn: false,
+        })
+    }
+
+    /// Maps a value of `History` (log2 of bytes) to a block character.
+    fn block(val: f64) -> char {
+        if val <= 0.0 {
+            return ' ';
+        }
+        let [lo, hi] = Y_WINDOW;
+        let level = ((val - lo) / (hi - lo) * Self::BLOCKS.len() as f64).floor();
+        let level = level.max(0.0).min((Self::BLOCKS.len() - 1) as f64);
+        Self::BLOCKS[level as usize]
+    }
+
+    fn sparkline(&self, metric: Metric, index: usize) -> String {
+        self.history
+            .get_data(metric, index)
+            .iter()
+            .map(|&(_, val)| Self::block(val))
+            .collect()
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let name_width = self.info.0.iter().map(|item| item.name.len()).max();
+        self.info
+            .0
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let (rx, tx) = match &self.last_diff.0[i] {
+                    Some(stat) => (format!("{}", stat.rx), format!("{}", stat.tx)),
+                    None => ("None".to_string(), "None".to_string()),
+                };
+                format!(
+                    "{:<nw$} rx {} {:<w$} tx {} {:<w$}",
+                    item.name,
+                    self.sparkline(Metric::Rx, i),
+                    rx,
+                    self.sparkline(Metric::Tx, i),
+                    tx,
+                    nw = name_width.unwrap_or(0),
+                    w = NumBytes::<u64>::display_width()
+                )
+            })
+            .collect()
+    }
+
+    fn draw(&mut self) -> io::Result<()> {
+        let lines = self.lines();
+        if self.drawn && !lines.is_empty() {
+            // move the cursor back to the first line drawn last time
+            write!(self.writer, "\x1b[{}A", lines.len())?;
+        }
+        for line in lines {
+            writeln!(self.writer, "\r\x1b[2K{}", line)?;
+        }
+        self.drawn = true;
+        self.writer.flush()
+    }
+}
+
+impl<T: io::Write> Write for SparkWriter<T> {
+    fn setup_shutdown(&mut self, callback: Box<dyn Fn() + 'static + Send>) -> Result<()> {
+        on_ctrlc(callback);
+        Ok(())
+    }
+
+    fn update_interval(&mut self, interval: Duration) -> Result<()> {
+        self.interval = interval;
+        Ok(())
+    }
+
+    fn update(&mut self, stats: InterfaceStats) -> Result<()> {
+        let diff = (&stats - &self.prev_stats).per_sec(self.interval);
+        self.prev_stats = stats;
+        self.history.push_back_pop_front(diff.clone());
+        self.last_diff = diff;
+        self.draw()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::info;
+    use crate::InterfaceStat;
+
+    fn stats(lo: u64, eth0: u64) -> InterfaceStats {
+        InterfaceStats(vec![
+            Some(InterfaceStat {
+                rx: NumBytes::from(lo),
+                tx: NumBytes::from(lo),
+                drops: 0,
+            }),
+            Some(InterfaceStat {
+                rx: NumBytes::from(eth0),
+                tx: NumBytes::from(0),
+                drops: 0,
+            }),
+        ])
+    }
+
+    #[test]
+    fn test_block() {
+        assert_eq!(SparkWriter::<Vec<u8>>::block(0.0), ' ');
+        assert_eq!(SparkWriter::<Vec<u8>>::block(5.0), '▁');
+        assert_eq!(SparkWriter::<Vec<u8>>::block(20.0), '▅');
+        assert_eq!(SparkWriter::<Vec<u8>>::block(40.0), '█');
+    }
+
+    #[test]
+    fn test_draw_in_place() {
+        let mut writer = SparkWriter::new(Vec::new(), 4, &info(), stats(0, 0)).unwrap();
+        writer.update(stats(1024, 1 << 20)).unwrap();
+        writer.update(stats(2048, 1 << 30)).unwrap();
+        let output = String::from_utf8(writer.writer).unwrap();
+        let expected = [
+            "\r\x1b[2Klo   rx    ▁ 1.00 KiB tx    ▁ 1.00 KiB\n",
+            "\r\x1b[2Keth0 rx    ▅ 1.00 MiB tx      0.00 B  \n",
+            "\x1b[2A",
+            "\r\x1b[2Klo   rx   ▁▁ 1.00 KiB tx   ▁▁ 1.00 KiB\n",
+            "\r\x1b[2Keth0 rx   ▅█ 1.00 GiB tx      0.00 B  \n",
+        ]
+        .concat();
+        assert_eq!(output, expected);
+    }
+}
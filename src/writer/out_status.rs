@@ -0,0 +1,291 @@
+//! Output one compact line per tick for status bars such as tmux, i3bar and waybar.
+
+use serde_json::json;
+use std::io;
+use std::time::Duration;
+
+use crate::utils::NumBytes;
+use crate::writer::{on_ctrlc, Write};
+use crate::{InterfaceInfo, InterfaceStats, Result, StatusMode};
+
+/// Part of the format of the status line.
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Name,
+    Rx,
+    Tx,
+}
+
+/// Splits `format` at the placeholders, so that they are only substituted in the format and
+/// not in the interface names substituted before them.
+fn parse_format(format: &str) -> Vec<Segment> {
+    let mut segments = vec![];
+    let mut text = String::new();
+    let mut rest = format;
+    while !rest.is_empty() {
+        let placeholder = [
+            ("{name}", Segment::Name),
+            ("{rx}", Segment::Rx),
+            ("{tx}", Segment::Tx),
+        ]
+        .iter()
+        .find(|(pattern, _)| rest.starts_with(pattern))
+        .cloned();
+        match placeholder {
+            Some((pattern, segment)) => {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(segment);
+                rest = &rest[pattern.len()..];
+            }
+            None => {
+                let c = rest.chars().next().expect("rest is not empty");
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    segments
+}
+
+pub struct StatusWriter<T: io::Write> {
+    writer: T,
+    info: InterfaceInfo,
+    format: Vec<Segment>,
+    mode: StatusMode,
+    prev_stats: InterfaceStats,
+    /// Time covered by the stats of the next update since `prev_stats`
+    interval: Duration,
+}
+
+/// Formats `num` without padding, e.g. `1.23KiB`.
+fn compact(num: NumBytes<u64>) -> String {
+    format!("{}", num).split_whitespace().collect()
+}
+
+impl<T: io::Write> StatusWriter<T> {
+    pub const DEFAULT_FORMAT: &'static str = "{name} ↓{rx} ↑{tx}";
+
+    /// Creates a writer which prints `format` for each interface.
+    ///
+    /// `{name}`, `{rx}` and `{tx}` in `format` are replaced with the interface name and the
+    /// received and transmitted bytes per second.
+    pub fn new(
+        mut writer: T,
+        format: &str,
+        mode: StatusMode,
+        info: &InterfaceInfo,
+        initial_stats: InterfaceStats,
+    ) -> Result<StatusWriter<T>> {
+        if mode == StatusMode::i3bar {
+            writeln!(writer, "{}", json!({ "version": 1 }))?;
+            writeln!(writer, "[")?;
+            writer.flush()?;
+        }
+        Ok(StatusWriter {
+            writer,
+            info: info.clone(),
+            format: parse_format(format),
+            mode,
+            prev_stats: initial_stats,
+            interval: Duration::from_secs(1),
+        })
+    }
+
+    fn format_item(&self, name: &str, rx: NumBytes<u64>, tx: NumBytes<u64>) -> String {
+        self.format
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Name => name.to_string(),
+                Segment::Rx => compact(rx),
+                Segment::Tx => compact(tx),
+            })
+            .collect()
+    }
+
+    /// Returns the status line, or `None` if no interface has stats.
+    fn text(&self, diff: &InterfaceStats) -> Option<String> {
+        let items: Vec<String> = self
+            .info
+            .0
+            .iter()
+            .zip(diff.0.iter())
+            .filter_map(|(item, stat)| {
+                stat.as_ref()
+                    .map(|stat| self.format_item(&item.name, stat.rx, stat.tx))
+            })
+            .collect();
+        if items.is_empty() {
+            None
+        } else {
+            Some(items.join(" "))
+        }
+    }
+
+    fn tooltip(&self, diff: &InterfaceStats) -> String {
+        self.info
+            .0
+            .iter()
+            .zip(diff.0.iter())
+            .map(|(item, stat)| match stat {
+                Some(stat) => format!(
+                    "{}: rx {}/s, tx {}/s",
+                    item.name,
+                    compact(stat.rx),
+                    compact(stat.tx)
+                ),
+                None => format!("{}: unavailable", item.name),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn class(diff: &InterfaceStats) -> &'static str {
+        let mut stats = diff.0.iter().flatten().peekable();
+        if stats.peek().is_none() {
+            "unavailable"
+        } else if stats.any(|stat| stat.rx.to_f64() > Some(0.0) || stat.tx.to_f64() > Some(0.0)) {
+            "active"
+        } else {
+            "idle"
+        }
+    }
+
+    fn write_line(&mut self, diff: &InterfaceStats) -> io::Result<()> {
+        let text = self.text(diff).unwrap_or_default();
+        match self.mode {
+            StatusMode::plain => writeln!(self.writer, "{}", text)?,
+            StatusMode::i3bar => writeln!(
+                self.writer,
+                "{},",
+                json!([{ "name": "mone", "full_text": text }])
+            )?,
+            StatusMode::waybar => writeln!(
+                self.writer,
+                "{}",
+                json!({
+                    "text": text,
+                    "tooltip": self.tooltip(diff),
+                    "class": Self::class(diff),
+                })
+            )?,
+        }
+        self.writer.flush()
+    }
+}
+
+impl<T: io::Write> Write for StatusWriter<T> {
+    fn setup_shutdown(&mut self, callback: Box<dyn Fn() + 'static + Send>) -> Result<()> {
+        on_ctrlc(callback);
+        Ok(())
+    }
+
+    fn update_interval(&mut self, interval: Duration) -> Result<()> {
+        self.interval = interval;
+        Ok(())
+    }
+
+    fn update(&mut self, stats: InterfaceStats) -> Result<()> {
+        let diff = (&stats - &self.prev_stats).per_sec(self.interval);
+        self.prev_stats = stats;
+        self.write_line(&diff)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::info;
+    use crate::InterfaceStat;
+
+    fn stats(rx: u64, tx: u64) -> InterfaceStats {
+        InterfaceStats(vec![
+            None,
+            Some(InterfaceStat {
+                rx: NumBytes::from(rx),
+                tx: NumBytes::from(tx),
+                drops: 0,
+            }),
+        ])
+    }
+
+    fn output(mode: StatusMode, format: &str) -> Vec<String> {
+        let mut writer = StatusWriter::new(Vec::new(), format, mode, &info(), stats(0, 0)).unwrap();
+        writer.update(stats(1_258_291, 307_200)).unwrap();
+        String::from_utf8(writer.writer)
+            .unwrap()
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            parse_format("{name} ↓{rx} {{tx}}"),
+            vec![
+                Segment::Name,
+                Segment::Text(" ↓".to_string()),
+                Segment::Rx,
+                Segment::Text(" {".to_string()),
+                Segment::Tx,
+                Segment::Text("}".to_string()),
+            ]
+        );
+        assert!(parse_format("").is_empty());
+    }
+
+    #[test]
+    fn test_placeholder_in_name() {
+        let writer = StatusWriter::new(
+            Vec::new(),
+            "{name}={rx}",
+            StatusMode::plain,
+            &info(),
+            stats(0, 0),
+        )
+        .unwrap();
+        let item = writer.format_item("{tx}", NumBytes::from(1024), NumBytes::from(0));
+        assert_eq!(item, "{tx}=1.00KiB");
+    }
+
+    #[test]
+    fn test_plain() {
+        let lines = output(StatusMode::plain, StatusWriter::<Vec<u8>>::DEFAULT_FORMAT);
+        assert_eq!(lines, vec!["eth0 ↓1.20MiB ↑300KiB"]);
+        let lines = output(StatusMode::plain, "[{name}] {rx}/{tx}");
+        assert_eq!(lines, vec!["[eth0] 1.20MiB/300KiB"]);
+    }
+
+    #[test]
+    fn test_i3bar() {
+        let lines = output(StatusMode::i3bar, "{rx}");
+        assert_eq!(lines[0], r#"{"version":1}"#);
+        assert_eq!(lines[1], "[");
+        assert_eq!(lines[2], r#"[{"full_text":"1.20MiB","name":"mone"}],"#);
+    }
+
+    #[test]
+    fn test_waybar() {
+        let lines = output(StatusMode::waybar, "{tx}");
+        let value: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(value["text"], "300KiB");
+        assert_eq!(
+            value["tooltip"],
+            "lo: unavailable\neth0: rx 1.20MiB/s, tx 300KiB/s"
+        );
+        assert_eq!(value["class"], "active");
+    }
+}
@@ -1,141 +1,96 @@
-use slice_deque::{sdeq, SliceDeque};
-use std::collections::HashMap;
-use std::sync::Mutex;
-use std::{fmt, io, thread};
-use termion::event::Key;
-use termion::input::MouseTerminal;
-use termion::input::TermRead;
-use termion::raw::{IntoRawMode, RawTerminal};
-use termion::screen::AlternateScreen;
-use tui::backend::TermionBackend;
-use tui::layout::{Constraint, Direction, Layout};
-use tui::style::{Color, Modifier, Style};
-use tui::widgets::{Axis, Block, Borders, Chart, Dataset, Marker, Widget};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tui::backend::Backend;
+use tui::buffer::Buffer;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::Style;
+use tui::widgets::{Axis, Block, Borders, Chart, Dataset, Marker, Paragraph, Text, Widget};
 use tui::Terminal;
 
-use crate::utils::NumBytes;
-use crate::writer::Write;
-use crate::{InterfaceInfo, InterfaceStats, Opt, Result};
+use crate::alert::{AlertMetric, FiringAlert};
+use crate::list::{flag_names, format_link};
+use crate::utils::Units;
+use crate::writer::history::{History, Metric};
+use crate::writer::panel::{Panels, View};
+use crate::writer::tui_backend::{DefaultTuiBackend, Key, Keys, TuiBackend};
+use crate::writer::{restore, theme::Theme, Write};
+use crate::{Error, InterfaceInfo, InterfaceStats, LiveOpt, Result, Utilization};
 
-#[derive(Clone, Debug, PartialEq)]
-struct MetricHistory {
-    data: Vec<SliceDeque<(f64, f64)>>,
-}
-
-impl<'a> MetricHistory {
-    fn empty(info: &'a InterfaceInfo, n_histories: usize) -> MetricHistory {
-        MetricHistory {
-            data: vec![sdeq![(0.0,0.0); n_histories]; info.0.len()],
-        }
-    }
-
-    fn get_data(&'a self, index: usize) -> &'a [(f64, f64)] {
-        &self.data[index]
-    }
+pub struct TuiWriter<B: Backend = DefaultTuiBackend> {
+    terminal: Mutex<Terminal<B>>,
+    info: InterfaceInfo,
+    n_histories: usize,
+    input_thread: Option<thread::JoinHandle<()>>,
+    /// Cleared on drop to stop the input thread
+    input_running: Arc<AtomicBool>,
+    /// Whether the terminal state was saved by `restore::install`
+    restore_installed: bool,
+    prev_stats: InterfaceStats,
+    /// Time covered by the stats of the next update since `prev_stats`
+    interval: Duration,
+    /// Difference of the stats of the last second
+    last_diff: InterfaceStats,
+    history: History,
+    theme: Theme,
+    units: Units,
+    alerts: Vec<FiringAlert>,
+    /// Utilization of the last second of the links with a known speed
+    utilization: Vec<Option<Utilization>>,
+    /// Utilization in percent above which a link is highlighted
+    threshold: f64,
+    /// Toggled with `i` by the input thread
+    show_details: Arc<AtomicBool>,
+    /// Views below the charts, one of them selected by the input thread
+    panels: Panels<B>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-enum Metric {
-    Rx,
-    Tx,
-}
+/// Resets the cells of an area, so that a popup hides what is drawn below it.
+struct Clear;
 
-impl fmt::Display for Metric {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Metric::Rx => write!(f, "rx"),
-            Metric::Tx => write!(f, "tx"),
+impl Widget for Clear {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                buf.get_mut(x, y).reset();
+            }
         }
     }
 }
 
-impl Metric {
-    fn variants() -> [Metric; 2] {
-        [Metric::Rx, Metric::Tx]
-    }
+/// Returns a `width` x `height` area in the center of `area`, shrunk to fit in it.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
 }
 
-#[derive(Clone, Debug, PartialEq)]
-struct History {
-    current: i32,
-    data: HashMap<Metric, MetricHistory>,
-}
-
-impl<'a> History {
-    fn empty(info: &'a InterfaceInfo, n_histories: usize) -> History {
-        History {
-            current: 0,
-            data: Metric::variants()
-                .iter()
-                .map(|&metric| (metric, MetricHistory::empty(info, n_histories)))
-                .collect(),
+impl<B: TuiBackend> TuiWriter<B> {
+    /// Creates a writer drawing on stdout in raw mode on the alternate screen.
+    ///
+    /// Returns an error if stdout is not a TTY. The terminal is restored even if mone panics or
+    /// is killed by `SIGTERM` or `SIGHUP`.
+    pub fn new(opt: &LiveOpt, info: &InterfaceInfo, initial_stats: InterfaceStats) -> Result<Self> {
+        if !nix::unistd::isatty(libc::STDOUT_FILENO)? {
+            return Err(Error::Other(
+                "TUI writer requires stdout to be a TTY. Try `--writer simple`.",
+            ));
         }
-    }
-
-    fn get_data(&'a self, metric: Metric, index: usize) -> &'a [(f64, f64)] {
-        self.data
-            .get(&metric)
-            .expect("Could not find a metric.")
-            .get_data(index)
-    }
-
-    fn pop_front(&mut self) {
-        for (_, h) in self.data.iter_mut() {
-            for d in h.data.iter_mut() {
-                d.pop_front();
-            }
-        }
-    }
-
-    fn push_back(&mut self, diff: InterfaceStats) {
-        self.current += 1;
-        for (metric, h) in self.data.iter_mut() {
-            for (i, d) in h.data.iter_mut().enumerate() {
-                let num = match &diff.0[i] {
-                    Some(stat) => match metric {
-                        Metric::Rx => stat.rx,
-                        Metric::Tx => stat.tx,
-                    },
-                    None => 0.into(),
-                };
-                let val = match num.to_f64() {
-                    Some(val) => val.max(1.0).log2(),
-                    None => 0.0,
-                };
-                d.push_back((f64::from(self.current), val));
-            }
-        }
-    }
-
-    fn push_back_pop_front(&mut self, diff: InterfaceStats) {
-        self.pop_front();
-        self.push_back(diff);
+        restore::install(libc::STDOUT_FILENO)?;
+        let terminal = B::setup()?;
+        let mut writer = TuiWriter::with_terminal(opt, info, initial_stats, terminal);
+        writer.restore_installed = true;
+        Ok(writer)
     }
 }
 
-type TuiBackend = TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<io::Stdout>>>>;
-
-pub struct TuiWriter {
-    terminal: Mutex<Terminal<TuiBackend>>,
-    info: InterfaceInfo,
-    n_histories: usize,
-    input_thread: Option<thread::JoinHandle<()>>,
-    prev_stats: InterfaceStats,
-    history: History,
-}
-
-impl TuiWriter {
-    const Y_WINDOW: [f64; 2] = [10.0, 30.0]; // 1 KiB -- 1GiB in log scale
-    const COLORS: [Color; 7] = [
-        Color::Red,
-        Color::Green,
-        Color::Yellow,
-        Color::Blue,
-        Color::Magenta,
-        Color::Cyan,
-        Color::Gray,
-    ];
-
+impl<B: Backend> TuiWriter<B> {
     fn get_x_labels(&self) -> Vec<String> {
         (0..(self.n_histories + 1))
             .step_by(60)
@@ -143,70 +98,180 @@ impl TuiWriter {
             .collect()
     }
 
+    /// Bytes of 1 K, 1 M and 1 G in the units, e.g. 1 KiB -- 1 GiB or 1 kbit -- 1 Gbit
+    fn get_y_values(&self) -> [f64; 3] {
+        let unit = |exp: i32| self.units.base().powi(exp) * self.units.unit_bytes();
+        [unit(1), unit(2), unit(3)]
+    }
+
+    /// Bounds of the y axis in log scale, so that the labels fall on round values
+    fn get_y_bounds(&self) -> [f64; 2] {
+        let values = self.get_y_values();
+        [values[0].log2(), values[2].log2()]
+    }
+
     fn get_y_labels(&self) -> [String; 3] {
+        let values = self.get_y_values();
         [
-            format!("{}", NumBytes::from((1024 as u64).pow(1))),
-            format!("{}", NumBytes::from((1024 as u64).pow(2))),
-            format!("{}", NumBytes::from((1024 as u64).pow(3))),
+            self.units.format(values[0]),
+            self.units.format(values[1]),
+            self.units.format(values[2]),
         ]
     }
 
-    pub fn new(
-        opt: &Opt,
+    /// Creates a writer drawing on an already set up `terminal`, e.g. one backed by
+    /// `tui::backend::TestBackend`.
+    pub fn with_terminal(
+        opt: &LiveOpt,
         info: &InterfaceInfo,
         initial_stats: InterfaceStats,
-    ) -> Result<TuiWriter> {
-        let history = History::empty(&info, opt.n);
-        let info = info.clone();
-
-        let terminal = {
-            let stdout = io::stdout()
-                .into_raw_mode()
-                .expect("Failed to turn stdout into raw mode");
-            let stdout = MouseTerminal::from(stdout);
-            let stdout = AlternateScreen::from(stdout);
-            let backend = TermionBackend::new(stdout);
-            let mut terminal = Terminal::new(backend).expect("Failed to setup terminal backend");
-            terminal.hide_cursor().expect("Failed to hide cursor");
-            terminal
-        };
-        let terminal = Mutex::new(terminal);
-
-        Ok(TuiWriter {
-            terminal,
-            info,
+        terminal: Terminal<B>,
+    ) -> TuiWriter<B> {
+        TuiWriter {
+            terminal: Mutex::new(terminal),
+            info: info.clone(),
             n_histories: opt.n,
             input_thread: None,
+            input_running: Arc::new(AtomicBool::new(true)),
+            restore_installed: false,
+            last_diff: InterfaceStats::empty(initial_stats.0.len()),
             prev_stats: initial_stats,
-            history,
-        })
+            interval: Duration::from_secs(1),
+            history: History::empty(info, opt.n),
+            theme: Theme::from_opt(opt),
+            units: opt.units(),
+            alerts: vec![],
+            utilization: vec![None; info.0.len()],
+            threshold: opt.utilization_threshold,
+            show_details: Arc::new(AtomicBool::new(false)),
+            panels: Panels::new(info),
+        }
+    }
+
+    /// Lines of the detail popup: the flags, link settings and addresses of each interface.
+    fn details(&self) -> Vec<String> {
+        let mut lines = vec![];
+        for item in self.info.0.iter() {
+            let mut header = format!("{}: <{}>", item.name, flag_names(item.flags).join(","));
+            let link = format_link(&item.link);
+            if !link.is_empty() {
+                header += &format!(" {}", link);
+            }
+            lines.push(header);
+            if let Some(mac) = &item.mac {
+                lines.push(format!("    ether {}", mac));
+            }
+            lines.extend(item.ipv4.iter().map(|addr| format!("    inet {}", addr)));
+            lines.extend(item.ipv6.iter().map(|addr| format!("    inet6 {}", addr)));
+        }
+        lines
     }
 
     fn update_history(&mut self, stats: InterfaceStats) {
-        let diff = &stats - &self.prev_stats;
+        let diff = (&stats - &self.prev_stats).per_sec(self.interval);
         self.prev_stats = stats;
+        self.last_diff = diff.clone();
+        self.utilization = diff
+            .0
+            .iter()
+            .zip(self.info.0.iter())
+            .map(|(stat, item)| {
+                stat.as_ref()
+                    .and_then(|stat| Utilization::new(stat, &item.link))
+            })
+            .collect();
         self.history.push_back_pop_front(diff);
     }
 
+    /// Returns the utilization of the `index`-th interface in `metric`, if its speed is known.
+    fn utilization_of(&self, metric: Metric, index: usize) -> Option<f64> {
+        self.utilization[index].map(|utilization| match metric {
+            Metric::Rx => utilization.rx,
+            Metric::Tx => utilization.tx,
+        })
+    }
+
+    /// Returns the links above the utilization threshold in `metric`, e.g. `eth0 92%`.
+    fn busy_links(&self, metric: Metric) -> Vec<String> {
+        self.info
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| match self.utilization_of(metric, i) {
+                Some(utilization) if utilization > self.threshold => {
+                    Some(format!("{} {:.0}%", item.name, utilization))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the firing alerts to show in the chart of `metric`.
+    fn alerts_for(&self, metric: Metric) -> Vec<&FiringAlert> {
+        self.alerts
+            .iter()
+            .filter(|a| {
+                matches!(
+                    (a.rule.metric, metric),
+                    (AlertMetric::Rx, Metric::Rx)
+                        | (AlertMetric::Tx, Metric::Tx)
+                        | (AlertMetric::Drops, _)
+                )
+            })
+            .collect()
+    }
+
     fn draw(&self) -> Result<()> {
         let mut terminal = self.terminal.lock().expect("Failed to aquire lock");
         terminal.draw(|mut f| {
+            let panel = self.panels.shown();
+            let constraints = match panel {
+                None => vec![Constraint::Percentage(50), Constraint::Percentage(50)],
+                _ => vec![
+                    Constraint::Percentage(35),
+                    Constraint::Percentage(35),
+                    Constraint::Percentage(30),
+                ],
+            };
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .constraints(constraints)
                 .split(f.size());
-            let datasets: Vec<Vec<Dataset>> = Metric::variants()
+            let names: Vec<Vec<String>> = Metric::variants()
                 .iter()
                 .map(|&metric| {
+                    let alerts = self.alerts_for(metric);
                     self.info
                         .0
                         .iter()
                         .enumerate()
                         .map(|(i, item)| {
+                            let mut name = if alerts.iter().any(|a| a.index == i) {
+                                format!("! {}", item.name)
+                            } else {
+                                item.name.clone()
+                            };
+                            if let Some(utilization) = self.utilization_of(metric, i) {
+                                name += &format!(" {:.0}%", utilization);
+                            }
+                            name
+                        })
+                        .collect()
+                })
+                .collect();
+            let datasets: Vec<Vec<Dataset>> = Metric::variants()
+                .iter()
+                .enumerate()
+                .map(|(l, &metric)| {
+                    self.info
+                        .0
+                        .iter()
+                        .enumerate()
+                        .map(|(i, _)| {
                             Dataset::default()
-                                .name(&item.name)
+                                .name(&names[l][i])
                                 .marker(Marker::Dot)
-                                .style(Style::default().fg(Self::COLORS[i % Self::COLORS.len()]))
+                                .style(Style::default().fg(self.theme.color(&self.info, i)))
                                 .data(self.history.get_data(metric, i))
                         })
                         .collect()
@@ -214,19 +279,38 @@ impl TuiWriter {
                 .collect();
             let x_labels = self.get_x_labels();
             let y_labels = self.get_y_labels();
-            for (l, metric) in Metric::variants().iter().enumerate() {
+            let y_title = format!("{}/s", self.units.name());
+            for (l, &metric) in Metric::variants().iter().enumerate() {
+                let alerts = self.alerts_for(metric);
+                let (mut title, mut title_style) = if alerts.is_empty() {
+                    (format!("{}", metric), self.theme.title)
+                } else {
+                    let rules: Vec<String> = alerts
+                        .iter()
+                        .map(|a| format!("{}: {}", self.info.0[a.index].name, a.rule))
+                        .collect();
+                    let title = format!("{} [ALERT {}]", metric, rules.join(", "));
+                    (title, self.theme.alert)
+                };
+                let busy = self.busy_links(metric);
+                if !busy.is_empty() {
+                    title += &format!(" [{}]", busy.join(", "));
+                    if alerts.is_empty() {
+                        title_style = self.theme.highlight;
+                    }
+                }
                 Chart::default()
                     .block(
                         Block::default()
-                            .title(&format!("{}", metric))
-                            .title_style(Style::default().fg(Color::Cyan).modifier(Modifier::BOLD))
+                            .title(&title)
+                            .title_style(title_style)
                             .borders(Borders::ALL),
                     )
                     .x_axis(
                         Axis::default()
                             .title("")
-                            .style(Style::default().fg(Color::Gray))
-                            .labels_style(Style::default().modifier(Modifier::ITALIC))
+                            .style(self.theme.axis)
+                            .labels_style(self.theme.labels)
                             .bounds([
                                 f64::from(self.history.current - self.n_histories as i32),
                                 f64::from(self.history.current),
@@ -235,53 +319,351 @@ impl TuiWriter {
                     )
                     .y_axis(
                         Axis::default()
-                            .title("Bytes/s")
-                            .style(Style::default().fg(Color::Gray))
-                            .labels_style(Style::default().modifier(Modifier::ITALIC))
-                            .bounds(Self::Y_WINDOW)
+                            .title(&y_title)
+                            .style(self.theme.axis)
+                            .labels_style(self.theme.labels)
+                            .bounds(self.get_y_bounds())
                             .labels(&y_labels),
                     )
                     .datasets(&datasets[l])
                     .render(&mut f, chunks[l]);
             }
+            if panel.is_some() {
+                let view = View {
+                    theme: &self.theme,
+                    units: self.units,
+                    info: &self.info,
+                    last_diff: &self.last_diff,
+                };
+                self.panels.draw(&mut f, chunks[2], &view);
+            }
+            if self.show_details.load(Ordering::Relaxed) {
+                let lines = self.details();
+                let text: Vec<Text> = lines
+                    .iter()
+                    .map(|line| Text::raw(format!("{}\n", line)))
+                    .collect();
+                let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) + 2;
+                let area = centered(f.size(), width as u16, lines.len() as u16 + 2);
+                Clear.render(&mut f, area);
+                Paragraph::new(text.iter())
+                    .block(
+                        Block::default()
+                            .title("Interfaces (i to close)")
+                            .title_style(self.theme.title)
+                            .borders(Borders::ALL),
+                    )
+                    .style(self.theme.labels)
+                    .render(&mut f, area);
+            }
         })?;
         Ok(())
     }
 }
 
-impl Write for TuiWriter {
+impl<B: TuiBackend> Write for TuiWriter<B> {
     fn setup_shutdown(&mut self, callback: Box<dyn Fn() + 'static + Send>) -> Result<()> {
+        let keys: fn(Arc<AtomicBool>) -> Keys = B::keys;
+        let running = Arc::clone(&self.input_running);
+        let show_details = Arc::clone(&self.show_details);
+        let panels = self.panels.selector();
         let input_thread = thread::spawn(move || {
-            let stdin = io::stdin();
-            for event in stdin.keys() {
-                match event {
-                    Ok(key) => match key {
-                        Key::Ctrl('c') | Key::Char('q') => {
-                            (*callback)();
-                            break;
-                        }
-                        _ => {}
-                    },
-                    Err(_) => {}
+            for key in keys(running) {
+                match key {
+                    Key::Ctrl('c') | Key::Char('q') => {
+                        (*callback)();
+                        break;
+                    }
+                    // shown from the next update
+                    Key::Char('i') => {
+                        show_details.fetch_xor(true, Ordering::Relaxed);
+                    }
+                    Key::Char(c) => {
+                        panels.toggle(c);
+                    }
+                    _ => {}
                 }
             }
         });
         self.input_thread = Some(input_thread);
+        // the panels are only shown with the keys read by the input thread
+        self.panels.spawn();
+
+        Ok(())
+    }
+
+    fn update_alerts(&mut self, alerts: &[FiringAlert]) -> Result<()> {
+        self.alerts = alerts.to_vec();
+        Ok(())
+    }
 
+    fn update_interval(&mut self, interval: Duration) -> Result<()> {
+        self.interval = interval;
         Ok(())
     }
 
     fn update(&mut self, stats: InterfaceStats) -> Result<()> {
+        if self.restore_installed {
+            // the writer is created on the main thread and updated on its own thread
+            restore::set_thread();
+        }
         self.update_history(stats);
         self.draw()?;
         Ok(())
     }
 }
 
-impl Drop for TuiWriter {
+impl<B: Backend> Drop for TuiWriter<B> {
     fn drop(&mut self) {
+        self.input_running.store(false, Ordering::Relaxed);
         if let Some(thread) = self.input_thread.take() {
             thread.join().expect("Failed to shutdown tick thread");
         }
+        if self.restore_installed {
+            restore::uninstall();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::info;
+    use crate::utils::NumBytes;
+    use crate::writer::tui_backend::RawInput;
+    use crate::InterfaceStat;
+    use structopt::StructOpt;
+    use tui::backend::TestBackend;
+
+    impl TuiBackend for TestBackend {
+        fn setup() -> Result<Terminal<Self>> {
+            Ok(Terminal::new(TestBackend::new(80, 24))?)
+        }
+
+        /// Keys of a pipe which is never written to, blocking like a terminal without input
+        fn keys(running: Arc<AtomicBool>) -> Keys {
+            let (read_fd, write_fd) = nix::unistd::pipe().unwrap();
+            let input = RawInput::new(read_fd, running);
+            Box::new(std::iter::from_fn(move || {
+                let key = input.next_byte().map(|b| Key::Char(b as char));
+                if key.is_none() {
+                    nix::unistd::close(read_fd).unwrap_or(());
+                    nix::unistd::close(write_fd).unwrap_or(());
+                }
+                key
+            }))
+        }
+    }
+
+    fn stats(t: u64) -> InterfaceStats {
+        InterfaceStats(vec![
+            Some(InterfaceStat {
+                rx: NumBytes::from(t * 1024 * 1024),
+                tx: NumBytes::from(t * 1024 * 1024),
+                drops: 0,
+            }),
+            Some(InterfaceStat {
+                rx: NumBytes::from(t * t * 64 * 1024),
+                tx: NumBytes::from(t * 4 * 1024),
+                drops: 0,
+            }),
+        ])
+    }
+
+    struct StatsReader(InterfaceInfo);
+
+    impl crate::reader::Read for StatsReader {
+        fn get_info(&self) -> &InterfaceInfo {
+            &self.0
+        }
+
+        fn read(&self) -> InterfaceStats {
+            stats(1)
+        }
+    }
+
+    #[test]
+    fn test_drop_after_monitor_stops() {
+        let opt = LiveOpt::from_iter(&["mone"]);
+        let terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let writer = TuiWriter::with_terminal(&opt, &info(), stats(0), terminal);
+        let mut monitor = crate::monitor::Monitor::new(
+            Box::new(StatsReader(info())),
+            vec![("tui".to_string(), Box::new(writer))],
+        )
+        .with_count(1);
+        // the input thread waits for a key which is never pressed
+        let started_at = std::time::Instant::now();
+        monitor.run().unwrap();
+        assert!(started_at.elapsed() < Duration::from_secs(2));
+    }
+
+    fn render(width: u16, height: u16, n_ticks: u64) -> Vec<String> {
+        let opt = LiveOpt::from_iter(&["mone", "-n", "120"]);
+        let terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        let mut writer = TuiWriter::with_terminal(&opt, &info(), stats(0), terminal);
+        for t in 1..=n_ticks {
+            writer.update(stats(t)).unwrap();
+        }
+        let terminal = writer.terminal.lock().unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_snapshot() {
+        let expected: Vec<&str> = include_str!("snapshots/tui_two_interfaces.txt")
+            .lines()
+            .collect();
+        assert_eq!(render(64, 40, 8), expected);
+    }
+
+    #[test]
+    fn test_draw_labels_and_legend() {
+        let lines = render(64, 40, 1);
+        assert!(lines[0].starts_with("┌rx─"));
+        assert!(lines[20].starts_with("┌tx─"));
+        for &top in &[0, 20] {
+            assert!(lines[top + 1].contains("1.00 GiB│ Bytes/s"));
+            assert!(lines[top + 2].contains("│lo  │"));
+            assert!(lines[top + 3].contains("│eth0│"));
+            assert!(lines[top + 9].contains("1.00 MiB│"));
+            assert!(lines[top + 16].contains("1.00 KiB│"));
+            assert!(lines[top + 18].contains("0                        60"));
+        }
+    }
+
+    #[test]
+    fn test_default_y_bounds() {
+        let opt = LiveOpt::from_iter(&["mone", "-n", "120"]);
+        let terminal = Terminal::new(TestBackend::new(64, 40)).unwrap();
+        let writer = TuiWriter::with_terminal(&opt, &info(), stats(0), terminal);
+        assert_eq!(writer.get_y_bounds(), crate::writer::history::Y_WINDOW);
+    }
+
+    #[test]
+    fn test_draw_si_bits() {
+        let opt = LiveOpt::from_iter(&["mone", "-n", "120", "--bits", "--si"]);
+        let terminal = Terminal::new(TestBackend::new(64, 40)).unwrap();
+        let mut writer = TuiWriter::with_terminal(&opt, &info(), stats(0), terminal);
+        writer.update(stats(1)).unwrap();
+
+        let terminal = writer.terminal.lock().unwrap();
+        let buffer = terminal.backend().buffer();
+        let line =
+            |y: u16| -> String { (0..64).map(|x| buffer.get(x, y).symbol.as_str()).collect() };
+        assert!(line(1).contains("1.00 Gbit│ Bits/s"));
+        assert!(line(9).contains("1.00 Mbit│"));
+        assert!(line(16).contains("1.00 kbit│"));
+    }
+
+    #[test]
+    fn test_draw_alerts() {
+        let opt = LiveOpt::from_iter(&["mone", "-n", "120"]);
+        let terminal = Terminal::new(TestBackend::new(64, 40)).unwrap();
+        let mut writer = TuiWriter::with_terminal(&opt, &info(), stats(0), terminal);
+        let alerts = [FiringAlert {
+            rule: "rx > 1 KiB".parse().unwrap(),
+            index: 1,
+        }];
+        writer.update_alerts(&alerts).unwrap();
+        writer.update(stats(1)).unwrap();
+
+        let terminal = writer.terminal.lock().unwrap();
+        let buffer = terminal.backend().buffer();
+        let line =
+            |y: u16| -> String { (0..64).map(|x| buffer.get(x, y).symbol.as_str()).collect() };
+        assert!(line(0).starts_with("┌rx [ALERT eth0: rx > 1 KiB]─"));
+        assert_eq!(buffer.get(1, 0).style, Theme::from_opt(&opt).alert);
+        assert!(line(3).contains("│! eth0│"));
+        assert!(line(20).starts_with("┌tx─"));
+        assert!(line(23).contains("│eth0│"));
+    }
+
+    #[test]
+    fn test_draw_utilization() {
+        let opt = LiveOpt::from_iter(&["mone", "-n", "120", "--utilization-threshold", "50"]);
+        let mut info = info();
+        info.0[0].link.speed = Some(10);
+        info.0[1].link.speed = Some(100);
+        let terminal = Terminal::new(TestBackend::new(64, 40)).unwrap();
+        let mut writer = TuiWriter::with_terminal(&opt, &info, stats(0), terminal);
+        writer.update(stats(1)).unwrap();
+
+        let terminal = writer.terminal.lock().unwrap();
+        let buffer = terminal.backend().buffer();
+        let line =
+            |y: u16| -> String { (0..64).map(|x| buffer.get(x, y).symbol.as_str()).collect() };
+        assert!(line(0).starts_with("┌rx [lo 84%]─"));
+        assert_eq!(buffer.get(1, 0).style, Theme::from_opt(&opt).highlight);
+        assert!(line(2).contains("│lo 84% │"));
+        assert!(line(3).contains("│eth0 1%│"));
+        assert!(line(20).starts_with("┌tx [lo 84%]─"));
+    }
+
+    #[test]
+    fn test_draw_details() {
+        let opt = LiveOpt::from_iter(&["mone", "-n", "120"]);
+        let mut info = info();
+        info.0[1].ipv4 = vec!["192.168.0.2".parse().unwrap()];
+        info.0[1].mac = Some("52:54:00:12:34:56".to_string());
+        info.0[1].link.mtu = Some(1500);
+        let terminal = Terminal::new(TestBackend::new(64, 40)).unwrap();
+        let mut writer = TuiWriter::with_terminal(&opt, &info, stats(0), terminal);
+        writer.show_details.store(true, Ordering::Relaxed);
+        writer.update(stats(1)).unwrap();
+
+        let terminal = writer.terminal.lock().unwrap();
+        let buffer = terminal.backend().buffer();
+        let popup =
+            |y: u16| -> String { (17..46).map(|x| buffer.get(x, y).symbol.as_str()).collect() };
+        assert_eq!(popup(17), "┌Interfaces (i to close)────┐");
+        assert_eq!(popup(18), "│lo: <>                     │");
+        assert_eq!(popup(19), "│eth0: <> mtu 1500          │");
+        assert_eq!(popup(20), "│    ether 52:54:00:12:34:56│");
+        assert_eq!(popup(21), "│    inet 192.168.0.2       │");
+        assert_eq!(popup(22), "└───────────────────────────┘");
+    }
+
+    #[test]
+    fn test_draw_panel() {
+        let opt = LiveOpt::from_iter(&["mone", "-n", "120"]);
+        let terminal = Terminal::new(TestBackend::new(64, 40)).unwrap();
+        let writer = TuiWriter::with_terminal(&opt, &info(), stats(0), terminal);
+        let selector = writer.panels.selector();
+        selector.toggle('p');
+        writer.draw().unwrap();
+
+        let terminal = writer.terminal.lock().unwrap();
+        let buffer = terminal.backend().buffer();
+        let line =
+            |y: u16| -> String { (0..64).map(|x| buffer.get(x, y).symbol.as_str()).collect() };
+        assert!(line(0).starts_with("┌rx─"));
+        assert!(line(14).starts_with("┌tx─"));
+        // shown before the panel thread reads the processes
+        assert!(line(28).starts_with("┌Top talkers (p to close)─"));
+
+        selector.toggle('s');
+        assert_eq!(writer.panels.shown(), Some(1));
+        selector.toggle('s');
+        assert_eq!(writer.panels.shown(), None);
+        selector.toggle('x');
+        assert_eq!(writer.panels.shown(), None);
+    }
+
+    #[test]
+    fn test_draw_missing_stats() {
+        let opt = LiveOpt::from_iter(&["mone"]);
+        let terminal = Terminal::new(TestBackend::new(64, 40)).unwrap();
+        let mut writer =
+            TuiWriter::with_terminal(&opt, &info(), InterfaceStats::empty(2), terminal);
+        writer.update(InterfaceStats::empty(2)).unwrap();
+        writer.update(stats(1)).unwrap();
     }
 }
@@ -0,0 +1,1003 @@
+//! Views shown below the charts of the TUI writer.
+//!
+//! The readers of the panels are polled on their own thread, so that a slow read, e.g. of the
+//! sockets of every process, does not delay the charts.
+
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::Style;
+use tui::widgets::{Block, Borders, Paragraph, Row, Table, Text, Widget};
+use tui::Frame;
+
+use crate::reader::in_capture::{CaptureReader, ClassStat};
+use crate::reader::in_process::{ProcessReader, ProcessStat};
+use crate::reader::in_snmp::{ProtocolReader, ProtocolStat, COUNTERS};
+use crate::reader::in_socket::{SocketReader, SocketStat};
+use crate::reader::in_tc::{TcReader, TcStat};
+use crate::reader::in_wireless::{WirelessReader, WirelessStat};
+use crate::utils::Units;
+use crate::writer::theme::Theme;
+use crate::{InterfaceInfo, InterfaceStats, Result};
+
+/// The rates of the panels are the differences between two reads.
+const READ_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the panel thread checks whether it is stopped or another panel is shown
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A view below the charts, toggled with its key.
+pub trait Panel: Send + 'static {
+    type Stats: Send + 'static;
+
+    /// Key showing the panel, or hiding it if it is shown
+    const KEY: char;
+    /// Title of the block the panel is drawn in
+    const TITLE: &'static str;
+
+    /// Reads the stats. Called on the panel thread every second while the panel is shown.
+    fn read(&mut self) -> Result<Self::Stats>;
+
+    /// Called when the panel is hidden, so that its rates are counted from when it is shown
+    /// again.
+    fn reset(&mut self);
+
+    /// Draws the last stats read, or the error reading them, in `area`.
+    fn draw<B: Backend>(
+        stats: &std::result::Result<Self::Stats, String>,
+        f: &mut Frame<B>,
+        area: Rect,
+        view: &View,
+    );
+}
+
+/// State of the TUI writer the panels are drawn with.
+pub struct View<'a> {
+    pub theme: &'a Theme,
+    pub units: Units,
+    pub info: &'a InterfaceInfo,
+    /// Difference of the stats of the last second
+    pub last_diff: &'a InterfaceStats,
+}
+
+impl<'a> View<'a> {
+    /// Draws a table of `rows`, or the error reading them, in a block titled `title`.
+    fn draw_table<B: Backend>(
+        &self,
+        f: &mut Frame<B>,
+        area: Rect,
+        title: &str,
+        header: &[&str],
+        widths: &[u16],
+        rows: std::result::Result<Vec<Vec<String>>, &str>,
+    ) {
+        let block = Block::default()
+            .title(title)
+            .title_style(self.theme.title)
+            .borders(Borders::ALL);
+        match rows {
+            Ok(rows) => Table::new(header.iter(), rows.iter().map(|row| Row::Data(row.iter())))
+                .block(block)
+                .header_style(self.theme.labels)
+                .widths(widths)
+                .render(f, area),
+            Err(err) => {
+                let text = [Text::raw(err.to_string())];
+                Paragraph::new(text.iter())
+                    .block(block)
+                    .style(self.theme.alert)
+                    .render(f, area);
+            }
+        }
+    }
+
+    fn rate_width(&self) -> u16 {
+        self.units.display_width() as u16 + 2
+    }
+}
+
+type Slot<S> = Arc<Mutex<Option<std::result::Result<S, String>>>>;
+
+/// Reading half of a panel, owned by the panel thread.
+trait Poll: Send {
+    fn poll(&mut self, shown: bool);
+}
+
+struct Poller<P: Panel> {
+    panel: P,
+    stats: Slot<P::Stats>,
+}
+
+impl<P: Panel> Poll for Poller<P> {
+    fn poll(&mut self, shown: bool) {
+        if shown {
+            let stats = self.panel.read().map_err(|err| err.to_string());
+            *self.stats.lock().expect("Failed to aquire lock") = Some(stats);
+        } else if self
+            .stats
+            .lock()
+            .expect("Failed to aquire lock")
+            .take()
+            .is_some()
+        {
+            self.panel.reset();
+        }
+    }
+}
+
+/// Drawing half of a panel, owned by the writer.
+trait Draw<B: Backend>: Send {
+    fn draw(&self, f: &mut Frame<B>, area: Rect, view: &View);
+}
+
+struct Drawer<P: Panel> {
+    stats: Slot<P::Stats>,
+    panel: PhantomData<fn() -> P>,
+}
+
+impl<P: Panel, B: Backend> Draw<B> for Drawer<P> {
+    fn draw(&self, f: &mut Frame<B>, area: Rect, view: &View) {
+        match &*self.stats.lock().expect("Failed to aquire lock") {
+            Some(stats) => P::draw(stats, f, area, view),
+            // not read yet
+            None => Block::default()
+                .title(P::TITLE)
+                .title_style(view.theme.title)
+                .borders(Borders::ALL)
+                .render(f, area),
+        }
+    }
+}
+
+/// Selects the panel shown, shared with the input thread.
+#[derive(Clone)]
+pub struct Selector {
+    /// Index of the panel shown plus one, or 0 if none is
+    selected: Arc<AtomicUsize>,
+    keys: Vec<char>,
+}
+
+impl Selector {
+    /// Returns the index of the panel shown.
+    pub fn shown(&self) -> Option<usize> {
+        self.selected.load(Ordering::Relaxed).checked_sub(1)
+    }
+
+    /// Shows the panel of `key`, or hides it if it is already shown.
+    pub fn toggle(&self, key: char) {
+        if let Some(i) = self.keys.iter().position(|&k| k == key) {
+            let next = if self.shown() == Some(i) { 0 } else { i + 1 };
+            self.selected.store(next, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The panels of the TUI writer, at most one of them shown.
+pub struct Panels<B: Backend> {
+    selector: Selector,
+    drawers: Vec<Box<dyn Draw<B>>>,
+    /// Moved to the panel thread when it is spawned
+    pollers: Vec<Box<dyn Poll>>,
+    /// Cleared on drop to stop the panel thread
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl<B: Backend> Panels<B> {
+    /// Creates the panels, capturing packets on the interfaces of `info`.
+    pub fn new(info: &InterfaceInfo) -> Panels<B> {
+        let interfaces: Vec<String> = info.0.iter().map(|item| item.name.clone()).collect();
+        Panels::empty()
+            .with_panel(ProcessReader::new())
+            .with_panel(SocketReader::new())
+            .with_panel(ProtocolReader::new())
+            .with_panel(WirelessReader::new())
+            .with_panel(TcReader::new())
+            .with_panel(CaptureReader::new(&interfaces))
+    }
+
+    fn empty() -> Panels<B> {
+        Panels {
+            selector: Selector {
+                selected: Arc::new(AtomicUsize::new(0)),
+                keys: vec![],
+            },
+            drawers: vec![],
+            pollers: vec![],
+            running: Arc::new(AtomicBool::new(true)),
+            thread: None,
+        }
+    }
+
+    fn with_panel<P: Panel>(mut self, panel: P) -> Self {
+        let stats = Arc::new(Mutex::new(None));
+        self.selector.keys.push(P::KEY);
+        self.drawers.push(Box::new(Drawer::<P> {
+            stats: Arc::clone(&stats),
+            panel: PhantomData,
+        }));
+        self.pollers.push(Box::new(Poller { panel, stats }));
+        self
+    }
+
+    pub fn selector(&self) -> Selector {
+        self.selector.clone()
+    }
+
+    /// Returns the index of the panel shown.
+    pub fn shown(&self) -> Option<usize> {
+        self.selector.shown()
+    }
+
+    /// Starts reading the panel shown on the panel thread.
+    pub fn spawn(&mut self) {
+        let mut pollers = std::mem::take(&mut self.pollers);
+        let selector = self.selector();
+        let running = Arc::clone(&self.running);
+        self.thread = Some(thread::spawn(move || {
+            let mut last_read: Option<(Option<usize>, Instant)> = None;
+            while running.load(Ordering::Relaxed) {
+                let shown = selector.shown();
+                let due = match last_read {
+                    Some((prev, read_at)) => prev != shown || read_at.elapsed() >= READ_INTERVAL,
+                    None => true,
+                };
+                if due {
+                    for (i, poller) in pollers.iter_mut().enumerate() {
+                        poller.poll(shown == Some(i));
+                    }
+                    last_read = Some((shown, Instant::now()));
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        }));
+    }
+
+    /// Draws the panel shown, if any, in `area`.
+    pub fn draw(&self, f: &mut Frame<B>, area: Rect, view: &View) {
+        if let Some(i) = self.selector.shown() {
+            self.drawers[i].draw(f, area, view);
+        }
+    }
+}
+
+impl<B: Backend> Drop for Panels<B> {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("Failed to shutdown panel thread");
+        }
+    }
+}
+
+/// Top talkers
+impl Panel for ProcessReader {
+    type Stats = Vec<ProcessStat>;
+    const KEY: char = 'p';
+    const TITLE: &'static str = "Top talkers (p to close)";
+
+    fn read(&mut self) -> Result<Self::Stats> {
+        ProcessReader::read(self)
+    }
+
+    fn reset(&mut self) {
+        *self = ProcessReader::new();
+    }
+
+    /// Draws the processes with the most traffic.
+    fn draw<B: Backend>(
+        stats: &std::result::Result<Self::Stats, String>,
+        f: &mut Frame<B>,
+        area: Rect,
+        view: &View,
+    ) {
+        let rate = |bytes: u64| view.units.format_rate(bytes as f64);
+        let rows = stats.as_ref().map(|processes| {
+            processes
+                .iter()
+                .map(|p| {
+                    vec![
+                        p.pid.to_string(),
+                        p.name.clone(),
+                        p.tcp_sockets.to_string(),
+                        p.udp_sockets.to_string(),
+                        rate(p.rx),
+                        rate(p.tx),
+                    ]
+                })
+                .collect()
+        });
+        view.draw_table(
+            f,
+            area,
+            Self::TITLE,
+            &["PID", "PROCESS", "TCP", "UDP", "RX", "TX"],
+            &[7, 16, 4, 4, view.rate_width(), view.rate_width()],
+            rows.map_err(String::as_str),
+        );
+    }
+}
+
+/// TCP connections
+impl Panel for SocketReader {
+    type Stats = Vec<SocketStat>;
+    const KEY: char = 's';
+    const TITLE: &'static str = "Connections (s to close)";
+
+    fn read(&mut self) -> Result<Self::Stats> {
+        SocketReader::read(self)
+    }
+
+    fn reset(&mut self) {
+        *self = SocketReader::new();
+    }
+
+    /// Draws the TCP connections with the highest throughput.
+    fn draw<B: Backend>(
+        stats: &std::result::Result<Self::Stats, String>,
+        f: &mut Frame<B>,
+        area: Rect,
+        view: &View,
+    ) {
+        let rate = |bytes: u64| view.units.format_rate(bytes as f64);
+        let total = |bytes: u64| view.units.format(bytes as f64);
+        let rows = stats.as_ref().map(|sockets| {
+            sockets
+                .iter()
+                .map(|s| {
+                    vec![
+                        s.state.to_string(),
+                        s.local.to_string(),
+                        s.remote.to_string(),
+                        format!("{:.1}ms", s.rtt),
+                        s.cwnd.to_string(),
+                        s.retrans.to_string(),
+                        rate(s.rx),
+                        rate(s.tx),
+                        total(s.total.rx),
+                        total(s.total.tx),
+                    ]
+                })
+                .collect()
+        });
+        let (rate_width, total_width) = (view.rate_width(), view.units.display_width() as u16);
+        view.draw_table(
+            f,
+            area,
+            Self::TITLE,
+            &[
+                "STATE", "LOCAL", "REMOTE", "RTT", "CWND", "RETR", "RX", "TX", "RX TOTAL",
+                "TX TOTAL",
+            ],
+            &[
+                10,
+                21,
+                21,
+                8,
+                5,
+                5,
+                rate_width,
+                rate_width,
+                total_width,
+                total_width,
+            ],
+            rows.map_err(String::as_str),
+        );
+    }
+}
+
+/// Protocol counters
+impl Panel for ProtocolReader {
+    type Stats = Vec<ProtocolStat>;
+    const KEY: char = 'n';
+    const TITLE: &'static str = "Protocols (n to close)";
+
+    fn read(&mut self) -> Result<Self::Stats> {
+        ProtocolReader::read(self)
+    }
+
+    fn reset(&mut self) {
+        *self = ProtocolReader::new();
+    }
+
+    /// Draws the protocol counters, a row for each protocol.
+    fn draw<B: Backend>(
+        stats: &std::result::Result<Self::Stats, String>,
+        f: &mut Frame<B>,
+        area: Rect,
+        view: &View,
+    ) {
+        let rows = stats.as_ref().map(|stats| {
+            let mut rows: Vec<Vec<String>> = vec![];
+            let mut stats = stats.iter().peekable();
+            while let Some(first) = stats.peek() {
+                let protocol = first.protocol;
+                let mut row = vec![protocol.to_string()];
+                while let Some(stat) = stats.next_if(|stat| stat.protocol == protocol) {
+                    row.push(format!("{} {}/s", stat.name, stat.rate));
+                }
+                rows.push(row);
+            }
+            rows
+        });
+        let max_counters = COUNTERS.iter().map(|(_, names)| names.len()).max();
+        let mut widths = vec![8];
+        widths.extend(vec![22; max_counters.unwrap_or(0)]);
+        view.draw_table(
+            f,
+            area,
+            Self::TITLE,
+            &["PROTOCOL", "COUNTERS"],
+            &widths,
+            rows.map_err(String::as_str),
+        );
+    }
+}
+
+/// Link quality of wireless interfaces
+impl Panel for WirelessReader {
+    type Stats = Vec<WirelessStat>;
+    const KEY: char = 'w';
+    const TITLE: &'static str = "Wireless (w to close)";
+
+    fn read(&mut self) -> Result<Self::Stats> {
+        WirelessReader::read(self)
+    }
+
+    fn reset(&mut self) {
+        *self = WirelessReader::new();
+    }
+
+    /// Draws the link quality of the wireless interfaces, with their rx and tx rates.
+    fn draw<B: Backend>(
+        stats: &std::result::Result<Self::Stats, String>,
+        f: &mut Frame<B>,
+        area: Rect,
+        view: &View,
+    ) {
+        let rate = |name: &str, rx: bool| -> String {
+            let stat = view
+                .info
+                .0
+                .iter()
+                .position(|item| item.name == name)
+                .and_then(|i| view.last_diff.0[i].as_ref());
+            match stat {
+                Some(stat) if rx => view.units.format_rate(stat.rx.to_f64().unwrap_or(0.0)),
+                Some(stat) => view.units.format_rate(stat.tx.to_f64().unwrap_or(0.0)),
+                None => "-".to_string(),
+            }
+        };
+        let rows = stats.as_ref().map(|stats| {
+            stats
+                .iter()
+                .map(|w| {
+                    vec![
+                        w.name.clone(),
+                        format!("{:.0}/70", w.quality),
+                        format!("{:.0} dBm", w.level),
+                        w.noise
+                            .map_or("-".to_string(), |noise| format!("{:.0} dBm", noise)),
+                        w.bitrate.map_or("-".to_string(), |bitrate| {
+                            format!("{:.1} Mbit/s", bitrate as f64 / 1e6)
+                        }),
+                        w.retry_rate.to_string(),
+                        w.missed_beacons.to_string(),
+                        rate(&w.name, true),
+                        rate(&w.name, false),
+                    ]
+                })
+                .collect()
+        });
+        view.draw_table(
+            f,
+            area,
+            Self::TITLE,
+            &[
+                "INTERFACE",
+                "QUALITY",
+                "SIGNAL",
+                "NOISE",
+                "BITRATE",
+                "RETRY",
+                "MISSED",
+                "RX",
+                "TX",
+            ],
+            &[12, 7, 8, 8, 12, 6, 6, view.rate_width(), view.rate_width()],
+            rows.map_err(String::as_str),
+        );
+    }
+}
+
+/// Qdiscs and classes of traffic control
+impl Panel for TcReader {
+    type Stats = Vec<TcStat>;
+    const KEY: char = 't';
+    const TITLE: &'static str = "Traffic control (t to close)";
+
+    fn read(&mut self) -> Result<Self::Stats> {
+        TcReader::read(self)
+    }
+
+    fn reset(&mut self) {
+        *self = TcReader::new();
+    }
+
+    /// Draws the qdiscs and classes, with the rates of their counters and their queues.
+    fn draw<B: Backend>(
+        stats: &std::result::Result<Self::Stats, String>,
+        f: &mut Frame<B>,
+        area: Rect,
+        view: &View,
+    ) {
+        let rows = stats.as_ref().map(|stats| {
+            stats
+                .iter()
+                .map(|s| {
+                    let kind = if s.msg.class { "class" } else { "qdisc" };
+                    vec![
+                        s.interface.clone(),
+                        format!("{} {}", kind, s.msg.kind),
+                        s.msg.handle.to_string(),
+                        s.msg.parent.to_string(),
+                        view.units.format_rate(s.rate.bytes as f64),
+                        s.rate.packets.to_string(),
+                        s.rate.drops.to_string(),
+                        s.rate.overlimits.to_string(),
+                        view.units.format(f64::from(s.rate.backlog)),
+                        s.rate.qlen.to_string(),
+                    ]
+                })
+                .collect()
+        });
+        view.draw_table(
+            f,
+            area,
+            Self::TITLE,
+            &[
+                "INTERFACE",
+                "KIND",
+                "HANDLE",
+                "PARENT",
+                "SENT",
+                "PKTS",
+                "DROPS",
+                "OVERLIMITS",
+                "BACKLOG",
+                "QLEN",
+            ],
+            &[
+                12,
+                14,
+                9,
+                9,
+                view.rate_width(),
+                7,
+                7,
+                10,
+                view.units.display_width() as u16,
+                6,
+            ],
+            rows.map_err(String::as_str),
+        );
+    }
+}
+
+/// Traffic of the captured packets by protocol and port
+impl Panel for CaptureReader {
+    /// The classes, and the packets the kernel dropped
+    type Stats = (Vec<ClassStat>, u64);
+    const KEY: char = 'c';
+    const TITLE: &'static str = "Capture (c to close)";
+
+    fn read(&mut self) -> Result<Self::Stats> {
+        let classes = CaptureReader::read(self)?;
+        Ok((classes, self.drops()))
+    }
+
+    fn reset(&mut self) {
+        self.stop();
+    }
+
+    /// Draws a bar stacking the classes by their share of the traffic of the last second, above
+    /// the rates of each class.
+    fn draw<B: Backend>(
+        stats: &std::result::Result<Self::Stats, String>,
+        f: &mut Frame<B>,
+        area: Rect,
+        view: &View,
+    ) {
+        let (stats, drops) = match stats {
+            Ok(stats) => stats,
+            Err(err) => return view.draw_table(f, area, Self::TITLE, &[], &[], Err(err)),
+        };
+        let title = if *drops == 0 {
+            Self::TITLE.to_string()
+        } else {
+            format!("Capture, {} packets dropped (c to close)", drops)
+        };
+        let mut block = Block::default()
+            .title(&title)
+            .title_style(view.theme.title)
+            .borders(Borders::ALL);
+        block.render(f, area);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+            .split(block.inner(area));
+
+        let total: u64 = stats.iter().map(|s| s.rate.bytes()).sum();
+        let width = u64::from(chunks[0].width);
+        let mut end = 0;
+        let mut sum = 0;
+        let bar: Vec<Text> = stats
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| total > 0 && s.rate.bytes() > 0)
+            .map(|(i, s)| {
+                // rounds the ends rather than the widths, so that the bar fills the line
+                sum += s.rate.bytes();
+                let start = end;
+                end = (width * sum + total / 2) / total;
+                let style = Style::default().fg(view.theme.palette_color(i));
+                Text::styled("█".repeat((end - start) as usize), style)
+            })
+            .collect();
+        Paragraph::new(bar.iter()).render(f, chunks[0]);
+
+        let rate = |bytes: u64| view.units.format_rate(bytes as f64);
+        let total_of = |bytes: u64| view.units.format(bytes as f64);
+        let rows: Vec<Vec<String>> = stats
+            .iter()
+            .map(|s| {
+                let share = if total > 0 {
+                    format!("{:.0}%", s.rate.bytes() as f64 * 100.0 / total as f64)
+                } else {
+                    "-".to_string()
+                };
+                vec![
+                    s.class.to_string(),
+                    share,
+                    rate(s.rate.rx_bytes),
+                    rate(s.rate.tx_bytes),
+                    s.rate.packets.to_string(),
+                    total_of(s.total.rx_bytes),
+                    total_of(s.total.tx_bytes),
+                ]
+            })
+            .collect();
+        let total_width = view.units.display_width() as u16;
+        let header = ["CLASS", "SHARE", "RX", "TX", "PKTS", "RX TOTAL", "TX TOTAL"];
+        Table::new(
+            header.iter(),
+            rows.iter().enumerate().map(|(i, row)| {
+                Row::StyledData(row.iter(), Style::default().fg(view.theme.palette_color(i)))
+            }),
+        )
+        .header_style(view.theme.labels)
+        .widths(&[
+            10,
+            5,
+            view.rate_width(),
+            view.rate_width(),
+            7,
+            total_width.max(8),
+            total_width.max(8),
+        ])
+        .render(f, chunks[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::in_capture::ClassCounters;
+    use crate::reader::in_process::SocketBytes;
+    use crate::reader::in_tc::{Handle, TcCounters, TcMsg};
+    use crate::reader::packet::Class;
+    use crate::test_utils::info;
+    use crate::utils::NumBytes;
+    use crate::{InterfaceStat, LiveOpt};
+    use structopt::StructOpt;
+    use tui::backend::TestBackend;
+    use tui::buffer::Buffer;
+    use tui::Terminal;
+
+    fn theme() -> Theme {
+        Theme::from_opt(&LiveOpt::from_iter(&["mone"]))
+    }
+
+    /// Draws `stats` below the charts of a `width` x 40 terminal, where the TUI writer draws the
+    /// panels.
+    fn draw<P: Panel>(stats: std::result::Result<P::Stats, String>, width: u16) -> Buffer {
+        let theme = theme();
+        let last_diff = InterfaceStats(vec![
+            None,
+            Some(InterfaceStat {
+                rx: NumBytes::from(64 * 1024),
+                tx: NumBytes::from(4 * 1024),
+                drops: 0,
+            }),
+        ]);
+        let view = View {
+            theme: &theme,
+            units: Units::default(),
+            info: &info(),
+            last_diff: &last_diff,
+        };
+        let mut terminal = Terminal::new(TestBackend::new(width, 40)).unwrap();
+        terminal
+            .draw(|mut f| P::draw(&stats, &mut f, Rect::new(0, 28, width, 12), &view))
+            .unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn line(buffer: &Buffer, y: u16) -> String {
+        (0..buffer.area().width)
+            .map(|x| buffer.get(x, y).symbol.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_processes() {
+        let buffer = draw::<ProcessReader>(
+            Ok(vec![ProcessStat {
+                pid: 4242,
+                name: "curl".to_string(),
+                tcp_sockets: 2,
+                udp_sockets: 0,
+                rx: 3 * 1024 * 1024,
+                tx: 512,
+            }]),
+            64,
+        );
+        assert!(line(&buffer, 28).starts_with("┌Top talkers (p to close)─"));
+        assert_eq!(
+            line(&buffer, 29).trim_end(),
+            "│PID     PROCESS          TCP  UDP  RX         TX              │"
+        );
+        assert!(
+            line(&buffer, 31).starts_with("│4242    curl             2    0    3.00 MiB/s 512 B/s")
+        );
+    }
+
+    #[test]
+    fn test_draw_protocols() {
+        let stat = |protocol, name, rate| ProtocolStat {
+            protocol,
+            name,
+            total: 1000,
+            rate,
+        };
+        let buffer = draw::<ProtocolReader>(
+            Ok(vec![
+                stat("Ip", "InReceives", 1200),
+                stat("Tcp", "InSegs", 1000),
+                stat("Tcp", "RetransSegs", 53),
+            ]),
+            120,
+        );
+        assert!(line(&buffer, 28).starts_with("┌Protocols (n to close)─"));
+        assert!(line(&buffer, 31).starts_with("│Ip       InReceives 1200/s  "));
+        assert!(
+            line(&buffer, 32).starts_with("│Tcp      InSegs 1000/s          RetransSegs 53/s   ")
+        );
+    }
+
+    #[test]
+    fn test_draw_wireless() {
+        let buffer = draw::<WirelessReader>(
+            Ok(vec![WirelessStat {
+                name: "eth0".to_string(),
+                quality: 58.0,
+                level: -52.0,
+                noise: None,
+                retries: 300,
+                discarded: 0,
+                missed_beacons: 2,
+                bitrate: Some(866_700_000),
+                retry_rate: 29,
+            }]),
+            100,
+        );
+        assert!(line(&buffer, 28).starts_with("┌Wireless (w to close)─"));
+        assert!(line(&buffer, 29).starts_with(
+            "│INTERFACE    QUALITY SIGNAL   NOISE    BITRATE      RETRY  MISSED RX         TX "
+        ));
+        assert!(line(&buffer, 31).starts_with(
+            "│eth0         58/70   -52 dBm  -        866.7 Mbit/s 29     2      64.0 KiB/s 4.00 KiB/s "
+        ));
+    }
+
+    #[test]
+    fn test_draw_tc() {
+        let counters = TcCounters {
+            bytes: 12_500,
+            packets: 10,
+            drops: 3,
+            overlimits: 7,
+            requeues: 0,
+            qlen: 48,
+            backlog: 50016,
+        };
+        let buffer = draw::<TcReader>(
+            Ok(vec![TcStat {
+                interface: "eth0".to_string(),
+                msg: TcMsg {
+                    class: true,
+                    ifindex: 2,
+                    handle: Handle(0x10010),
+                    parent: Handle(0x10001),
+                    kind: "htb".to_string(),
+                    counters,
+                },
+                rate: counters,
+            }]),
+            120,
+        );
+        assert!(line(&buffer, 28).starts_with("┌Traffic control (t to close)─"));
+        assert!(line(&buffer, 29).starts_with(
+            "│INTERFACE    KIND           HANDLE    PARENT    SENT       PKTS    DROPS   OVERLIMITS BACKLOG  QLEN "
+        ));
+        assert!(line(&buffer, 31).starts_with(
+            "│eth0         class htb      1:10      1:1       12.2 KiB/s 10      3       7          48.8 KiB 48 "
+        ));
+    }
+
+    #[test]
+    fn test_draw_capture() {
+        let counters = |rx_bytes: u64, tx_bytes: u64, packets: u64| ClassCounters {
+            rx_bytes,
+            tx_bytes,
+            packets,
+        };
+        let classes = vec![
+            ClassStat {
+                class: Class::Tcp(443),
+                total: counters(3 << 20, 1 << 20, 4000),
+                rate: counters(2048, 1024, 5),
+            },
+            ClassStat {
+                class: Class::Udp(53),
+                total: counters(512, 1024, 12),
+                rate: counters(512, 512, 2),
+            },
+        ];
+        let buffer = draw::<CaptureReader>(Ok((classes.clone(), 0)), 120);
+        assert!(line(&buffer, 28).starts_with("┌Capture (c to close)─"));
+        // 3 KiB/s and 1 KiB/s of the 118 columns
+        assert_eq!(
+            line(&buffer, 29),
+            format!("│{}{}│", "█".repeat(89), "█".repeat(29))
+        );
+        assert_eq!(buffer.get(89, 29).style.fg, theme().palette_color(0));
+        assert_eq!(buffer.get(90, 29).style.fg, theme().palette_color(1));
+        assert!(line(&buffer, 30)
+            .starts_with("│CLASS      SHARE RX         TX         PKTS    RX TOTAL TX TOTAL "));
+        assert!(line(&buffer, 32)
+            .starts_with("│TCP/443    75%   2.00 KiB/s 1.00 KiB/s 5       3.00 MiB 1.00 MiB "));
+        assert!(line(&buffer, 33)
+            .starts_with("│UDP/53     25%   512 B/s    512 B/s    2        512 B   1.00 KiB "));
+
+        let buffer = draw::<CaptureReader>(Ok((classes, 7)), 120);
+        assert!(line(&buffer, 28).starts_with("┌Capture, 7 packets dropped (c to close)─"));
+    }
+
+    #[test]
+    fn test_draw_sockets() {
+        let buffer = draw::<SocketReader>(
+            Ok(vec![SocketStat {
+                state: "ESTAB",
+                local: "192.168.0.2:41154".parse().unwrap(),
+                remote: "[2001:db8::1]:443".parse().unwrap(),
+                rtt: 12.25,
+                cwnd: 10,
+                retrans: 3,
+                total: SocketBytes {
+                    rx: 1 << 30,
+                    tx: 1 << 20,
+                },
+                rx: 2 * 1024 * 1024,
+                tx: 1024,
+            }]),
+            160,
+        );
+        assert!(line(&buffer, 28).starts_with("┌Connections (s to close)─"));
+        assert!(line(&buffer, 29).starts_with(
+            "│STATE      LOCAL                 REMOTE                RTT      CWND  RETR  RX         TX         RX TOTAL TX TOTAL"
+        ));
+        assert!(line(&buffer, 31).starts_with(
+            "│ESTAB      192.168.0.2:41154     [2001:db8::1]:443     12.2ms   10    3     2.00 MiB/s 1.00 KiB/s 1.00 GiB 1.00 MiB"
+        ));
+
+        let buffer = draw::<SocketReader>(Err("Permission denied".to_string()), 160);
+        assert!(line(&buffer, 29).starts_with("│Permission denied"));
+    }
+
+    /// A panel counting its reads
+    struct Counter(u64);
+
+    impl Panel for Counter {
+        type Stats = u64;
+        const KEY: char = 'k';
+        const TITLE: &'static str = "Counter (k to close)";
+
+        fn read(&mut self) -> Result<u64> {
+            self.0 += 1;
+            Ok(self.0)
+        }
+
+        fn reset(&mut self) {
+            self.0 = 0;
+        }
+
+        fn draw<B: Backend>(
+            stats: &std::result::Result<u64, String>,
+            f: &mut Frame<B>,
+            area: Rect,
+            _view: &View,
+        ) {
+            let text = [Text::raw(format!("{:?}", stats))];
+            Paragraph::new(text.iter()).render(f, area);
+        }
+    }
+
+    #[test]
+    fn test_poll() {
+        let stats = Arc::new(Mutex::new(None));
+        let mut poller = Poller {
+            panel: Counter(0),
+            stats: Arc::clone(&stats),
+        };
+        poller.poll(false);
+        assert_eq!(*stats.lock().unwrap(), None);
+        poller.poll(true);
+        poller.poll(true);
+        assert_eq!(*stats.lock().unwrap(), Some(Ok(2)));
+        poller.poll(false);
+        assert_eq!(*stats.lock().unwrap(), None);
+        poller.poll(true);
+        assert_eq!(*stats.lock().unwrap(), Some(Ok(1)));
+    }
+
+    #[test]
+    fn test_panel_thread() {
+        let mut panels = Panels::<TestBackend>::empty().with_panel(Counter(0));
+        panels.spawn();
+        let selector = panels.selector();
+        let theme = theme();
+        let text = |panels: &Panels<TestBackend>| {
+            let view = View {
+                theme: &theme,
+                units: Units::default(),
+                info: &info(),
+                last_diff: &InterfaceStats::empty(2),
+            };
+            let mut terminal = Terminal::new(TestBackend::new(24, 3)).unwrap();
+            terminal
+                .draw(|mut f| panels.draw(&mut f, Rect::new(0, 0, 24, 3), &view))
+                .unwrap();
+            line(terminal.backend().buffer(), 0)
+        };
+
+        // read as soon as it is shown rather than at the next second
+        selector.toggle('k');
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(text(&panels).trim_end(), "Ok(1)");
+        selector.toggle('k');
+        assert_eq!(text(&panels).trim_end(), "");
+        thread::sleep(Duration::from_millis(500));
+        selector.toggle('k');
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(text(&panels).trim_end(), "Ok(1)");
+
+        // stops the thread
+        let started_at = Instant::now();
+        drop(panels);
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+    }
+}
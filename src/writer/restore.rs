@@ -0,0 +1,322 @@
+//! Restores the terminal when the TUI exits abnormally.
+//!
+//! The terminal libraries only restore the terminal when their screen objects are dropped,
+//! which does not happen on a panic outside the main thread or on a fatal signal. Here the
+//! original terminal attributes are saved before entering raw mode, and are restored by a
+//! panic hook and on `SIGTERM` / `SIGHUP`.
+//!
+//! Only a panic of the thread drawing the TUI exits the process. A panic of the main thread
+//! restores the terminal and lets the runtime exit, and panics of other threads are left to
+//! their owners.
+
+use nix::sys::termios::{self, SetArg, Termios};
+use signal_hook::consts::signal::{SIGHUP, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::{panic, process};
+
+use crate::Result;
+
+/// Leaves the alternate screen, disables mouse reporting and shows the cursor.
+const RESET_SEQUENCE: &[u8] = b"\x1b[?1049l\x1b[?1000l\x1b[?1002l\x1b[?1015l\x1b[?1006l\x1b[?25h";
+
+static SAVED_STATE: Mutex<Option<TerminalState>> = Mutex::new(None);
+
+static HOOKS_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Thread drawing the TUI, registered by `install` and `set_thread`
+static TUI_THREAD: Mutex<Option<ThreadId>> = Mutex::new(None);
+
+pub struct TerminalState {
+    fd: RawFd,
+    termios: Termios,
+}
+
+impl TerminalState {
+    pub fn save(fd: RawFd) -> Result<TerminalState> {
+        Ok(TerminalState {
+            fd,
+            termios: termios::tcgetattr(fd)?,
+        })
+    }
+
+    pub fn restore(&self) -> Result<()> {
+        nix::unistd::write(self.fd, RESET_SEQUENCE)?;
+        termios::tcsetattr(self.fd, SetArg::TCSANOW, &self.termios)?;
+        Ok(())
+    }
+}
+
+/// Restores the state saved by `install`, if any.
+pub fn restore() {
+    // The lock may be poisoned by the panic being handled.
+    let state = match SAVED_STATE.lock() {
+        Ok(state) => state,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if let Some(state) = state.as_ref() {
+        state.restore().unwrap_or(());
+    }
+}
+
+/// Registers the current thread as the one drawing the TUI, so that its panics exit the
+/// process.
+pub fn set_thread() {
+    let mut tui_thread = TUI_THREAD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *tui_thread = Some(thread::current().id());
+}
+
+/// Forgets the state saved by `install` after the TUI exited normally, so that later panics
+/// and signals leave the terminal alone.
+pub fn uninstall() {
+    SAVED_STATE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take();
+    TUI_THREAD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take();
+}
+
+/// Restores the terminal on a panic of the TUI thread or the main thread, and returns whether
+/// the panicking thread is the TUI thread.
+fn restore_on_panic() -> bool {
+    let current = thread::current();
+    let is_tui_thread = *TUI_THREAD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        == Some(current.id());
+    if is_tui_thread || current.name() == Some("main") {
+        restore();
+    }
+    is_tui_thread
+}
+
+/// Saves the current state of the terminal on `fd` and installs a panic hook and a `SIGTERM` /
+/// `SIGHUP` handler that restore it and exit with a non-zero code. The current thread is
+/// registered as the one drawing the TUI until `set_thread` is called from another one.
+///
+/// Must be called before the terminal is put into raw mode.
+pub fn install(fd: RawFd) -> Result<()> {
+    let state = TerminalState::save(fd)?;
+    SAVED_STATE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .replace(state);
+    set_thread();
+    // the hook and the handler stay installed after `uninstall`
+    if HOOKS_INSTALLED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let is_tui_thread = restore_on_panic();
+        default_hook(info);
+        // the other threads are joined by their owners, which handle the panic
+        if is_tui_thread {
+            process::exit(101);
+        }
+    }));
+
+    let mut signals = Signals::new([SIGTERM, SIGHUP])?;
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            restore();
+            eprintln!("mone: terminated by signal {}", signal);
+            process::exit(128 + signal);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::pty::openpty;
+    use nix::sys::signal::{kill, Signal};
+    use nix::sys::termios::LocalFlags;
+    use nix::unistd::{close, dup, read, Pid};
+    use std::env;
+    use std::fs::File;
+    use std::os::unix::io::FromRawFd;
+    use std::process::{Command, ExitStatus, Stdio};
+    use std::time::Duration;
+
+    /// Environment variable selecting what `child_process` does in a child.
+    const CHILD_ENV: &str = "MONE_TEST_RESTORE_CHILD";
+
+    /// Runs in a child spawned by `spawn_child` on a pty: installs the handlers, puts the
+    /// terminal into raw mode and then panics, uninstalls or waits for a signal. Does nothing in
+    /// the normal test run.
+    #[test]
+    fn child_process() {
+        let mode = match env::var(CHILD_ENV) {
+            Ok(mode) => mode,
+            Err(_) => return,
+        };
+        install(libc::STDOUT_FILENO).unwrap();
+        let mut raw = termios::tcgetattr(libc::STDOUT_FILENO).unwrap();
+        termios::cfmakeraw(&mut raw);
+        termios::tcsetattr(libc::STDOUT_FILENO, SetArg::TCSANOW, &raw).unwrap();
+
+        match mode.as_str() {
+            "panic" => {
+                thread::spawn(|| {
+                    set_thread();
+                    panic!("TUI thread panicked");
+                })
+                .join()
+                .unwrap_or(());
+                thread::sleep(Duration::from_secs(10));
+            }
+            "other-panic" => {
+                let res = thread::spawn(|| panic!("another thread panicked")).join();
+                assert!(res.is_err());
+                process::exit(0);
+            }
+            "uninstall" => {
+                uninstall();
+                kill(Pid::this(), Signal::SIGTERM).unwrap();
+                thread::sleep(Duration::from_secs(10));
+            }
+            _ => {
+                nix::unistd::write(libc::STDOUT_FILENO, b"ready").unwrap();
+                thread::sleep(Duration::from_secs(10));
+            }
+        }
+        process::exit(1);
+    }
+
+    /// Output of a child, the terminal attributes after it exited and its exit status
+    struct ChildResult {
+        output: Vec<u8>,
+        termios: Termios,
+        status: ExitStatus,
+    }
+
+    /// Runs `child_process` in `mode` in a child with a pty as its terminal. Sends `signal` to
+    /// the child once it is ready, if given.
+    fn spawn_child(mode: &str, signal: Option<Signal>) -> ChildResult {
+        let pty = openpty(None, None).unwrap();
+        let stdio = || Stdio::from(unsafe { File::from_raw_fd(dup(pty.slave).unwrap()) });
+        let test_name = format!(
+            "{}::child_process",
+            module_path!().split_once("::").unwrap().1
+        );
+        let mut child = Command::new(env::current_exe().unwrap())
+            .args([
+                test_name.as_str(),
+                "--exact",
+                "--nocapture",
+                "--test-threads=1",
+            ])
+            .env(CHILD_ENV, mode)
+            .stdin(stdio())
+            .stdout(stdio())
+            .stderr(stdio())
+            .spawn()
+            .unwrap();
+
+        let mut output = vec![];
+        if let Some(signal) = signal {
+            let mut buf = [0u8; 1024];
+            while !output.windows(5).any(|w| w == b"ready") {
+                let len = read(pty.master, &mut buf).unwrap();
+                output.extend_from_slice(&buf[..len]);
+            }
+            kill(Pid::from_raw(child.id() as i32), signal).unwrap();
+        }
+        let status = child.wait().unwrap();
+        let termios = termios::tcgetattr(pty.slave).unwrap();
+
+        // reading fails with EIO once every slave is closed
+        close(pty.slave).unwrap();
+        let mut buf = [0u8; 1024];
+        while let Ok(len) = read(pty.master, &mut buf) {
+            if len == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..len]);
+        }
+        close(pty.master).unwrap();
+
+        ChildResult {
+            output,
+            termios,
+            status,
+        }
+    }
+
+    fn contains_reset(output: &[u8]) -> bool {
+        output
+            .windows(RESET_SEQUENCE.len())
+            .any(|w| w == RESET_SEQUENCE)
+    }
+
+    #[test]
+    fn test_restore_pty() {
+        let pty = openpty(None, None).unwrap();
+        let state = TerminalState::save(pty.slave).unwrap();
+        assert!(state.termios.local_flags.contains(LocalFlags::ICANON));
+
+        let mut raw = termios::tcgetattr(pty.slave).unwrap();
+        termios::cfmakeraw(&mut raw);
+        termios::tcsetattr(pty.slave, SetArg::TCSANOW, &raw).unwrap();
+        let flags = termios::tcgetattr(pty.slave).unwrap().local_flags;
+        assert!(!flags.contains(LocalFlags::ICANON));
+
+        state.restore().unwrap();
+        let flags = termios::tcgetattr(pty.slave).unwrap().local_flags;
+        assert!(flags.contains(LocalFlags::ICANON));
+        assert!(flags.contains(LocalFlags::ECHO));
+
+        let mut buf = [0u8; 64];
+        let len = read(pty.master, &mut buf).unwrap();
+        assert_eq!(&buf[..len], RESET_SEQUENCE);
+
+        close(pty.slave).unwrap();
+        close(pty.master).unwrap();
+    }
+
+    #[test]
+    fn test_restore_on_tui_thread_panic() {
+        let res = spawn_child("panic", None);
+        assert_eq!(res.status.code(), Some(101));
+        assert!(contains_reset(&res.output));
+        assert!(res.termios.local_flags.contains(LocalFlags::ICANON));
+    }
+
+    #[test]
+    fn test_keep_terminal_on_other_thread_panic() {
+        let res = spawn_child("other-panic", None);
+        assert_eq!(res.status.code(), Some(0));
+        assert!(!contains_reset(&res.output));
+        assert!(!res.termios.local_flags.contains(LocalFlags::ICANON));
+    }
+
+    #[test]
+    fn test_keep_terminal_after_uninstall() {
+        let res = spawn_child("uninstall", None);
+        assert_eq!(res.status.code(), Some(128 + Signal::SIGTERM as i32));
+        assert!(!contains_reset(&res.output));
+        assert!(!res.termios.local_flags.contains(LocalFlags::ICANON));
+    }
+
+    #[test]
+    fn test_restore_on_signal() {
+        for &signal in &[Signal::SIGTERM, Signal::SIGHUP] {
+            let res = spawn_child("signal", Some(signal));
+            assert_eq!(res.status.code(), Some(128 + signal as i32));
+            assert!(contains_reset(&res.output));
+            assert!(res.termios.local_flags.contains(LocalFlags::ICANON));
+        }
+    }
+}
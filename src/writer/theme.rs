@@ -0,0 +1,229 @@
+//! Colour themes for the TUI writer.
+
+use std::collections::HashMap;
+use std::{env, str};
+use tui::style::{Color, Modifier, Style};
+
+use crate::{Error, InterfaceInfo, LiveOpt, ThemeType};
+
+/// Colour assigned to a single interface, as given by `--color <name>=<color>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceColor {
+    pub name: String,
+    pub color: Color,
+}
+
+impl str::FromStr for InterfaceColor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<InterfaceColor, Error> {
+        let mut parts = s.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(color)) if !name.is_empty() => Ok(InterfaceColor {
+                name: name.to_string(),
+                color: parse_color(color)?,
+            }),
+            _ => Err(Error::ParseError(format!(
+                "expected <interface>=<color>, got `{}`",
+                s
+            ))),
+        }
+    }
+}
+
+/// Parses a colour name (`red`, `lightblue`, ...), a 256-colour index (`208`) or a hex
+/// triplet (`#ff8800`).
+pub fn parse_color(s: &str) -> Result<Color, Error> {
+    let color = match s.to_lowercase().as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.len() == 7
+            && hex.starts_with('#')
+            && hex[1..].bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+            match (channel(1), channel(3), channel(5)) {
+                (Ok(r), Ok(g), Ok(b)) => Color::Rgb(r, g, b),
+                _ => return Err(Error::ParseError(format!("invalid color `{}`", s))),
+            }
+        }
+        index => match index.parse::<u8>() {
+            Ok(i) => Color::Indexed(i),
+            Err(_) => return Err(Error::ParseError(format!("invalid color `{}`", s))),
+        },
+    };
+    Ok(color)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    palette: Vec<Color>,
+    overrides: HashMap<String, Color>,
+    pub title: Style,
+    pub axis: Style,
+    pub labels: Style,
+    /// Style of the titles of charts with firing alerts
+    pub alert: Style,
+    /// Style of the titles of charts with links above the utilization threshold
+    pub highlight: Style,
+}
+
+impl Theme {
+    /// Okabe-Ito palette, distinguishable with the common forms of colour blindness.
+    const COLORBLIND_PALETTE: [Color; 7] = [
+        Color::Rgb(230, 159, 0),
+        Color::Rgb(86, 180, 233),
+        Color::Rgb(0, 158, 115),
+        Color::Rgb(240, 228, 66),
+        Color::Rgb(0, 114, 178),
+        Color::Rgb(213, 94, 0),
+        Color::Rgb(204, 121, 167),
+    ];
+
+    pub fn new(theme_type: ThemeType) -> Theme {
+        let italic = Style::default().modifier(Modifier::ITALIC);
+        let bold = Style::default().modifier(Modifier::BOLD);
+        let (palette, title, axis, alert, highlight) = match theme_type {
+            ThemeType::dark => (
+                vec![
+                    Color::Red,
+                    Color::Green,
+                    Color::Yellow,
+                    Color::Blue,
+                    Color::Magenta,
+                    Color::Cyan,
+                    Color::Gray,
+                ],
+                bold.fg(Color::Cyan),
+                Style::default().fg(Color::Gray),
+                bold.fg(Color::LightRed),
+                bold.fg(Color::Yellow),
+            ),
+            ThemeType::light => (
+                vec![
+                    Color::Blue,
+                    Color::Red,
+                    Color::Green,
+                    Color::Magenta,
+                    Color::Black,
+                    Color::Cyan,
+                    Color::DarkGray,
+                ],
+                bold.fg(Color::Blue),
+                Style::default().fg(Color::Black),
+                bold.fg(Color::Red),
+                bold.fg(Color::Magenta),
+            ),
+            ThemeType::monochrome => (
+                vec![Color::Reset],
+                bold,
+                Style::default(),
+                bold.modifier(Modifier::BOLD | Modifier::REVERSED),
+                bold.modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            ),
+            ThemeType::colorblind => (
+                Self::COLORBLIND_PALETTE.to_vec(),
+                bold.fg(Color::Rgb(86, 180, 233)),
+                Style::default(),
+                bold.fg(Color::Rgb(213, 94, 0)),
+                bold.fg(Color::Rgb(240, 228, 66)),
+            ),
+        };
+        Theme {
+            palette,
+            overrides: HashMap::new(),
+            title,
+            axis,
+            labels: italic,
+            alert,
+            highlight,
+        }
+    }
+
+    /// Builds the theme selected in `opt`, falling back to `monochrome` when `NO_COLOR` is set.
+    ///
+    /// See https://no-color.org/ .
+    pub fn from_opt(opt: &LiveOpt) -> Theme {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        if no_color {
+            return Theme::new(ThemeType::monochrome);
+        }
+        Theme::new(opt.theme).with_overrides(&opt.colors)
+    }
+
+    pub fn with_overrides(mut self, colors: &[InterfaceColor]) -> Theme {
+        for c in colors {
+            self.overrides.insert(c.name.clone(), c.color);
+        }
+        self
+    }
+
+    /// Returns the colour of the `index`-th interface in `info`.
+    pub fn color(&self, info: &InterfaceInfo, index: usize) -> Color {
+        info.0
+            .get(index)
+            .and_then(|item| self.overrides.get(&item.name))
+            .cloned()
+            .unwrap_or_else(|| self.palette_color(index))
+    }
+
+    /// Returns the `index`-th colour of the palette, e.g. of a series other than an interface.
+    pub fn palette_color(&self, index: usize) -> Color {
+        self.palette[index % self.palette.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InterfaceInfoItem;
+
+    #[test]
+    fn test_parse_interface_color() {
+        let c: InterfaceColor = "eth0=lightblue".parse().unwrap();
+        assert_eq!(c.name, "eth0");
+        assert_eq!(c.color, Color::LightBlue);
+        let c: InterfaceColor = "wlan0=#ff8000".parse().unwrap();
+        assert_eq!(c.color, Color::Rgb(255, 128, 0));
+        let c: InterfaceColor = "lo=208".parse().unwrap();
+        assert_eq!(c.color, Color::Indexed(208));
+        assert!("eth0".parse::<InterfaceColor>().is_err());
+        assert!("eth0=purple".parse::<InterfaceColor>().is_err());
+        // 7 bytes, but not 6 hex digits
+        assert!("eth0=#aébcd".parse::<InterfaceColor>().is_err());
+        assert!("eth0=#+1+2+3".parse::<InterfaceColor>().is_err());
+    }
+
+    #[test]
+    fn test_theme_color() {
+        let info = InterfaceInfo(vec![
+            InterfaceInfoItem::new("lo"),
+            InterfaceInfoItem::new("eth0"),
+        ]);
+        let theme = Theme::new(ThemeType::dark).with_overrides(&[InterfaceColor {
+            name: "eth0".to_string(),
+            color: Color::White,
+        }]);
+        assert_eq!(theme.color(&info, 0), Color::Red);
+        assert_eq!(theme.color(&info, 1), Color::White);
+        assert_eq!(
+            Theme::new(ThemeType::monochrome).color(&info, 1),
+            Color::Reset
+        );
+    }
+}
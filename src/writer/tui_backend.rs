@@ -0,0 +1,204 @@
+//! Terminal libraries the TUI writer can run on.
+//!
+//! The library is selected with the `termion` (default) or `crossterm` cargo feature. If both
+//! are enabled, crossterm is used.
+
+use nix::poll::{poll, EventFlags, PollFd};
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tui::backend::Backend;
+use tui::Terminal;
+
+use crate::Result;
+
+/// A key press, independent of the terminal library.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    Other,
+}
+
+pub type Keys = Box<dyn Iterator<Item = Key>>;
+
+/// Terminal library used by `TuiWriter`.
+///
+/// `setup` puts the terminal into raw mode on the alternate screen. The returned terminal
+/// restores the original state when it is dropped.
+pub trait TuiBackend: Backend + Sized {
+    fn setup() -> Result<Terminal<Self>>;
+
+    /// Key presses read from stdin. Blocks until the next key press is available, and ends
+    /// once `running` is cleared.
+    fn keys(running: Arc<AtomicBool>) -> Keys;
+}
+
+/// Bytes read from a file descriptor without buffering, so that a blocked read can be given up
+/// when the TUI stops.
+pub struct RawInput {
+    fd: RawFd,
+    running: Arc<AtomicBool>,
+}
+
+impl RawInput {
+    /// Interval in milliseconds at which `running` is checked while no byte is available
+    const POLL_INTERVAL: i32 = 100;
+
+    pub fn new(fd: RawFd, running: Arc<AtomicBool>) -> RawInput {
+        RawInput { fd, running }
+    }
+
+    /// Returns whether a byte is available within `timeout` milliseconds.
+    fn poll(&self, timeout: i32) -> bool {
+        let mut fds = [PollFd::new(self.fd, EventFlags::POLLIN)];
+        match poll(&mut fds, timeout) {
+            Ok(n) => n > 0,
+            Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => false,
+            Err(_) => true,
+        }
+    }
+
+    fn read_byte(&self) -> Option<u8> {
+        let mut buf = [0u8];
+        match nix::unistd::read(self.fd, &mut buf) {
+            Ok(1) => Some(buf[0]),
+            _ => None,
+        }
+    }
+
+    /// Waits for the next byte. Returns `None` at the end of the input or once `running` is
+    /// cleared.
+    pub fn next_byte(&self) -> Option<u8> {
+        while self.running.load(Ordering::Relaxed) {
+            if self.poll(Self::POLL_INTERVAL) {
+                return self.read_byte();
+            }
+        }
+        None
+    }
+
+    /// The next byte if it is already available, e.g. the rest of an escape sequence.
+    pub fn available_byte(&self) -> Option<u8> {
+        if self.poll(0) {
+            self.read_byte()
+        } else {
+            None
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "crossterm")] {
+        pub type DefaultTuiBackend = tui::backend::CrosstermBackend;
+    } else if #[cfg(feature = "termion")] {
+        pub type DefaultTuiBackend = self::termion_backend::TermionTuiBackend;
+    } else {
+        compile_error!("Either the `termion` or the `crossterm` feature must be enabled.");
+    }
+}
+
+#[cfg(feature = "termion")]
+pub mod termion_backend {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::{io, iter};
+    use termion::event::{self, Event};
+    use termion::input::MouseTerminal;
+    use termion::raw::{IntoRawMode, RawTerminal};
+    use termion::screen::AlternateScreen;
+    use tui::backend::TermionBackend;
+    use tui::Terminal;
+
+    use super::{Key, Keys, RawInput, TuiBackend};
+    use crate::Result;
+
+    pub type TermionTuiBackend =
+        TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<io::Stdout>>>>;
+
+    impl TuiBackend for TermionTuiBackend {
+        fn setup() -> Result<Terminal<Self>> {
+            let stdout = io::stdout().into_raw_mode()?;
+            let stdout = MouseTerminal::from(stdout);
+            let stdout = AlternateScreen::from(stdout);
+            let mut terminal = Terminal::new(TermionBackend::new(stdout))?;
+            terminal.hide_cursor()?;
+            Ok(terminal)
+        }
+
+        fn keys(running: Arc<AtomicBool>) -> Keys {
+            let input = RawInput::new(libc::STDIN_FILENO, running);
+            Box::new(iter::from_fn(move || {
+                let byte = input.next_byte()?;
+                let mut rest = iter::from_fn(|| input.available_byte().map(Ok));
+                Some(match event::parse_event(byte, &mut rest) {
+                    Ok(Event::Key(event::Key::Char(c))) => Key::Char(c),
+                    Ok(Event::Key(event::Key::Ctrl(c))) => Key::Ctrl(c),
+                    _ => Key::Other,
+                })
+            }))
+        }
+    }
+}
+
+#[cfg(feature = "crossterm")]
+pub mod crossterm_backend {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::{iter, str};
+    use tui::backend::CrosstermBackend;
+    use tui::Terminal;
+
+    use super::{Key, Keys, RawInput, TuiBackend};
+    use crate::Result;
+
+    /// Maps a character read in raw mode to a key, e.g. `'\u{3}'` to `Ctrl('c')`.
+    fn key_from_char(c: char) -> Key {
+        match c as u32 {
+            1..=26 => Key::Ctrl((b'a' + c as u8 - 1) as char),
+            _ if c.is_control() => Key::Other,
+            _ => Key::Char(c),
+        }
+    }
+
+    impl TuiBackend for CrosstermBackend {
+        fn setup() -> Result<Terminal<Self>> {
+            let screen = crossterm::Screen::default();
+            let alternate_screen = screen.enable_alternate_modes(true)?;
+            let mut terminal =
+                Terminal::new(CrosstermBackend::with_alternate_screen(alternate_screen)?)?;
+            terminal.hide_cursor()?;
+            Ok(terminal)
+        }
+
+        fn keys(running: Arc<AtomicBool>) -> Keys {
+            let input = RawInput::new(libc::STDIN_FILENO, running);
+            Box::new(iter::from_fn(move || {
+                let byte = input.next_byte()?;
+                // the continuation bytes of a UTF-8 character
+                let len = match byte {
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf7 => 4,
+                    _ => 1,
+                };
+                let mut buf = vec![byte];
+                buf.extend((1..len).filter_map(|_| input.available_byte()));
+                let c = str::from_utf8(&buf).ok().and_then(|s| s.chars().next());
+                Some(c.map_or(Key::Other, key_from_char))
+            }))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_key_from_char() {
+            assert_eq!(key_from_char('q'), Key::Char('q'));
+            assert_eq!(key_from_char('\u{3}'), Key::Ctrl('c'));
+            assert_eq!(key_from_char('\u{1b}'), Key::Other);
+        }
+    }
+}